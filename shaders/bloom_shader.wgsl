@@ -0,0 +1,84 @@
+#define_import_path spark::bloom_shader
+
+// Glow post-processing, ported from blur_shader.frag and texture_merge_shader.frag.
+// The emission target is blurred horizontally into a downscaled texture, then vertically,
+// then added over the scene.
+
+// world::BLUR_SCALE, the blur targets are 1 / BLUR_SCALE of the emission target
+const BLUR_SCALE: f32 = f32(#{BLUR_SCALE});
+
+@group(0) @binding(0) var image_texture: texture_2d<f32>;
+@group(0) @binding(1) var image_sampler: sampler;
+
+// only used by the composite
+@group(1) @binding(0) var glow_texture: texture_2d<f32>;
+@group(1) @binding(1) var glow_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// One triangle covering the screen
+@vertex fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+
+    result.position = vec4<f32>(x, y, 0.0, 1.0);
+    result.tex_coords = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+
+    return result;
+}
+
+// The horizontal pass reads the full size emission target, so steps are scaled to downscaled texels
+@fragment fn fs_blur_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = BLUR_SCALE / f32(textureDimensions(image_texture).x);
+    return blur(in.tex_coords, vec2<f32>(texel, 0.0));
+}
+
+@fragment fn fs_blur_vertical(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / f32(textureDimensions(image_texture).y);
+    return blur(in.tex_coords, vec2<f32>(0.0, texel));
+}
+
+fn blur(tex_coords: vec2<f32>, texel_step: vec2<f32>) -> vec4<f32> {
+    var weight = array<f32, 28>(
+        0.049835, 0.049448, 0.048304, 0.046456, 0.043987, 0.041004, 0.037631, 0.034002, 0.030246, 0.026489,
+        0.022839, 0.019388, 0.016203, 0.013331, 0.010799, 0.008612, 0.006762, 0.005227, 0.003978, 0.00298,
+        0.002199, 0.001597, 0.001142, 0.000804, 0.000557, 0.00038, 0.000255, 0.000169
+    );
+
+    var result = textureSample(image_texture, image_sampler, tex_coords).rgb * weight[0];
+
+    for (var i = 1; i < 28; i++) {
+        let offset = texel_step * f32(i);
+        result += textureSample(image_texture, image_sampler, tex_coords + offset).rgb * weight[i];
+        result += textureSample(image_texture, image_sampler, tex_coords - offset).rgb * weight[i];
+    }
+
+    return vec4<f32>(result, 1.0);
+}
+
+fn calc_brightness(color: vec3<f32>) -> f32 {
+    return (color.x + color.y + color.z) / 3.0;
+}
+
+@fragment fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = vec4<f32>(textureSample(image_texture, image_sampler, in.tex_coords).rgb, 1.0);
+
+    let raw_bright = textureSample(glow_texture, glow_sampler, in.tex_coords).rgb;
+    let brightness = calc_brightness(raw_bright);
+
+    if (brightness > 0.05) {
+        let mult = 1.5;
+        var additive = 0.4;
+        if (brightness > 0.3) {
+            additive = 1.8;
+        }
+        color += vec4<f32>(mult * raw_bright + vec3<f32>(2.0 * additive, 0.6 * additive, 0.6 * additive), 1.0);
+    }
+
+    return color;
+}
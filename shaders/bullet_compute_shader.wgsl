@@ -0,0 +1,156 @@
+#define_import_path spark::bullet_compute_shader
+
+// Bullet simulation and bullet vs enemy collision.
+// Mirrors BulletStore::update_bullets, see src/render/bullet_compute.rs
+
+struct BulletSimParams {
+    delta_time: f32,
+    bullet_count: u32,
+    group_count: u32,
+    enemy_count: u32,
+    max_collision_distance: f32,
+    bullet_half_length: f32,
+    enemy_half_length: f32,
+    hit_distance: f32,
+};
+
+struct Bullet {
+    position: vec3<f32>,
+    group: u32,
+    direction: vec3<f32>,
+    speed: f32,
+};
+
+struct Enemy {
+    position: vec3<f32>,
+    _padding0: f32,
+    direction: vec3<f32>,
+    _padding1: f32,
+};
+
+@group(0) @binding(0) var<uniform> params: BulletSimParams;
+@group(0) @binding(1) var<storage, read_write> bullets: array<Bullet>;
+@group(0) @binding(2) var<storage, read_write> group_time_to_live: array<f32>;
+@group(0) @binding(3) var<storage, read> enemies: array<Enemy>;
+@group(0) @binding(4) var<storage, read_write> enemy_hits: array<atomic<u32>>;
+@group(0) @binding(5) var<storage, read_write> group_hits: array<atomic<u32>>;
+
+@compute @workgroup_size(64)
+fn age_groups(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.group_count) {
+        return;
+    }
+    group_time_to_live[index] -= params.delta_time;
+}
+
+@compute @workgroup_size(64)
+fn update_bullets(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.bullet_count) {
+        return;
+    }
+
+    var bullet = bullets[index];
+
+    if (group_time_to_live[bullet.group] <= 0.0) {
+        return;
+    }
+
+    bullet.position += bullet.direction * (params.delta_time * bullet.speed);
+    bullets[index].position = bullet.position;
+
+    let a0 = bullet.position - bullet.direction * params.bullet_half_length;
+    let a1 = bullet.position + bullet.direction * params.bullet_half_length;
+
+    for (var e = 0u; e < params.enemy_count; e++) {
+        let enemy = enemies[e];
+
+        if (distance(bullet.position, enemy.position) > params.max_collision_distance) {
+            continue;
+        }
+
+        let b0 = enemy.position - enemy.direction * params.enemy_half_length;
+        let b1 = enemy.position + enemy.direction * params.enemy_half_length;
+
+        if (distance_between_line_segments(a0, a1, b0, b1) <= params.hit_distance) {
+            atomicStore(&enemy_hits[e], 1u);
+            atomicStore(&group_hits[bullet.group], 1u);
+        }
+    }
+}
+
+// Port of geom::distance_between_line_segments
+fn distance_between_line_segments(a0: vec3<f32>, a1: vec3<f32>, b0: vec3<f32>, b1: vec3<f32>) -> f32 {
+    let eps = 0.001;
+
+    let mag_a = length(a1 - a0);
+    let mag_b = length(b1 - b0);
+
+    let a = (a1 - a0) / mag_a;
+    let b = (b1 - b0) / mag_b;
+
+    let cross_ab = cross(a, b);
+    let cl = length(cross_ab);
+    let denom = cl * cl;
+
+    // Parallel segments
+    if (denom < eps) {
+        let d0 = dot(a, b0 - a0);
+        let d1 = dot(a, b1 - a0);
+
+        // Is segment B before A?
+        if (d0 <= 0.0 && 0.0 >= d1) {
+            if (abs(d0) < abs(d1)) {
+                return length(a0 - b0);
+            }
+            return length(a0 - b1);
+        } else if (d0 >= mag_a && mag_a <= d1) {
+            if (abs(d0) < abs(d1)) {
+                return length(a1 - b0);
+            }
+            return length(a1 - b1);
+        }
+
+        // Segments overlap, return distance between parallel segments
+        return length(((d0 * a) + a0) - b0);
+    }
+
+    // Lines criss-cross: Calculate the projected closest points
+    let t = b0 - a0;
+    let det_a = determinant(mat3x3<f32>(t, b, cross_ab));
+    let det_b = determinant(mat3x3<f32>(t, a, cross_ab));
+
+    let t0 = det_a / denom;
+    let t1 = det_b / denom;
+
+    var p_a = a0 + (a * t0);
+    var p_b = b0 + (b * t1);
+
+    // Clamp projections
+    if (t0 < 0.0) {
+        p_a = a0;
+    } else if (t0 > mag_a) {
+        p_a = a1;
+    }
+
+    if (t1 < 0.0) {
+        p_b = b0;
+    } else if (t1 > mag_b) {
+        p_b = b1;
+    }
+
+    // Clamp projection A
+    if (t0 < 0.0 || t0 > mag_a) {
+        let d = clamp(dot(b, p_a - b0), 0.0, mag_b);
+        p_b = b0 + (b * d);
+    }
+
+    // Clamp projection B
+    if (t1 < 0.0 || t1 > mag_b) {
+        let d = clamp(dot(a, p_b - a0), 0.0, mag_a);
+        p_a = a0 + (a * d);
+    }
+
+    return length(p_a - p_b);
+}
@@ -1,5 +1,5 @@
 #define_import_path spark::bullet_shader
-#import spark::common::{CameraUniform};
+#import spark::common::{CameraUniform, FragmentOutput};
 
 // Bullet instances
 // shaders/instanced_texture_shader.vert
@@ -87,7 +87,7 @@ fn rotate_by_quat(v: vec3<f32>, q_orig: vec4<f32>) -> vec3<f32> {
     return result;
 }
 
-@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
     var color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords);
-    return color;
+    return FragmentOutput(color, color);
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+#define_import_path spark::burn_mark_shader
+#import spark::common::{CameraUniform, FragmentOutput};
+
+// Burn mark decals, one instance per mark, drawn flat on the floor
+
+// Just above the floor, the pipeline depth bias does the rest
+const BURN_MARK_Y: f32 = 0.01;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+// x, z, scale, alpha
+struct InstanceInput {
+    @location(2) mark: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) alpha: f32,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var mark_texture: texture_2d<f32>;
+@group(1) @binding(1) var mark_sampler: sampler;
+
+@vertex fn vs_main(vertex_in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var result: VertexOutput;
+
+    // unit square is in the xy plane, lay it down on xz
+    let scale = instance.mark.z;
+    let world_position = vec3<f32>(
+        instance.mark.x + vertex_in.position.x * scale,
+        BURN_MARK_Y,
+        instance.mark.y - vertex_in.position.y * scale
+    );
+
+    result.position = camera.projection * camera.view * vec4<f32>(world_position, 1.0);
+    result.tex_coords = vertex_in.tex_coords;
+    result.alpha = instance.mark.w;
+
+    return result;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var color = textureSample(mark_texture, mark_sampler, in.tex_coords);
+    color.a *= in.alpha;
+
+    // zero alpha leaves the glow under the mark alone
+    return FragmentOutput(color, vec4<f32>(0.0));
+}
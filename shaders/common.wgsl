@@ -29,11 +29,19 @@ struct DirectionLight {
 struct PointLight {
     world_position: vec4<f32>,
     color: vec4<f32>,
+    radius: f32,
+    falloff: f32,
 }
 
+// point_lights::MAX_POINT_LIGHTS
+const MAX_POINT_LIGHTS: i32 = #{MAX_POINT_LIGHTS};
+
 const MAX_BONES = 100;
 const MAX_BONE_INFLUENCE = 4;
 
+// shadow_cascades::SHADOW_CASCADES, at most 4 as the splits are a vec4
+const SHADOW_CASCADES: i32 = #{SHADOW_CASCADES};
+
 struct ModelTransforms {
     model_transform: mat4x4<f32>,
     node_transform: mat4x4<f32>,
@@ -42,19 +50,58 @@ struct ModelTransforms {
 
 struct ShaderParameters {
     direction_light: DirectionLight,
-    point_light: PointLight,
+    point_lights: array<PointLight, #{MAX_POINT_LIGHTS}>,
     model_rotation: mat4x4<f32>,
-    light_space_matrix: mat4x4<f32>,
+    light_space_matrices: array<mat4x4<f32>, #{SHADOW_CASCADES}>,
+    cascade_splits: vec4<f32>,
     view_position: vec4<f32>,
     ambient_color: vec4<f32>,
+    floor_light_color: vec4<f32>,
+    floor_ambient_color: vec4<f32>,
     time: f32,
     depth_mode: i32,
     use_light: i32,
-    use_point_light: i32,
+    num_point_lights: i32,
     use_emissive: i32,
     use_specular: i32,
 }
 
+// Forward pass targets, the scene and the glow that gets blurred over it
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) emission: vec4<f32>,
+}
+
+// Index of the shadow cascade covering a fragment view_depth in front of the camera
+fn select_cascade(view_depth: f32, cascade_splits: vec4<f32>) -> i32 {
+    for (var i = 0; i < SHADOW_CASCADES - 1; i++) {
+        if (view_depth <= cascade_splits[i]) {
+            return i;
+        }
+    }
+    return SHADOW_CASCADES - 1;
+}
+
+// Shadow map uv and depth for a light space position, flipping y from NDC to texture coordinates
+fn shadow_map_coords(light_space_position: vec4<f32>) -> vec3<f32> {
+    let ndc = light_space_position.xyz / light_space_position.w;
+    return vec3<f32>(ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5), ndc.z);
+}
+
+// Whether the coordinates fall inside the shadow map, outside is unshadowed
+fn in_shadow_map(coords: vec3<f32>) -> bool {
+    return all(coords.xy >= vec2<f32>(0.0)) && all(coords.xy <= vec2<f32>(1.0)) && coords.z <= 1.0;
+}
+
+// Diffuse light from a point light at a surface, fading to nothing at the light's radius
+fn point_light_diffuse(light: PointLight, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let to_light = light.world_position.xyz - world_position;
+    let distance = max(length(to_light), 0.0001);
+    let diff = max(dot(normal, to_light / distance), 0.0);
+    let attenuation = pow(clamp(1.0 - distance / light.radius, 0.0, 1.0), light.falloff);
+    return light.color.xyz * diff * attenuation;
+}
+
 struct AnimationOutput {
     position: vec4<f32>,
     local_normal: vec3<f32>,
@@ -1,4 +1,4 @@
-#define_import_path spark::debug_depth_shader
+#define_import_path spark::debug_test_shader
 #import spark::common::{CameraUniform};
 
 struct VertexInput {
@@ -1,5 +1,6 @@
 #define_import_path spark::floor_shader
-#import spark::common::{CameraUniform, DirectionLight, PointLight, ShaderParameters};
+#import spark::common::{CameraUniform, DirectionLight, PointLight, ShaderParameters, FragmentOutput};
+#import spark::common::{select_cascade, shadow_map_coords, in_shadow_map, point_light_diffuse};
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -34,11 +35,12 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_position: vec3<f32>,
-    @location(2) light_space_position: vec4<f32>,
+    @location(2) view_depth: f32,
 };
 
+// camera is the shadow cascade's light camera in the shadow pass
 @vertex fn vs_shadow(vertex_input: VertexInput) -> @builtin(position) vec4<f32> {
-    return params.light_space_matrix * model_transform * vec4<f32>(vertex_input.position, 1.0);
+    return camera.projection * camera.view * model_transform * vec4<f32>(vertex_input.position, 1.0);
 }
 
 // from basic_texture_shader.vert
@@ -52,16 +54,15 @@ struct VertexOutput {
     result.tex_coords = vertex_input.tex_coords;
 
     result.world_position = (model_transform * in_position).xyz;
-    result.light_space_position = params.light_space_matrix * vec4<f32>(result.world_position, 1.0);
+    result.view_depth = -(camera.view * vec4<f32>(result.world_position, 1.0)).z;
 
     return result;
 }
 
 
-@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
 
     var use_light = params.use_light;
-    var use_point_light = params.use_point_light;
     var use_emissive = params.use_emissive;
     var use_specular = params.use_specular;
 
@@ -78,29 +79,32 @@ struct VertexOutput {
 
         normal = normalize(normal * 2.0 - 1.0);
         var diff = max(dot(normal, lightDir), 0.0);
-        var amb = params.ambient_color.xyz * diffuse_color.xyz;
+        var amb = params.floor_ambient_color.xyz * diffuse_color.xyz;
 
         var bias = max(0.05 * (1.0 - dot(normal, lightDir)), 0.005);
 
         bias = 0.0002;
         var shadow = 0.0;
 
+        let cascade = select_cascade(in.view_depth, params.cascade_splits);
+        let light_space_position = params.light_space_matrices[cascade] * vec4<f32>(in.world_position, 1.0);
+
         for (var x = -1; x <= 1; x += 1) {
           for (var y = -1; y <= 1; y += 1) {
                 let offset = vec2<f32>(f32(x), f32(y)) * texelSize;
-                shadow += shadow_calculation(bias, in.light_space_position, offset);
+                shadow += shadow_calculation(bias, light_space_position, offset, cascade);
           }
         }
 
         shadow /= 9.0; // average
         shadow *= 0.9; // attenuate
 
-//        shadow = fetch_shadow(in.light_space_position, bias);
-//        shadow = shadow_calculation(0.0002, in.light_space_position, vec2<f32>(0.0, 0.0));
+//        shadow = fetch_shadow(light_space_position, bias);
+//        shadow = shadow_calculation(0.0002, light_space_position, vec2<f32>(0.0, 0.0), cascade);
 
 //        color = 0.7 * (1.0 - shadow) * params.direction_light.color * diffuse_color * diff + vec4<f32>(amb, 1.0);
 
-        color = (1.0 - shadow) * diffuse_color * params.direction_light.color + vec4<f32>(amb, 1.0);
+        color = (1.0 - shadow) * diffuse_color * params.floor_light_color + vec4<f32>(amb, 1.0);
 
         if (use_specular == 2) {
           var normal = vec3<f32>(0.0, 1.0, 0.0);
@@ -110,27 +114,19 @@ struct VertexOutput {
           var shininess = 0.7;
           var str = 1.0;//0.88;
           var spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
-          color += str * spec * textureSample(specular_texture, specular_sampler, in.tex_coords) * params.direction_light.color;
+          color += str * spec * textureSample(specular_texture, specular_sampler, in.tex_coords) * params.floor_light_color;
         }
 
-        if (use_point_light == 1) {
-          var lightDir = normalize(params.point_light.world_position.xyz - in.world_position);
-          var normal = vec3<f32>(0.0, 1.0, 0.0);
-          var diff = max(dot(normal, lightDir), 0.0);
-          var distance = length(params.point_light.world_position.xyz - in.world_position);
-          var linear_val = 0.5;
-          var constant = 0.0;
-          var quadratic = 3.0;
-          var attenuation = 1.0 / (constant + linear_val * distance + quadratic * (distance * distance));
-          var diffuse  = params.point_light.color.xyz  * diff * diffuse_color.xyz;
-          diffuse *= attenuation;
+        for (var i = 0; i < params.num_point_lights; i++) {
+          let floor_normal = vec3<f32>(0.0, 1.0, 0.0);
+          let diffuse = point_light_diffuse(params.point_lights[i], in.world_position, floor_normal) * diffuse_color.xyz;
           // needs to have the opposite effect for good flash shadows
           // color += vec4(diffuse.xyz, 1.0) * (1.0 - shadow * diff); // doesn't work
-          color += vec4<f32>(diffuse.xyz, 1.0);
+          color += vec4<f32>(diffuse, 0.0);
         }
     }
 
-    return color;
+    return FragmentOutput(color, vec4<f32>(0.0, 0.0, 0.0, 1.0));
 }
 
 //fn ShadowCalculation(bias: f32, fragPosLightSpace: vec4<f32>) -> f32 {
@@ -174,28 +170,16 @@ fn fetch_shadow(homogeneous_coords: vec4<f32>, bias: f32) -> f32 {
       return shadow;
 }
 
-fn shadow_calculation(bias: f32, frag_light_space_position: vec4<f32>, offset: vec2<f32>) -> f32 {
-
-  let proj_correction = frag_light_space_position.xyz / frag_light_space_position.w;
-  let flip_correction = vec2<f32>(0.5, -0.5);
-
-  let projCoords = proj_correction.xy * flip_correction + vec2<f32>(0.5, 0.5);
-  var currentDepth = proj_correction.z;
+fn shadow_calculation(bias: f32, frag_light_space_position: vec4<f32>, offset: vec2<f32>, cascade: i32) -> f32 {
 
-//  var projCoords = light_space_position.xyz / light_space_position.w;
-//  projCoords = projCoords * 0.5 + 0.5;
-
-  let shadow_depth = textureSample(shadow_map_texture, shadow_map_sampler, projCoords.xy + offset, 0);
+  let coords = shadow_map_coords(frag_light_space_position);
+  let currentDepth = coords.z;
 
-//  let shadow_depth = textureSample(shadow_map_texture, shadow_map_sampler, projCoords.xy + offset).z;
-
-//    let flip_correction = vec2<f32>(1.0, -1.0);
-//    let tex_coords = light_space_position * flip_correction + vec2<f32>(0.0, 1.0);
-//
-//    var value = textureSample(shadow_map_texture, shadow_map_sampler, tex_coords, 0);
+  // sampled before the range check, texture samples need uniform control flow
+  let shadow_depth = textureSample(shadow_map_texture, shadow_map_sampler, coords.xy + offset, cascade);
 
   var shadow = 0.0;
-  if (currentDepth - bias) > shadow_depth {
+  if in_shadow_map(coords) && (currentDepth - bias) > shadow_depth {
     shadow = 1.0;
   };
 
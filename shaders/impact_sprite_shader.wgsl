@@ -0,0 +1,62 @@
+#define_import_path spark::impact_sprite_shader
+#import spark::common::{CameraUniform, FragmentOutput};
+
+// Bullet impact explosions, one instance per sprite.
+// Quads are turned to face the camera and the sprite sheet frame comes from the age.
+
+// Half size of the quad in world units
+const IMPACT_SPRITE_SCALE: f32 = 2.0;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+// world position and age
+struct InstanceInput {
+    @location(2) position_age: vec4<f32>,
+}
+
+struct SpriteUniform {
+    number_of_columns: f32,
+    time_per_sprite: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) age: f32,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+
+@group(2) @binding(0) var<uniform> sprite: SpriteUniform;
+
+@vertex fn vs_main(vertex_in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var result: VertexOutput;
+
+    // camera right and up in world space are the first two rows of the view rotation
+    let camera_right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+    let camera_up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+
+    let offset = (camera_right * vertex_in.position.x + camera_up * vertex_in.position.y) * IMPACT_SPRITE_SCALE;
+    let world_position = instance.position_age.xyz + offset;
+
+    result.position = camera.projection * camera.view * vec4<f32>(world_position, 1.0);
+    result.tex_coords = vertex_in.tex_coords;
+    result.age = instance.position_age.w;
+
+    return result;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let col = min(floor(in.age / sprite.time_per_sprite), sprite.number_of_columns - 1.0);
+    let sprite_tex_coords = vec2<f32>((in.tex_coords.x + col) / sprite.number_of_columns, in.tex_coords.y);
+    let color = textureSample(sprite_texture, sprite_sampler, sprite_tex_coords);
+
+    // explosions glow
+    return FragmentOutput(color, color);
+}
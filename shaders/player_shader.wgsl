@@ -1,6 +1,7 @@
 #define_import_path spark::player_shader
-#import spark::common::{VertexInput, CameraUniform, DirectionLight, PointLight, ShaderParameters};
+#import spark::common::{VertexInput, CameraUniform, DirectionLight, PointLight, ShaderParameters, FragmentOutput};
 #import spark::common::{MAX_BONES, MAX_BONE_INFLUENCE, get_animated_position, AnimationOutput};
+#import spark::common::{select_cascade, shadow_map_coords, in_shadow_map, point_light_diffuse};
 
 
 // camera
@@ -35,12 +36,13 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
-    @location(3) light_space_position: vec4<f32>,
+    @location(3) view_depth: f32,
 };
 
+// camera is the shadow cascade's light camera in the shadow pass
 @vertex fn vs_shadow(vertex_input: VertexInput) -> @builtin(position) vec4<f32> {
     var anim_output = get_animated_position(vertex_input, node_transform, bone_transforms);
-    return params.light_space_matrix * model_transform * anim_output.position;
+    return camera.projection * camera.view * model_transform * anim_output.position;
 }
 
 @vertex fn vs_main(vertex_input: VertexInput) -> VertexOutput {
@@ -55,22 +57,22 @@ struct VertexOutput {
     result.normal = (params.model_rotation * vec4<f32>(vertex_input.normal, 1.0)).xyz;
 
     result.world_position = (model_transform * vec4<f32>(vertex_input.position, 1.0)).xyz;
-    result.light_space_position = params.light_space_matrix * vec4<f32>(result.world_position, 1.0);
+    result.view_depth = -(camera.view * vec4<f32>(result.world_position, 1.0)).z;
 
     return result;
 }
 
 // Fragment shader section
 
-@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
 
     var use_light = params.use_light;
-    var use_point_light = params.use_point_light;
     var use_emissive = params.use_emissive;
     var depth_mode = params.depth_mode;
     var time = params.time;
 
     var color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords);
+    var emission = vec4<f32>(0.0, 0.0, 0.0, 1.0);
 
       if (use_light == 1) {
 
@@ -88,16 +90,16 @@ struct VertexOutput {
           var bias: f32 = max(0.05 * (1.0 - dot(normal, lightDir)), 0.005);
 
           bias = 0.001;
-          shadow = ShadowCalculation(bias, in.light_space_position);
+          let cascade = select_cascade(in.view_depth, params.cascade_splits);
+          let light_space_position = params.light_space_matrices[cascade] * vec4<f32>(in.world_position, 1.0);
+          shadow = ShadowCalculation(bias, light_space_position, cascade);
 
           color = (1.0 - shadow) * params.direction_light.color * color * diff + vec4<f32>(amb, 1.0);
         }
 
-        if (use_point_light == 1) {
-          var lightDir = normalize(params.point_light.world_position.xyz - in.world_position);
-          var diff = max(dot(normal, lightDir), 0.0);
-          var diffuse  = 0.7 * params.point_light.color.xyz * diff * textureSample(diffuse_texture, diffuse_sampler, in.tex_coords).xyz;
-          color += vec4<f32>(diffuse.xyz, 1.0);
+        for (var i = 0; i < params.num_point_lights; i++) {
+          let diffuse = 0.7 * point_light_diffuse(params.point_lights[i], in.world_position, normal) * textureSample(diffuse_texture, diffuse_sampler, in.tex_coords).xyz;
+          color += vec4<f32>(diffuse, 0.0);
         }
 
         if (shadow < 0.1) {  // Spec
@@ -113,24 +115,23 @@ struct VertexOutput {
         }
 
         if (use_emissive == 1) {
-          var emission = textureSample(emissive_texture, emissive_sampler, in.tex_coords);//.rgb;
+          emission = textureSample(emissive_texture, emissive_sampler, in.tex_coords);//.rgb;
           color += emission;
         }
       }
 
-    return color;
+    return FragmentOutput(color, emission);
 }
 
-fn ShadowCalculation(bias: f32, fragPosLightSpace: vec4<f32>) -> f32 {
+fn ShadowCalculation(bias: f32, fragPosLightSpace: vec4<f32>, cascade: i32) -> f32 {
 
-  var projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
-  projCoords = projCoords * 0.5 + 0.5;
+  let coords = shadow_map_coords(fragPosLightSpace);
 
-  let shadowDepth = textureSample(shadow_map_texture, shadow_map_sampler, projCoords.xy, 0);
-  var currentDepth = projCoords.z;
+  let shadowDepth = textureSample(shadow_map_texture, shadow_map_sampler, coords.xy, cascade);
+  var currentDepth = coords.z;
 
   var shadow = 0.0;
-  if (currentDepth - bias) > shadowDepth {
+  if in_shadow_map(coords) && (currentDepth - bias) > shadowDepth {
     shadow = 1.0;
   };
 
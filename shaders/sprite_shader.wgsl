@@ -1,5 +1,5 @@
 #define_import_path spark::sprite_shader
-#import spark::common::{CameraUniform};
+#import spark::common::{CameraUniform, FragmentOutput};
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -44,9 +44,10 @@ struct VertexOutput {
     return result;
 }
 
-@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+// muzzle flashes glow with their own color
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
   var col = floor(in.age / sprite.time_per_sprite);
   var sprite_tex_coords = vec2<f32>(in.tex_coords.x / sprite.number_of_columns + col * (1 / sprite.number_of_columns), in.tex_coords.y);
   var color = textureSample(sprite_texture, sprite_sampler, sprite_tex_coords);
-  return color;
+  return FragmentOutput(color, color);
 }
\ No newline at end of file
@@ -1,13 +1,19 @@
-#define_import_path spark::player_shader
-#import spark::common::{VertexInput, CameraUniform, DirectionLight, PointLight, ShaderParameters};
+#define_import_path spark::wiggle_shader
+#import spark::common::{VertexInput, CameraUniform, DirectionLight, PointLight, ShaderParameters, FragmentOutput};
 #import spark::common::{MAX_BONES, MAX_BONE_INFLUENCE, get_animated_position, AnimationOutput};
+#import spark::common::{select_cascade, shadow_map_coords, in_shadow_map, point_light_diffuse};
 #import spark::common::{MONSTER_Y};
 
-const MAX_ENEMIES = 100;
-
-struct EnemyUniform {
-    model_transform: mat4x4<f32>,
-    model_rotation: mat4x4<f32>,
+// one per enemy, the columns of its transforms from the instance vertex buffer
+struct EnemyInstance {
+    @location(7) model_transform_0: vec4<f32>,
+    @location(8) model_transform_1: vec4<f32>,
+    @location(9) model_transform_2: vec4<f32>,
+    @location(10) model_transform_3: vec4<f32>,
+    @location(11) model_rotation_0: vec4<f32>,
+    @location(12) model_rotation_1: vec4<f32>,
+    @location(13) model_rotation_2: vec4<f32>,
+    @location(14) model_rotation_3: vec4<f32>,
 }
 
 // camera
@@ -21,16 +27,14 @@ struct EnemyUniform {
 // game and lighting
 @group(2) @binding(0) var<uniform> params: ShaderParameters;
 
-@group(3) @binding(0) var<uniform> enemy_uniforms: array<EnemyUniform, MAX_ENEMIES>;
-
 // material information
-@group(4) @binding(0) var diffuse_texture: texture_2d<f32>;
-@group(4) @binding(1) var diffuse_sampler: sampler;
+@group(3) @binding(0) var diffuse_texture: texture_2d<f32>;
+@group(3) @binding(1) var diffuse_sampler: sampler;
 
 // fyi: eeldog model also has height material, but not used.
 
-@group(5) @binding(0) var shadow_map_texture: texture_depth_2d_array;
-@group(5) @binding(1) var shadow_map_sampler: sampler;
+@group(4) @binding(0) var shadow_map_texture: texture_depth_2d_array;
+@group(4) @binding(1) var shadow_map_sampler: sampler;
 
 const wiggleMagnitude: f32 = 3.0;
 const wiggleDistModifier: f32 = 0.12;
@@ -44,25 +48,31 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
-    @location(3) light_space_position: vec4<f32>,
+    @location(3) view_depth: f32,
 };
 
-@vertex fn vs_shadow(in: VertexInput, @builtin(instance_index) index: u32) -> @builtin(position) vec4<f32> {
-    let enemy = enemy_uniforms[index];
+fn instance_transform(enemy: EnemyInstance) -> mat4x4<f32> {
+    return mat4x4<f32>(enemy.model_transform_0, enemy.model_transform_1, enemy.model_transform_2, enemy.model_transform_3);
+}
+
+fn instance_rotation(enemy: EnemyInstance) -> mat4x4<f32> {
+    return mat4x4<f32>(enemy.model_rotation_0, enemy.model_rotation_1, enemy.model_rotation_2, enemy.model_rotation_3);
+}
 
+// camera is the shadow cascade's light camera in the shadow pass
+@vertex fn vs_shadow(in: VertexInput, enemy: EnemyInstance) -> @builtin(position) vec4<f32> {
     let x_offset = sin(wiggleTimeModifier * params.time + wiggleDistModifier * distance(nose_position, in.position)) * wiggleMagnitude;
 
-    let position = params.light_space_matrix * enemy.model_transform * vec4<f32>(in.position.x + x_offset, in.position.y, in.position.z, 1.0);
+    let position = camera.projection * camera.view * instance_transform(enemy) * vec4<f32>(in.position.x + x_offset, in.position.y, in.position.z, 1.0);
     return position;
 }
 
-@vertex fn vs_main(in: VertexInput, @builtin(instance_index) index: u32) -> VertexOutput {
+@vertex fn vs_main(in: VertexInput, enemy: EnemyInstance) -> VertexOutput {
 
     var result: VertexOutput;
 
-    var enemy = enemy_uniforms[index];
-    var enemy_transform = enemy.model_transform;
-    var enemy_model_rotation = enemy.model_rotation;
+    var enemy_transform = instance_transform(enemy);
+    var enemy_model_rotation = instance_rotation(enemy);
 
     var time = params.time;
 
@@ -72,7 +82,7 @@ struct VertexOutput {
         result.position = camera.projection * camera.view * enemy_transform
                         * vec4<f32>(in.position.x + x_offset, in.position.y, in.position.z, 1.0);
    } else {
-        result.position = params.light_space_matrix * enemy_transform
+        result.position = params.light_space_matrices[0] * enemy_transform
                         * vec4<f32>(in.position.x + x_offset, in.position.y, in.position.z, 1.0);
    }
 
@@ -81,17 +91,16 @@ struct VertexOutput {
     result.normal = (enemy_model_rotation * vec4<f32>(in.normal, 1.0)).xyz;
 
     result.world_position = (enemy_transform * vec4<f32>(in.position, 1.0)).xyz;
-    result.light_space_position = params.light_space_matrix * vec4<f32>(result.world_position, 1.0);
+    result.view_depth = -(camera.view * vec4<f32>(result.world_position, 1.0)).z;
 
     return result;
 }
 
 // fragment
 
-@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+@fragment fn fs_main(in: VertexOutput) -> FragmentOutput {
 
     var use_light = params.use_light;
-    var use_point_light = params.use_point_light;
     var use_emissive = params.use_emissive;
     var depth_mode = params.depth_mode;
 
@@ -113,16 +122,16 @@ struct VertexOutput {
           var bias: f32 = max(0.05 * (1.0 - dot(normal, lightDir)), 0.005);
 
           bias = 0.001;
-          shadow = ShadowCalculation(bias, in.light_space_position);
+          let cascade = select_cascade(in.view_depth, params.cascade_splits);
+          let light_space_position = params.light_space_matrices[cascade] * vec4<f32>(in.world_position, 1.0);
+          shadow = ShadowCalculation(bias, light_space_position, cascade);
 
           color = (1.0 - shadow) * params.direction_light.color * color * diff + vec4<f32>(amb, 1.0);
         }
 
-        if (use_point_light != 0) {
-          var lightDir = normalize(params.point_light.world_position.xyz - in.world_position);
-          var diff = max(dot(normal, lightDir), 0.0);
-          var diffuse  = 0.7 * params.point_light.color.xyz  * diff * textureSample(diffuse_texture, diffuse_sampler, in.tex_coords).xyz;
-          color += vec4<f32>(diffuse.xyz, 1.0);
+        for (var i = 0; i < params.num_point_lights; i++) {
+          let diffuse = 0.7 * point_light_diffuse(params.point_lights[i], in.world_position, normal) * textureSample(diffuse_texture, diffuse_sampler, in.tex_coords).xyz;
+          color += vec4<f32>(diffuse, 0.0);
         }
 
         if (shadow < 0.1) {  // Spec
@@ -143,19 +152,18 @@ struct VertexOutput {
 //        }
       }
 
-    return color;
+    return FragmentOutput(color, vec4<f32>(0.0, 0.0, 0.0, 1.0));
 }
 
-fn ShadowCalculation(bias: f32, fragPosLightSpace: vec4<f32>) -> f32 {
+fn ShadowCalculation(bias: f32, fragPosLightSpace: vec4<f32>, cascade: i32) -> f32 {
 
-  var projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
-  projCoords = projCoords * 0.5 + 0.5;
+  let coords = shadow_map_coords(fragPosLightSpace);
 
-  let shadowDepth = textureSample(shadow_map_texture, shadow_map_sampler, projCoords.xy, 0);
-  var currentDepth = projCoords.z;
+  let shadowDepth = textureSample(shadow_map_texture, shadow_map_sampler, coords.xy, cascade);
+  var currentDepth = coords.z;
 
   var shadow = 0.0;
-  if (currentDepth - bias) > shadowDepth {
+  if in_shadow_map(coords) && (currentDepth - bias) > shadowDepth {
     shadow = 1.0;
   };
 
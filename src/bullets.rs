@@ -1,7 +1,9 @@
-use std::f32::consts::PI;
+use std::collections::VecDeque;
 use std::mem;
+use std::ops::Range;
 
-use glam::{vec3, vec4, Mat4, Quat, Vec3, Vec4Swizzles};
+use glam::{vec3, vec4, Mat4, Quat, Vec3, Vec4, Vec4Swizzles};
+use rayon::prelude::*;
 use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::Material;
 use spark_gap::texture_config::{TextureConfig, TextureFilter, TextureType, TextureWrap};
@@ -12,69 +14,136 @@ use crate::aabb::Aabb;
 use crate::capsule::Capsule;
 use crate::enemy::{Enemy, ENEMY_COLLIDER};
 use crate::geom::{distance_between_line_segments, oriented_angle};
-use crate::render::buffers::{create_vertex_buffer, create_vertex_buffer_init, update_uniform_buffer};
+use crate::render::buffers::{create_vertex_buffer, create_vertex_buffer_init, update_buffer_range, update_uniform_buffer};
 use crate::small_mesh::SmallMesh;
+use crate::spatial_hash::SpatialHash;
 use crate::sprite_sheet::{SpriteSheet, SpriteSheetSprite};
-use crate::world::{World, MAX_BULLET_GROUPS, SPREAD_AMOUNT};
+use crate::weapons::Weapon;
+use crate::world::BULLET_GROUPS_IN_FLIGHT;
 
 pub struct BulletGroup {
-    start_index: usize,
-    group_size: i32,
-    time_to_live: f32,
+    // slot of the group's first bullet
+    pub(crate) start_index: usize,
+    pub(crate) group_size: i32,
+    pub(crate) time_to_live: f32,
+    // whether any bullet of the group has hit an enemy
+    pub(crate) has_hit: bool,
+    // index of the weapon that fired the group
+    pub(crate) weapon: usize,
+    pub(crate) speed: f32,
 }
 
 impl BulletGroup {
-    pub const fn new(start_index: usize, group_size: i32, time_to_live: f32) -> Self {
+    pub const fn new(start_index: usize, group_size: i32, time_to_live: f32, weapon: usize, speed: f32) -> Self {
         Self {
             start_index,
             group_size,
             time_to_live,
+            has_hit: false,
+            weapon,
+            speed,
         }
     }
+
+    pub fn is_live(&self) -> bool {
+        self.time_to_live > 0.0
+    }
 }
 
-pub struct BulletSystem {
+/// CPU side bullet simulation state. Positions and rotations are uploaded by `BulletSystem`.
+///
+/// Bullets live in ring buffers of slots. Groups are fired at the tail and removed from the head,
+/// so the stored bullets are one run of slots that can wrap past the end of the storage, see `live_ranges`.
+/// Weapons have different lifetimes, so a group can expire while older ones are still flying. It stays
+/// in the ring until the groups before it are gone, and is skipped by the update, collisions and drawing.
+pub struct BulletStore {
     pub bullet_positions: Vec<Vec3>,
     pub bullet_rotations: Vec<Quat>,
     pub bullet_directions: Vec<Vec3>,
-    pub bullet_groups: Vec<BulletGroup>,
+    pub bullet_groups: VecDeque<BulletGroup>,
+    // slot of the oldest live bullet
+    head: usize,
+    len: usize,
+
+    /// Groups fired since the last clear that hit at least one enemy. Every group with a bullet touching an
+    /// enemy counts, whichever broadphase or the GPU found it, see `apply_hits`.
+    pub groups_hit: u32,
+    pub broadphase: Broadphase,
+    pub update_mode: BulletUpdateMode,
+    bullet_grid: SpatialHash,
+}
 
-    // fixed size calculation vecs
-    x_rotations: Vec<Quat>,
-    y_rotations: Vec<Quat>,
+/// How bullets are paired with enemies before the exact capsule test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Broadphase {
+    AabbSubGroups,
+    SpatialHash,
+}
+
+/// Whether bullets are advanced and tested on this thread or spread over the rayon pool.
+/// Both give the same kills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulletUpdateMode {
+    Serial,
+    Parallel,
+}
 
+impl BulletUpdateMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            BulletUpdateMode::Serial => BulletUpdateMode::Parallel,
+            BulletUpdateMode::Parallel => BulletUpdateMode::Serial,
+        }
+    }
+}
+
+/// GPU side of the bullets: mesh, material and instance buffers.
+pub struct BulletSystem {
     pub impact_mesh: SmallMesh,
-    pub bullet_material: Material,
+    // one per weapon, in weapon order
+    pub bullet_materials: Vec<Material>,
 
     pub impact_spritesheet: SpriteSheet,
-    pub impact_sprites: Vec<SpriteSheetSprite>,
 
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
 
+    // same slots as the BulletStore ring, reallocated when it grows
     pub bullet_positions_buffer: Buffer,
     pub bullet_rotations_buffer: Buffer,
+    instance_capacity: usize,
+
+    // position and age per impact sprite, grown when a burst of kills outnumbers it
+    pub impact_instances_buffer: Buffer,
+    impact_capacity: usize,
+
+    // scratch vec for the interpolated positions uploaded each frame
+    render_positions: Vec<Vec3>,
+    impact_instances: Vec<Vec4>,
 }
 
 // const BULLET_SCALE: f32 = 0.3;
 const BULLET_SCALE: f32 = 0.3;
-const BULLET_LIFETIME: f32 = 1.0;
-// seconds
-// const BULLET_SPEED: f32 = 15.0;
-const BULLET_SPEED: f32 = 5.0;
-// const BULLET_SPEED: f32 = 1.0;
-// Game units per second
-const ROTATION_PER_BULLET: f32 = 3.0 * PI / 180.0;
 
 const SCALE_VEC: Vec3 = vec3(BULLET_SCALE, BULLET_SCALE, BULLET_SCALE);
 const BULLET_NORMAL: Vec3 = vec3(0.0, 1.0, 0.0);
 const CANONICAL_DIR: Vec3 = vec3(0.0, 0.0, 1.0);
 
-const BULLET_COLLIDER: Capsule = Capsule { height: 0.3, radius: 0.03 };
+pub(crate) const BULLET_COLLIDER: Capsule = Capsule { height: 0.3, radius: 0.03 };
+
+pub(crate) const BULLET_ENEMY_MAX_COLLISION_DIST: f32 = BULLET_COLLIDER.height / 2.0 + BULLET_COLLIDER.radius + ENEMY_COLLIDER.height / 2.0 + ENEMY_COLLIDER.radius;
 
-const BULLET_ENEMY_MAX_COLLISION_DIST: f32 = BULLET_COLLIDER.height / 2.0 + BULLET_COLLIDER.radius + ENEMY_COLLIDER.height / 2.0 + ENEMY_COLLIDER.radius;
+// Smallest run of bullets worth handing to another thread
+const PARALLEL_MIN_BULLETS: usize = 256;
 
-const MAX_BULLETS: usize = (SPREAD_AMOUNT * SPREAD_AMOUNT * MAX_BULLET_GROUPS) as usize;
+// Enough for the shots in flight with the default weapon, the store grows past it
+pub(crate) const INITIAL_BULLET_CAPACITY: usize = 400 * BULLET_GROUPS_IN_FLIGHT as usize;
+
+pub const IMPACT_SPRITE_COLUMNS: f32 = 11.0;
+pub const IMPACT_TIME_PER_SPRITE: f32 = 0.05;
+pub const IMPACT_SPRITE_DURATION: f32 = IMPACT_SPRITE_COLUMNS * IMPACT_TIME_PER_SPRITE;
+// Enough impact sprites for most bursts of kills, the instance buffer grows past it
+const INITIAL_IMPACT_CAPACITY: usize = 100;
 
 // Trim off margin around the bullet image
 // const TEXTURE_MARGIN: f32 = 0.0625;
@@ -126,87 +195,133 @@ const BULLET_INDICES_H_V: [u32; 12] = [
     4, 6, 7,
 ];
 
-impl BulletSystem {
-    pub fn new(context: &mut GpuContext, impact_mesh: SmallMesh) -> Self {
-        let texture_config = TextureConfig {
-            flip_v: false,
-            flip_h: true,
-            gamma_correction: false,
-            filter: TextureFilter::Nearest,
-            texture_type: TextureType::None,
-            wrap: TextureWrap::Repeat,
-        };
+impl BulletStore {
+    pub fn new() -> Self {
+        Self::with_capacity(INITIAL_BULLET_CAPACITY)
+    }
 
-        let bullet_material = Material::new(context, "angrygl_assets/bullet/bullet_texture_transparent.png", &texture_config).unwrap();
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
 
-        let vertices = BULLET_VERTICES_H_V;
-        let indices = BULLET_INDICES_H_V;
+        Self {
+            bullet_positions: vec![Vec3::default(); capacity],
+            bullet_rotations: vec![Quat::default(); capacity],
+            bullet_directions: vec![Vec3::default(); capacity],
+            bullet_groups: VecDeque::new(),
+            head: 0,
+            len: 0,
+            groups_hit: 0,
+            broadphase: Broadphase::SpatialHash,
+            update_mode: BulletUpdateMode::Parallel,
+            // cells as big as the furthest a bullet can be from a enemy and still hit it
+            bullet_grid: SpatialHash::new(BULLET_ENEMY_MAX_COLLISION_DIST),
+        }
+    }
 
-        let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("bullet mesh vertex buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+    /// Removes every bullet, keeping the settings and allocations.
+    pub fn clear(&mut self) {
+        self.bullet_groups.clear();
+        self.head = 0;
+        self.len = 0;
+        self.groups_hit = 0;
+    }
 
-        let index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("bullet mesh index buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+    /// Number of bullet slots before the storage has to grow.
+    pub fn capacity(&self) -> usize {
+        self.bullet_positions.len()
+    }
 
-        let impact_sprite_sheet_material = Material::new(context, "angrygl_assets/bullet/impact_spritesheet_with_00.png", &texture_config).unwrap();
-        let impact_spritesheet = SpriteSheet::new(context, impact_sprite_sheet_material, 11.0, 0.05);
+    /// Number of live bullets.
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        let bullet_positions_buffer = create_vertex_buffer(context, mem::size_of::<Vec3>() * MAX_BULLETS, "bullet positions buffer");
-        let bullet_rotations_buffer = create_vertex_buffer(context, mem::size_of::<Quat>() * MAX_BULLETS, "bullet rotations buffer");
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-        // Pre-calculate the bullet spread rotations. Only needs to be done once.
-        let mut x_rotations = Vec::with_capacity(SPREAD_AMOUNT as usize);
-        let mut y_rotations = Vec::with_capacity(SPREAD_AMOUNT as usize);
-        let spread_centering = ROTATION_PER_BULLET * (SPREAD_AMOUNT as f32 - 1.0) / 4.0;
+    /// Slots of the live bullets, oldest first. The second range is empty unless they wrap past the end of the storage.
+    pub fn live_ranges(&self) -> [Range<usize>; 2] {
+        self.slot_ranges(self.head, self.len)
+    }
 
-        for i in 0..SPREAD_AMOUNT {
-            let y_rot = Quat::from_axis_angle(
-                vec3(0.0, 1.0, 0.0),
-                ROTATION_PER_BULLET.mul_add((i - SPREAD_AMOUNT) as f32 / 2.0, spread_centering),
-            );
-            let x_rot = Quat::from_axis_angle(
-                vec3(1.0, 0.0, 0.0),
-                ROTATION_PER_BULLET.mul_add((i - SPREAD_AMOUNT) as f32 / 2.0, spread_centering),
-            );
-            x_rotations.push(x_rot);
-            y_rotations.push(y_rot)
+    pub fn live_positions(&self) -> impl Iterator<Item = &Vec3> {
+        self.live_ranges().into_iter().flat_map(|range| self.bullet_positions[range].iter())
+    }
+
+    /// Slots of the bullets in `group` as ranges, the second is empty unless the group wraps.
+    pub fn group_ranges(&self, group: &BulletGroup) -> [Range<usize>; 2] {
+        self.slot_ranges(group.start_index, group.group_size as usize)
+    }
+
+    /// Slots of the bullets in `group`, in the order they were fired.
+    pub fn group_slots(&self, group: &BulletGroup) -> impl Iterator<Item = usize> {
+        let capacity = self.capacity();
+        let start = group.start_index;
+        (0..group.group_size as usize).map(move |offset| (start + offset) % capacity)
+    }
+
+    /// Makes room for `additional` more bullets. Growing unwraps the live bullets to the start of the new storage.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len + additional;
+        let capacity = self.capacity();
+        if required <= capacity {
+            return;
         }
 
-        Self {
-            bullet_positions: vec![],
-            bullet_rotations: vec![],
-            bullet_directions: Default::default(),
-            bullet_groups: vec![],
-            x_rotations,
-            y_rotations,
-            bullet_material,
-            impact_spritesheet,
-            impact_sprites: vec![],
-            impact_mesh,
-            vertex_buffer,
-            index_buffer,
-            bullet_positions_buffer,
-            bullet_rotations_buffer,
+        let new_capacity = required.next_power_of_two().max(capacity * 2);
+        let live_slots: Vec<usize> = self.live_ranges().into_iter().flatten().collect();
+
+        self.bullet_positions = unwrap_ring(&self.bullet_positions, &live_slots, new_capacity);
+        self.bullet_rotations = unwrap_ring(&self.bullet_rotations, &live_slots, new_capacity);
+        self.bullet_directions = unwrap_ring(&self.bullet_directions, &live_slots, new_capacity);
+
+        for group in self.bullet_groups.iter_mut() {
+            group.start_index = (group.start_index + capacity - self.head) % capacity;
         }
+        self.head = 0;
     }
 
-    pub fn create_bullets(&mut self, dx: f32, dz: f32, muzzle_transform: &Mat4, spread_amount: i32) -> bool {
-        // limit number of bullet groups
-        if self.bullet_groups.len() >= MAX_BULLET_GROUPS as usize {
-            return false;
+    /// Kills the enemies and counts the groups of (group index, enemy index) hits.
+    fn apply_hits(&mut self, enemies: &mut [Enemy], hits: Vec<(usize, usize)>) {
+        for (group_index, enemy_index) in hits {
+            // println!("killed enemy!");
+            enemies[enemy_index].is_alive = false;
+            self.mark_group_hit(group_index);
         }
-        // let spreadAmount = 100;
+    }
 
-        let muzzle_world_position = *muzzle_transform * vec4(0.0, 0.0, 0.0, 1.0);
+    /// Counts a group toward `groups_hit` the first time one of its bullets hits.
+    pub fn mark_group_hit(&mut self, group_index: usize) {
+        let group = &mut self.bullet_groups[group_index];
+        if !group.has_hit {
+            group.has_hit = true;
+            self.groups_hit += 1;
+        }
+    }
+
+    fn group_of_bullet(&self, slot: usize) -> usize {
+        let age = self.age_of_slot(slot);
+        self.bullet_groups.partition_point(|group| self.age_of_slot(group.start_index) <= age) - 1
+    }
 
-        let projectile_spawn_point = muzzle_world_position.xyz();
+    // Position of a slot counting from the oldest live bullet
+    fn age_of_slot(&self, slot: usize) -> usize {
+        (slot + self.capacity() - self.head) % self.capacity()
+    }
+
+    fn slot_ranges(&self, start: usize, count: usize) -> [Range<usize>; 2] {
+        let capacity = self.capacity();
+        let end = start + count;
+        if end <= capacity {
+            [start..end, 0..0]
+        } else {
+            [start..capacity, 0..end - capacity]
+        }
+    }
 
+    /// Fires one shot of `weapon`, growing the storage if it is full.
+    pub fn create_bullets(&mut self, dx: f32, dz: f32, projectile_spawn_point: Vec3, weapon_index: usize, weapon: &Weapon) {
         let mid_direction = vec3(dx, 0.0, dz).normalize();
 
         let normalized_direction = mid_direction.normalize_or_zero();
@@ -222,175 +337,288 @@ impl BulletSystem {
         let mut mid_dir_quat = Quat::from_xyzw(1.0, 0.0, 0.0, 0.0);
         mid_dir_quat *= Quat::from_axis_angle(rot_vec, theta.to_radians());
 
-        let start_index = self.bullet_positions.len();
-
-        let bullet_group_size = spread_amount * spread_amount;
-
-        let bullet_group = BulletGroup::new(start_index, bullet_group_size, BULLET_LIFETIME);
-
-        self.bullet_positions.resize(start_index + bullet_group_size as usize, Vec3::default());
-        self.bullet_rotations.resize(start_index + bullet_group_size as usize, Quat::default());
-        self.bullet_directions.resize(start_index + bullet_group_size as usize, Vec3::default());
-
-        // let i_start = 0;
-        // let i_end = spread_amount;
-        //
-        // let spread_centering = ROTATION_PER_BULLET * (spread_amount as f32 - 1.0) / 4.0;
-
-        // for i in i_start..i_end {
-        //     let y_quat = mid_dir_quat
-        //         * Quat::from_axis_angle(
-        //             vec3(0.0, 1.0, 0.0),
-        //             ROTATION_PER_BULLET.mul_add((i - spread_amount) as f32 / 2.0, spread_centering),
-        //         );
-        //
-        //     for j in 0..spread_amount {
-        //         let rot_quat = y_quat
-        //             * Quat::from_axis_angle(
-        //                 vec3(1.0, 0.0, 0.0),
-        //                 ROTATION_PER_BULLET.mul_add((j - spread_amount) as f32 / 2.0, spread_centering),
-        //             );
-        //
-        //         let direction = rot_quat.mul_vec3(CANONICAL_DIR * -1.0);
-        //
-        //         let index = (i * spread_amount + j) as usize + start_index;
-        //
-        //         self.bullet_positions[index] = projectile_spawn_point;
-        //         self.bullet_directions[index] = direction;
-        //         self.bullet_rotations[index] = rot_quat;
-        //     }
-        // }
+        let bullet_group_size = weapon.bullet_rotations.len();
+        self.reserve(bullet_group_size);
 
-        let start: usize = start_index;
-        let end = start + bullet_group_size as usize;
+        let start_index = (self.head + self.len) % self.capacity();
+        let definition = &weapon.definition;
+        let bullet_group = BulletGroup::new(start_index, bullet_group_size as i32, definition.lifetime, weapon_index, definition.bullet_speed);
 
-        for index in start..end {
-            let count = index - start;
-            let i = (count as f32 / SPREAD_AMOUNT as f32).floor();
-            let j = count as f32 % SPREAD_AMOUNT as f32;
+        for (count, bullet_rotation) in weapon.bullet_rotations.iter().enumerate() {
+            let slot = (start_index + count) % self.capacity();
 
-            let y_quat = mid_dir_quat * self.y_rotations[i as usize];
-            let rot_quat = y_quat * self.x_rotations[j as usize];
+            let rot_quat = mid_dir_quat * *bullet_rotation;
             let direction = rot_quat.mul_vec3(CANONICAL_DIR * -1.0);
 
-            self.bullet_positions[index] = projectile_spawn_point;
-            self.bullet_rotations[index] = rot_quat;
-            self.bullet_directions[index] = direction;
+            self.bullet_positions[slot] = projectile_spawn_point;
+            self.bullet_rotations[slot] = rot_quat;
+            self.bullet_directions[slot] = direction;
+        }
+
+        self.len += bullet_group_size;
+        self.bullet_groups.push_back(bullet_group);
+    }
+
+    /// Advances the bullets, expires old groups and marks any enemy hit as not alive.
+    pub fn update_bullets(&mut self, delta_time: f32, enemies: &mut [Enemy]) {
+        for group in self.bullet_groups.iter_mut() {
+            group.time_to_live -= delta_time;
         }
 
-        self.bullet_groups.push(bullet_group);
+        for group_index in 0..self.bullet_groups.len() {
+            let group = &self.bullet_groups[group_index];
+            if !group.is_live() {
+                continue;
+            }
 
-        true
+            let delta_position_magnitude = delta_time * group.speed;
+
+            for range in self.group_ranges(group) {
+                let positions = &mut self.bullet_positions[range.clone()];
+                let directions = &self.bullet_directions[range];
+
+                match self.update_mode {
+                    BulletUpdateMode::Serial => {
+                        for (position, direction) in positions.iter_mut().zip(directions.iter()) {
+                            *position += *direction * delta_position_magnitude;
+                        }
+                    }
+                    BulletUpdateMode::Parallel => {
+                        positions
+                            .par_iter_mut()
+                            .zip(directions.par_iter())
+                            .with_min_len(PARALLEL_MIN_BULLETS)
+                            .for_each(|(position, direction)| *position += *direction * delta_position_magnitude);
+                    }
+                }
+            }
+        }
+
+        if !enemies.is_empty() {
+            match self.broadphase {
+                Broadphase::AabbSubGroups => self.collide_aabb_sub_groups(enemies),
+                Broadphase::SpatialHash => self.collide_spatial_hash(enemies),
+            }
+        }
+
+        self.remove_expired_groups();
     }
 
-    pub fn update_bullets(&mut self, context: &GpuContext, world: &mut World) {
-        let use_aabb = !world.enemies.is_empty();
-        let num_sub_groups = if use_aabb { 9 } else { 1 };
+    /// Drops the expired groups at the head of the ring. Their bullets are left in place and the head moves past them.
+    pub fn remove_expired_groups(&mut self) {
+        while let Some(group) = self.bullet_groups.front() {
+            if group.is_live() {
+                break;
+            }
+            self.head = (self.head + group.group_size as usize) % self.capacity();
+            self.len -= group.group_size as usize;
+            self.bullet_groups.pop_front();
+        }
 
-        let delta_position_magnitude = world.delta_time * BULLET_SPEED;
+        if self.len == 0 {
+            self.head = 0;
+        }
+    }
 
-        let mut first_live_bullet_group: usize = 0;
+    /// Splits each live group into sub groups and only tests the enemies inside a sub group's bounding box.
+    /// Cost grows with sub groups times enemies, kept to compare against the spatial hash.
+    fn collide_aabb_sub_groups(&mut self, enemies: &mut [Enemy]) {
+        // (group index, group)
+        let live_groups: Vec<(usize, &BulletGroup)> = self.bullet_groups.iter().enumerate().filter(|(_, group)| group.is_live()).collect();
+        let targets: &[Enemy] = enemies;
+
+        // (group index, enemy index)
+        let group_hits = |(group_index, group): &(usize, &BulletGroup)| {
+            let group_index = *group_index;
+            self.aabb_sub_group_hits(group, targets)
+                .into_iter()
+                .map(move |enemy_index| (group_index, enemy_index))
+        };
 
-        for group in self.bullet_groups.iter_mut() {
-            group.time_to_live -= world.delta_time;
+        let hits: Vec<(usize, usize)> = match self.update_mode {
+            BulletUpdateMode::Serial => live_groups.iter().flat_map(group_hits).collect(),
+            BulletUpdateMode::Parallel => live_groups.par_iter().flat_map_iter(group_hits).collect(),
+        };
 
-            if group.time_to_live <= 0.0 {
-                first_live_bullet_group += 1;
-            } else {
-                // could make this async
-                let bullet_group_start_index = group.start_index as i32;
-                let num_bullets_in_group = group.group_size;
-                let sub_group_size = num_bullets_in_group / num_sub_groups;
+        self.apply_hits(enemies, hits);
+    }
 
-                for sub_group in 0..num_sub_groups {
-                    let mut bullet_start = sub_group_size * sub_group;
+    /// Indices of the enemies hit by a bullet group.
+    fn aabb_sub_group_hits(&self, group: &BulletGroup, enemies: &[Enemy]) -> Vec<usize> {
+        let num_sub_groups = 9;
 
-                    let mut bullet_end = if sub_group == (num_sub_groups - 1) {
-                        num_bullets_in_group
-                    } else {
-                        bullet_start + sub_group_size
-                    };
+        let slots: Vec<usize> = self.group_slots(group).collect();
+        let num_bullets_in_group = slots.len();
+        let sub_group_size = num_bullets_in_group / num_sub_groups;
 
-                    bullet_start += bullet_group_start_index;
-                    bullet_end += bullet_group_start_index;
+        let mut hits = vec![];
 
-                    for bullet_index in bullet_start..bullet_end {
-                        self.bullet_positions[bullet_index as usize] += self.bullet_directions[bullet_index as usize] * delta_position_magnitude;
-                    }
+        for sub_group in 0..num_sub_groups {
+            let bullet_start = sub_group_size * sub_group;
 
-                    let mut subgroup_bound_box = Aabb::new();
+            let bullet_end = if sub_group == (num_sub_groups - 1) {
+                num_bullets_in_group
+            } else {
+                bullet_start + sub_group_size
+            };
 
-                    if use_aabb {
-                        for bullet_index in bullet_start..bullet_end {
-                            subgroup_bound_box.expand_to_include(self.bullet_positions[bullet_index as usize]);
-                        }
+            let sub_group_slots = &slots[bullet_start..bullet_end];
 
-                        subgroup_bound_box.expand_by(BULLET_ENEMY_MAX_COLLISION_DIST);
-                    }
+            let mut subgroup_bound_box = Aabb::new();
 
-                    for i in 0..world.enemies.len() {
-                        let enemy = &mut world.enemies[i];
+            for slot in sub_group_slots.iter() {
+                subgroup_bound_box.expand_to_include(self.bullet_positions[*slot]);
+            }
 
-                        if use_aabb && !subgroup_bound_box.contains_point(enemy.position) {
-                            continue;
-                        }
-                        for bullet_index in bullet_start..bullet_end {
-                            if bullet_collides_with_enemy(
-                                &self.bullet_positions[bullet_index as usize],
-                                &self.bullet_directions[bullet_index as usize],
-                                enemy,
-                            ) {
-                                // println!("killed enemy!");
-                                enemy.is_alive = false;
-                                break;
-                            }
-                        }
+            subgroup_bound_box.expand_by(BULLET_ENEMY_MAX_COLLISION_DIST);
+
+            for (enemy_index, enemy) in enemies.iter().enumerate() {
+                if !subgroup_bound_box.contains_point(enemy.position) {
+                    continue;
+                }
+                for slot in sub_group_slots.iter() {
+                    if bullet_collides_with_enemy(&self.bullet_positions[*slot], &self.bullet_directions[*slot], enemy) {
+                        hits.push(enemy_index);
+                        break;
                     }
                 }
             }
         }
 
-        let mut first_live_bullet: usize = 0;
+        hits
+    }
 
-        if first_live_bullet_group != 0 {
-            first_live_bullet =
-                self.bullet_groups[first_live_bullet_group - 1].start_index + self.bullet_groups[first_live_bullet_group - 1].group_size as usize;
-            self.bullet_groups.drain(0..first_live_bullet_group);
+    /// Buckets the live bullets into a grid and only tests each enemy against the bullets in its nearby cells.
+    fn collide_spatial_hash(&mut self, enemies: &mut [Enemy]) {
+        self.bullet_grid.clear();
+        for group in self.bullet_groups.iter().filter(|group| group.is_live()) {
+            for slot in self.group_ranges(group).into_iter().flatten() {
+                self.bullet_grid.insert(slot, self.bullet_positions[slot]);
+            }
         }
+        self.bullet_grid.build();
+
+        let grid = &self.bullet_grid;
+        let positions = &self.bullet_positions;
+        let directions = &self.bullet_directions;
+
+        // (group index, enemy index) for each group with a bullet touching the enemy
+        let enemy_hits = |(enemy_index, enemy): (usize, &Enemy)| {
+            let mut groups: Vec<usize> = grid
+                .nearby(enemy.position)
+                .filter(|slot| bullet_collides_with_enemy(&positions[*slot], &directions[*slot], enemy))
+                .map(|slot| self.group_of_bullet(slot))
+                .collect();
+            groups.sort_unstable();
+            groups.dedup();
+            groups.into_iter().map(move |group_index| (group_index, enemy_index))
+        };
 
-        if first_live_bullet != 0 {
-            self.bullet_positions.drain(0..first_live_bullet);
-            self.bullet_directions.drain(0..first_live_bullet);
-            self.bullet_rotations.drain(0..first_live_bullet);
+        let hits: Vec<(usize, usize)> = match self.update_mode {
+            BulletUpdateMode::Serial => enemies.iter().enumerate().flat_map(enemy_hits).collect(),
+            BulletUpdateMode::Parallel => enemies.par_iter().enumerate().flat_map_iter(enemy_hits).collect(),
+        };
 
-            for group in self.bullet_groups.iter_mut() {
-                group.start_index -= first_live_bullet;
-            }
-        }
+        self.apply_hits(enemies, hits);
+    }
+}
 
-        if !self.impact_sprites.is_empty() {
-            for sheet in self.impact_sprites.iter_mut() {
-                sheet.age += &world.delta_time;
-            }
-            let sprite_duration = self.impact_spritesheet.uniform.num_columns as f32 * self.impact_spritesheet.uniform.time_per_sprite;
+// Copy of a ring's live slots, in order, at the start of a bigger vec
+fn unwrap_ring<T: Copy + Default>(ring: &[T], live_slots: &[usize], capacity: usize) -> Vec<T> {
+    let mut unwrapped = Vec::with_capacity(capacity);
+    unwrapped.extend(live_slots.iter().map(|slot| ring[*slot]));
+    unwrapped.resize(capacity, T::default());
+    unwrapped
+}
+
+impl BulletSystem {
+    pub fn new(context: &mut GpuContext, impact_mesh: SmallMesh, weapons: &[Weapon]) -> Self {
+        let texture_config = TextureConfig {
+            flip_v: false,
+            flip_h: true,
+            gamma_correction: false,
+            filter: TextureFilter::Nearest,
+            texture_type: TextureType::None,
+            wrap: TextureWrap::Repeat,
+        };
+
+        let bullet_materials = weapons
+            .iter()
+            .map(|weapon| Material::new(context, &weapon.definition.bullet_texture, &texture_config).unwrap())
+            .collect();
+
+        let vertices = BULLET_VERTICES_H_V;
+        let indices = BULLET_INDICES_H_V;
+
+        let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("bullet mesh vertex buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("bullet mesh index buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
 
-            self.impact_sprites.retain(|sprite| sprite.age < sprite_duration);
+        let impact_sprite_sheet_material = Material::new(context, "angrygl_assets/bullet/impact_spritesheet_with_00.png", &texture_config).unwrap();
+        let impact_spritesheet = SpriteSheet::new(context, impact_sprite_sheet_material, IMPACT_SPRITE_COLUMNS, IMPACT_TIME_PER_SPRITE);
+
+        let instance_capacity = INITIAL_BULLET_CAPACITY;
+        let (bullet_positions_buffer, bullet_rotations_buffer) = create_bullet_instance_buffers(context, instance_capacity);
+        let impact_capacity = INITIAL_IMPACT_CAPACITY;
+        let impact_instances_buffer = create_vertex_buffer(context, mem::size_of::<Vec4>() * impact_capacity, "impact sprite instances buffer");
+
+        Self {
+            bullet_materials,
+            impact_spritesheet,
+            impact_mesh,
+            vertex_buffer,
+            index_buffer,
+            bullet_positions_buffer,
+            bullet_rotations_buffer,
+            instance_capacity,
+            impact_instances_buffer,
+            impact_capacity,
+            render_positions: Vec::with_capacity(INITIAL_BULLET_CAPACITY),
+            impact_instances: Vec::with_capacity(INITIAL_IMPACT_CAPACITY),
         }
+    }
 
-        for enemy in world.enemies.iter() {
-            if !enemy.is_alive {
-                self.impact_sprites.push(SpriteSheetSprite::new(enemy.position));
-                world.burn_marks.add_mark(enemy.position);
-                // world.sound_system.play_enemy_destroyed();
-            }
+    /// Uploads the live bullet instances into the same slots they have in the store. `render_lag` is the time in
+    /// seconds the rendered frame is behind the last simulation tick, bullets move in straight lines so they are
+    /// stepped back along their direction at their group's speed.
+    pub fn update_buffers(&mut self, context: &GpuContext, bullet_store: &BulletStore, render_lag: f32) {
+        if bullet_store.capacity() != self.instance_capacity {
+            self.instance_capacity = bullet_store.capacity();
+            (self.bullet_positions_buffer, self.bullet_rotations_buffer) = create_bullet_instance_buffers(context, self.instance_capacity);
         }
 
-        update_uniform_buffer(context, &self.bullet_positions_buffer, &self.bullet_positions.as_slice());
-        update_uniform_buffer(context, &self.bullet_rotations_buffer, &self.bullet_rotations.as_slice());
+        for group in bullet_store.bullet_groups.iter().filter(|group| group.is_live()) {
+            let lag_distance = render_lag * group.speed;
+
+            for range in bullet_store.group_ranges(group) {
+                if range.is_empty() {
+                    continue;
+                }
+
+                self.render_positions.clear();
+                self.render_positions.extend(
+                    bullet_store.bullet_positions[range.clone()]
+                        .iter()
+                        .zip(bullet_store.bullet_directions[range.clone()].iter())
+                        .map(|(position, direction)| *position - *direction * lag_distance),
+                );
+
+                update_buffer_range(context, &self.bullet_positions_buffer, range.start, self.render_positions.as_slice());
+                update_buffer_range(context, &self.bullet_rotations_buffer, range.start, &bullet_store.bullet_rotations[range]);
+            }
+        }
     }
 
-    pub fn draw_bullets(&mut self, projection_view: &Mat4) {
-        if self.bullet_positions.is_empty() {
+    pub fn draw_bullets(&mut self, bullet_store: &BulletStore, projection_view: &Mat4) {
+        if bullet_store.is_empty() {
             return;
         }
 
@@ -451,55 +679,37 @@ impl BulletSystem {
          */
     }
 
-    // needs layout, buffers, and render
-    pub fn draw_bullet_impacts(&self, world: &World, projection_view: &Mat4) {
-        // sprite_shader.set_mat4("PV", projection_view);
-        // sprite_shader.set_int("numCols", self.bullet_impact_spritesheet.num_columns);
-        // sprite_shader.set_float("timePerSprite", self.bullet_impact_spritesheet.time_per_sprite);
-
-        // bind_texture(sprite_shader, 0, "spritesheet", &self.bullet_impact_spritesheet.texture);
-
-        // unsafe {
-        //     gl::Enable(gl::BLEND);
-        // gl::DepthMask(gl::FALSE);
-        // gl::Disable(gl::CULL_FACE);
-        //
-        // gl::BindVertexArray(self.unit_square_vao as GLuint);
-        // }
-
-        let scale = 2.0f32; // 0.25f32;
-
-        for sprite in &self.impact_sprites {
-            let mut model = Mat4::from_translation(sprite.world_position);
-            model *= Mat4::from_rotation_x(-90.0f32.to_radians());
-
-            // TODO: Billboarding
-            // for (int i = 0; i < 3; i++)
-            // {
-            //     for (int j = 0; j < 3; j++)
-            //     {
-            //         model[i][j] = viewTransform[j][i];
-            //     }
-            // }
+    /// Uploads position and age of each impact sprite, the shader picks the frame from the age.
+    pub fn update_impacts(&mut self, context: &GpuContext, impact_sprites: &[SpriteSheetSprite]) {
+        self.impact_instances.clear();
+        self.impact_instances.extend(impact_sprites.iter().map(|sprite| sprite.world_position.extend(sprite.age)));
 
-            model *= Mat4::from_scale(vec3(scale, scale, scale));
-
-            // sprite_shader.set_float("age", sprite.age);
-            // sprite_shader.set_mat4("model", &model);
+        if self.impact_instances.len() > self.impact_capacity {
+            self.grow_impact_instances_buffer(context, self.impact_instances.len());
+        }
 
-            // unsafe {
-            //     gl::DrawArrays(gl::TRIANGLES, 0, 6);
-            // }
+        if !self.impact_instances.is_empty() {
+            update_uniform_buffer(context, &self.impact_instances_buffer, self.impact_instances.as_slice());
         }
+    }
 
-        // unsafe {
-        //     gl::Disable(gl::BLEND);
-        //     gl::Enable(gl::CULL_FACE);
-        //     gl::DepthMask(gl::TRUE);
-        // }
+    pub fn num_impacts(&self) -> u32 {
+        self.impact_instances.len() as u32
+    }
+
+    // Doubles the capacity until `count` impacts fit, like the enemy instances
+    fn grow_impact_instances_buffer(&mut self, context: &GpuContext, count: usize) {
+        self.impact_capacity = count.next_power_of_two().max(self.impact_capacity * 2);
+        self.impact_instances_buffer = create_vertex_buffer(context, mem::size_of::<Vec4>() * self.impact_capacity, "impact sprite instances buffer");
     }
 }
 
+fn create_bullet_instance_buffers(context: &GpuContext, capacity: usize) -> (Buffer, Buffer) {
+    let positions = create_vertex_buffer(context, mem::size_of::<Vec3>() * capacity, "bullet positions buffer");
+    let rotations = create_vertex_buffer(context, mem::size_of::<Quat>() * capacity, "bullet rotations buffer");
+    (positions, rotations)
+}
+
 fn bullet_collides_with_enemy(position: &Vec3, direction: &Vec3, enemy: &Enemy) -> bool {
     if position.distance(enemy.position) > BULLET_ENEMY_MAX_COLLISION_DIST {
         return false;
@@ -557,9 +767,217 @@ fn hamilton_product_quat_quat(first: Quat, other: &Quat) -> Quat {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
     use glam::vec3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
+    use crate::bullets::{Broadphase, BulletStore, BulletUpdateMode};
+    use crate::enemy::Enemy;
     use crate::geom::oriented_angle;
+    use crate::weapons::{SpreadPattern, Weapon, WeaponDefinition};
+    use crate::world::{BULLET_GROUPS_IN_FLIGHT, MONSTER_Y};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // Full bullet groups fanned out around the origin and enemies scattered in front of them.
+    fn bullets_and_enemies(num_groups: i32, num_enemies: usize) -> (BulletStore, Vec<Enemy>) {
+        let mut rng = StdRng::seed_from_u64(99);
+        let weapon = Weapon::new(WeaponDefinition::default());
+        let mut store = BulletStore::new();
+
+        for i in 0..num_groups {
+            let angle = (i as f32 * 36.0).to_radians();
+            store.create_bullets(angle.sin(), angle.cos(), vec3(0.0, MONSTER_Y, 0.0), 0, &weapon);
+        }
+
+        let enemies = (0..num_enemies)
+            .map(|_| {
+                let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+                let distance = rng.gen::<f32>() * 6.0;
+                let position = vec3(angle.sin() * distance, MONSTER_Y, angle.cos() * distance);
+                Enemy {
+                    position,
+                    previous_position: position,
+                    direction: (-position).normalize_or_zero(),
+                    is_alive: true,
+                }
+            })
+            .collect();
+
+        (store, enemies)
+    }
+
+    fn run_ticks(broadphase: Broadphase, update_mode: BulletUpdateMode, num_groups: i32, num_enemies: usize, ticks: usize) -> Vec<bool> {
+        let (mut store, mut enemies) = bullets_and_enemies(num_groups, num_enemies);
+        store.broadphase = broadphase;
+        store.update_mode = update_mode;
+
+        for _ in 0..ticks {
+            store.update_bullets(DT, &mut enemies);
+        }
+
+        enemies.iter().map(|e| e.is_alive).collect()
+    }
+
+    #[test]
+    fn test_spatial_hash_kills_same_enemies_as_aabb() {
+        let aabb = run_ticks(Broadphase::AabbSubGroups, BulletUpdateMode::Serial, BULLET_GROUPS_IN_FLIGHT, 500, 60);
+        let grid = run_ticks(Broadphase::SpatialHash, BulletUpdateMode::Serial, BULLET_GROUPS_IN_FLIGHT, 500, 60);
+
+        assert!(aabb.iter().any(|alive| !alive));
+        assert_eq!(aabb, grid);
+    }
+
+    #[test]
+    fn test_broadphases_count_the_same_groups_hit() {
+        // two groups fired together overlap, so the enemy in their path is touched by both
+        let weapon = Weapon::new(WeaponDefinition {
+            pattern: SpreadPattern::Single,
+            ..Default::default()
+        });
+        let spawn = vec3(0.0, MONSTER_Y, 0.0);
+
+        for broadphase in [Broadphase::AabbSubGroups, Broadphase::SpatialHash] {
+            let mut store = BulletStore::new();
+            store.broadphase = broadphase;
+            store.create_bullets(0.0, 1.0, spawn, 0, &weapon);
+            store.create_bullets(0.0, 1.0, spawn, 0, &weapon);
+
+            let position = spawn + store.bullet_directions[0] * (DT * weapon.definition.bullet_speed);
+            let mut enemies = vec![Enemy {
+                position,
+                previous_position: position,
+                direction: vec3(1.0, 0.0, 0.0),
+                is_alive: true,
+            }];
+            store.update_bullets(DT, &mut enemies);
+
+            assert!(!enemies[0].is_alive, "{:?}", broadphase);
+            assert_eq!(store.groups_hit, 2, "{:?}", broadphase);
+        }
+    }
+
+    #[test]
+    fn test_parallel_kills_same_enemies_as_serial() {
+        for broadphase in [Broadphase::AabbSubGroups, Broadphase::SpatialHash] {
+            let serial = run_ticks(broadphase, BulletUpdateMode::Serial, BULLET_GROUPS_IN_FLIGHT, 500, 60);
+            let parallel = run_ticks(broadphase, BulletUpdateMode::Parallel, BULLET_GROUPS_IN_FLIGHT, 500, 60);
+
+            assert_eq!(serial, parallel, "{:?}", broadphase);
+        }
+    }
+
+    #[test]
+    fn test_ring_wraps_and_grows() {
+        let mut store = BulletStore::with_capacity(10);
+        let weapon = Weapon::new(WeaponDefinition {
+            pattern: SpreadPattern::Square { size: 2, spacing: 3.0 },
+            ..Default::default()
+        });
+        let fire = |store: &mut BulletStore, x: f32| store.create_bullets(0.0, 1.0, vec3(x, MONSTER_Y, 0.0), 0, &weapon);
+
+        fire(&mut store, 1.0);
+        fire(&mut store, 2.0);
+        store.bullet_groups[0].time_to_live = 0.0;
+        store.remove_expired_groups();
+
+        // the third group runs past the end of the storage
+        fire(&mut store, 3.0);
+        assert_eq!(store.capacity(), 10);
+        assert_eq!(store.live_ranges(), [4..10, 0..2]);
+        assert_eq!(store.group_slots(&store.bullet_groups[1]).collect::<Vec<_>>(), vec![8, 9, 0, 1]);
+
+        // the fourth doesn't fit, growing unwraps the live bullets in age order
+        fire(&mut store, 4.0);
+        assert_eq!(store.capacity(), 20);
+        assert_eq!(store.live_ranges(), [0..12, 0..0]);
+        let xs: Vec<f32> = store.live_positions().map(|position| position.x).collect();
+        assert_eq!(xs, [2.0, 2.0, 2.0, 2.0, 3.0, 3.0, 3.0, 3.0, 4.0, 4.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn test_short_lived_group_waits_behind_older_group() {
+        let slow = Weapon::new(WeaponDefinition {
+            pattern: SpreadPattern::Single,
+            lifetime: 1.0,
+            bullet_speed: 2.0,
+            ..Default::default()
+        });
+        let fast = Weapon::new(WeaponDefinition {
+            pattern: SpreadPattern::Single,
+            lifetime: 0.5,
+            bullet_speed: 10.0,
+            ..Default::default()
+        });
+
+        let mut store = BulletStore::with_capacity(4);
+        store.create_bullets(0.0, 1.0, vec3(0.0, MONSTER_Y, 0.0), 0, &slow);
+        store.create_bullets(0.0, 1.0, vec3(0.0, MONSTER_Y, 0.0), 1, &fast);
+
+        for _ in 0..45 {
+            store.update_bullets(DT, &mut []);
+        }
+
+        // the fast group expired but stays in the ring, and no longer moves, until the slow one is gone
+        assert_eq!(store.bullet_groups.len(), 2);
+        assert!(!store.bullet_groups[1].is_live());
+        let fast_position = store.bullet_positions[1];
+        store.update_bullets(DT, &mut []);
+        assert_eq!(store.bullet_positions[1], fast_position);
+        assert!(store.bullet_positions[0].distance(store.bullet_positions[1]) > 1.0);
+
+        for _ in 0..20 {
+            store.update_bullets(DT, &mut []);
+        }
+        assert!(store.is_empty());
+    }
+
+    // cargo test --release bench_broadphase -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_broadphase() {
+        // stay under the bullet lifetime so every tick has the full set of bullets
+        let ticks = 30;
+
+        for num_enemies in [10, 100, 1000, 5000] {
+            for (broadphase, update_mode) in [
+                (Broadphase::AabbSubGroups, BulletUpdateMode::Serial),
+                (Broadphase::SpatialHash, BulletUpdateMode::Serial),
+                (Broadphase::SpatialHash, BulletUpdateMode::Parallel),
+            ] {
+                let (mut store, enemies) = bullets_and_enemies(BULLET_GROUPS_IN_FLIGHT, num_enemies);
+                store.broadphase = broadphase;
+                store.update_mode = update_mode;
+
+                let start = Instant::now();
+                for _ in 0..ticks {
+                    // fresh enemies each tick so every one of them is tested
+                    let mut enemies: Vec<Enemy> = enemies
+                        .iter()
+                        .map(|e| Enemy {
+                            position: e.position,
+                            previous_position: e.previous_position,
+                            direction: e.direction,
+                            is_alive: true,
+                        })
+                        .collect();
+                    store.update_bullets(DT, &mut enemies);
+                }
+                let elapsed = start.elapsed();
+
+                println!(
+                    "{:?} {:?}  bullets: {}  enemies: {}  per tick: {:.3} ms",
+                    broadphase,
+                    update_mode,
+                    store.len(),
+                    num_enemies,
+                    elapsed.as_secs_f64() * 1000.0 / ticks as f64
+                );
+            }
+        }
+    }
 
     #[test]
     fn test_oriented_rotation() {
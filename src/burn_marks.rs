@@ -1,20 +1,38 @@
+use crate::render::buffers::{create_vertex_buffer_init, update_uniform_buffer};
 use crate::small_mesh::SmallMesh;
-use glam::{vec3, Mat4, Vec3};
+use glam::{vec4, Vec3, Vec4};
 use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::Material;
 use spark_gap::texture_config::{TextureConfig, TextureWrap};
+use wgpu::Buffer;
 
-const BURN_MARK_TIME: f32 = 5.0;
+pub const BURN_MARK_TIME: f32 = 5.0;
+
+// Oldest marks are dropped past this
+pub const MAX_BURN_MARKS: usize = 100;
 
 pub struct BurnMark {
-    position: Vec3,
-    time_left: f32,
+    pub position: Vec3,
+    pub time_left: f32,
 }
 
+impl BurnMark {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            time_left: BURN_MARK_TIME,
+        }
+    }
+}
+
+/// GPU side of the burn marks: the decal quad, texture and per mark instance buffer.
 pub struct BurnMarks {
-    unit_square: SmallMesh,
-    mark_material: Material,
-    marks: Vec<BurnMark>,
+    pub unit_square: SmallMesh,
+    pub mark_material: Material,
+    pub instance_buffer: Buffer,
+    pub num_marks: u32,
+    // scratch vec for the instances uploaded each frame
+    instances: Vec<Vec4>,
 }
 
 impl BurnMarks {
@@ -22,61 +40,33 @@ impl BurnMarks {
         let texture_config = TextureConfig::new().set_wrap(TextureWrap::Repeat);
         let mark_material = Material::new(context, "angrygl_assets/bullet/burn_mark.png", &texture_config).unwrap();
 
+        let instances = vec![Vec4::ZERO; MAX_BURN_MARKS];
+        let instance_buffer = create_vertex_buffer_init(context, instances.as_slice(), "burn mark instances");
+
         Self {
             unit_square,
             mark_material,
-            marks: vec![],
+            instance_buffer,
+            num_marks: 0,
+            instances,
         }
     }
 
-    pub fn add_mark(&mut self, position: Vec3) {
-        self.marks.push(BurnMark {
-            position,
-            time_left: BURN_MARK_TIME,
-        });
-    }
-
-    pub fn draw_marks(&mut self, projection_view: &Mat4, delta_time: f32) {
-        if self.marks.is_empty() {
-            return;
-        }
-
-        // shader.use_shader();
-        // shader.set_mat4("PV", projection_view);
-
-        // bind_texture(shader, 0, "texture_diffuse", &self.mark_texture);
-        // bind_texture(shader, 1, "texture_normal", &self.mark_texture);
+    /// Uploads x, z, scale and alpha of each mark. Marks shrink and fade out over BURN_MARK_TIME.
+    pub fn update(&mut self, context: &GpuContext, marks: &[BurnMark]) {
+        let marks = &marks[marks.len().saturating_sub(MAX_BURN_MARKS)..];
 
-        // unsafe {
-        //     gl::Enable(gl::BLEND);
-        //     gl::DepthMask(gl::FALSE);
-        //     gl::Disable(gl::CULL_FACE);
-        //     gl::BindVertexArray(self.unit_square_vao as GLuint);
-        // }
+        self.instances.clear();
+        self.instances.extend(marks.iter().map(|mark| {
+            let scale = 0.5 * mark.time_left;
+            let alpha = (mark.time_left / BURN_MARK_TIME).clamp(0.0, 1.0);
+            vec4(mark.position.x, mark.position.z, scale, alpha)
+        }));
 
-        for mark in self.marks.iter_mut() {
-            let scale: f32 = 0.5 * mark.time_left;
-            mark.time_left -= delta_time;
+        self.num_marks = self.instances.len() as u32;
 
-            // model *= Mat4::from_translation(vec3(mark.x, 0.01, mark.z));
-            let mut model = Mat4::from_translation(mark.position);
-
-            model *= Mat4::from_rotation_x(-90.0f32.to_radians());
-            model *= Mat4::from_scale(vec3(scale, scale, scale));
-
-            // shader.set_mat4("model", &model);
-
-            // unsafe {
-            //     gl::DrawArrays(gl::TRIANGLES, 0, 6);
-            // }
+        if !self.instances.is_empty() {
+            update_uniform_buffer(context, &self.instance_buffer, self.instances.as_slice());
         }
-
-        self.marks.retain(|m| m.time_left > 0.0);
-
-        // unsafe {
-        //     gl::Disable(gl::BLEND);
-        //     gl::DepthMask(gl::TRUE);
-        //     gl::Enable(gl::CULL_FACE);
-        // }
     }
 }
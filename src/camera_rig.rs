@@ -0,0 +1,176 @@
+use glam::{vec3, Mat4, Vec3};
+
+use crate::config::CameraConfig;
+use crate::game_state::GameState;
+
+//
+// Camera behaviour on top of the raw views. The follow camera trails the player with damping,
+// and trauma from firing and nearby explosions shakes whichever camera is active. The shake is
+// only applied to the rendered view, aiming and the shadow cascades use the steady camera.
+// Damping and trauma amounts come from the config file, see CameraConfig.
+//
+
+// Shake at full trauma, it scales with trauma squared so small amounts barely show
+const MAX_SHAKE_OFFSET: f32 = 0.12;
+const MAX_SHAKE_ROLL: f32 = 2.5 * std::f32::consts::PI / 180.0;
+const SHAKE_FREQUENCY: f32 = 30.0;
+
+// Top down and side camera positions relative to the focus. Their views are orthographic so the
+// distance only has to keep the scene between the clip planes.
+pub const TOP_DOWN_OFFSET: Vec3 = vec3(0.0, 20.0, 0.0);
+pub const SIDE_OFFSET: Vec3 = vec3(0.0, 1.0, -20.0);
+
+/// Cameras cycled through with the C key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraType {
+    Game,
+    Floating,
+    TopDown,
+    Side,
+}
+
+impl CameraType {
+    pub fn next(self) -> Self {
+        match self {
+            CameraType::Game => CameraType::Floating,
+            CameraType::Floating => CameraType::TopDown,
+            CameraType::TopDown => CameraType::Side,
+            CameraType::Side => CameraType::Game,
+        }
+    }
+}
+
+pub struct CameraRig {
+    pub config: CameraConfig,
+    /// Point the follow camera looks at, trailing the player.
+    pub focus: Vec3,
+    trauma: f32,
+    shake_time: f32,
+}
+
+impl CameraRig {
+    pub fn new(config: CameraConfig, focus: Vec3) -> Self {
+        Self {
+            config,
+            focus,
+            trauma: 0.0,
+            shake_time: 0.0,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.focus + Vec3::from(self.config.follow_offset)
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Jumps to `target` and stops shaking, for a new run.
+    pub fn snap_to(&mut self, target: Vec3) {
+        self.focus = target;
+        self.trauma = 0.0;
+    }
+
+    /// Moves the focus towards `target`. The damping is exponential so it settles the same way at any frame rate.
+    pub fn follow(&mut self, target: Vec3, delta_time: f32) {
+        let t = 1.0 - (-self.config.follow_damping * delta_time).exp();
+        self.focus = self.focus.lerp(target, t);
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    /// Trauma from the last simulation tick: `shots_fired` shots and the enemies that exploded near the player.
+    pub fn add_tick_trauma(&mut self, state: &GameState, shots_fired: u32) {
+        self.add_trauma(self.config.fire_trauma * shots_fired as f32);
+
+        // explosions start at age 0 on the tick the enemy died
+        let radius = self.config.explosion_shake_radius;
+        for sprite in state.impact_sprites.iter().filter(|sprite| sprite.age == 0.0) {
+            let distance = sprite.world_position.distance(state.player.position);
+            if distance < radius {
+                self.add_trauma(self.config.explosion_trauma * (1.0 - distance / radius));
+            }
+        }
+    }
+
+    pub fn update_shake(&mut self, delta_time: f32) {
+        self.trauma = (self.trauma - self.config.trauma_decay * delta_time).max(0.0);
+        self.shake_time += delta_time;
+    }
+
+    /// `view` moved and rolled by the current shake.
+    pub fn shake_view(&self, view: Mat4) -> Mat4 {
+        let shake = self.trauma * self.trauma;
+        if shake <= 0.0 {
+            return view;
+        }
+
+        let t = self.shake_time * SHAKE_FREQUENCY;
+        let offset = vec3(shake_noise(t, 0.0), shake_noise(t, 1.0), shake_noise(t, 2.0)) * (MAX_SHAKE_OFFSET * shake);
+        let roll = shake_noise(t, 3.0) * MAX_SHAKE_ROLL * shake;
+
+        Mat4::from_rotation_z(roll) * Mat4::from_translation(offset) * view
+    }
+}
+
+// Smooth noise between -1 and 1, a different curve for each channel
+fn shake_noise(t: f32, channel: f32) -> f32 {
+    let phase = channel * 12.9898;
+    ((t + phase).sin() + (t * 2.17 + phase * 0.5).sin() * 0.5) / 1.5
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Mat4, Vec3};
+
+    use crate::camera_rig::{CameraRig, CameraType};
+    use crate::config::CameraConfig;
+
+    #[test]
+    fn test_follow_settles_the_same_at_any_frame_rate() {
+        let target = vec3(4.0, 0.0, -2.0);
+        let mut at_30 = CameraRig::new(CameraConfig::default(), Vec3::ZERO);
+        let mut at_120 = CameraRig::new(CameraConfig::default(), Vec3::ZERO);
+
+        for _ in 0..15 {
+            at_30.follow(target, 1.0 / 30.0);
+        }
+        for _ in 0..60 {
+            at_120.follow(target, 1.0 / 120.0);
+        }
+
+        assert!(at_30.focus.distance(at_120.focus) < 0.001);
+        assert!(at_30.focus.distance(target) < target.length() * 0.1);
+        assert_eq!(at_30.position(), at_30.focus + Vec3::from(CameraConfig::default().follow_offset));
+    }
+
+    #[test]
+    fn test_trauma_is_capped_and_decays() {
+        let mut rig = CameraRig::new(CameraConfig::default(), Vec3::ZERO);
+        assert_eq!(rig.shake_view(Mat4::IDENTITY), Mat4::IDENTITY);
+
+        rig.add_trauma(0.7);
+        rig.add_trauma(0.7);
+        assert_eq!(rig.trauma(), 1.0);
+
+        rig.update_shake(0.1);
+        assert!(rig.trauma() < 1.0);
+        assert_ne!(rig.shake_view(Mat4::IDENTITY), Mat4::IDENTITY);
+
+        rig.update_shake(2.0);
+        assert_eq!(rig.trauma(), 0.0);
+    }
+
+    #[test]
+    fn test_camera_types_cycle() {
+        let mut camera = CameraType::Game;
+        for _ in 0..4 {
+            camera = camera.next();
+        }
+        assert_eq!(camera, CameraType::Game);
+        assert_eq!(CameraType::TopDown.next(), CameraType::Side);
+    }
+}
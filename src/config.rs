@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Context;
+use glam::{vec3, Vec3};
+use serde::Deserialize;
+
+//
+// Gameplay and look tunables, read from a RON file, see angrygl_assets/config.ron. Every key is
+// optional and falls back to its default, unknown keys are errors so a typo doesn't silently do
+// nothing. Set ANGRY_CONFIG_RELOAD to re-apply the file while the game runs.
+//
+
+pub const CONFIG_FILE: &str = "angrygl_assets/config.ron";
+
+// How often the config file is checked for changes
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub gameplay: GameplayConfig,
+    pub lighting: LightingConfig,
+    pub camera: CameraConfig,
+}
+
+/// Values the simulation reads. Changing them changes how a seed plays out, so replays need the same values.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameplayConfig {
+    /// Game units per second.
+    pub player_speed: f32,
+    pub player_collision_radius: f32,
+    /// Game units per second, before the wave difficulty multiplier.
+    pub enemy_speed: f32,
+    /// Degrees per second.
+    pub enemy_turn_rate: f32,
+    /// Enemies closer than this push each other apart.
+    pub enemy_separation_radius: f32,
+    pub enemy_separation_weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightingConfig {
+    /// Direction towards the light, normalized when used.
+    pub light_direction: [f32; 3],
+    pub light_factor: f32,
+    /// Red and green of the light relative to its blue.
+    pub non_blue: f32,
+    /// The floor's light and ambient strength, it is lit separately from the models.
+    pub floor_light_factor: f32,
+    /// Red and green of the floor light relative to its blue.
+    pub floor_non_blue: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    /// Follow camera position relative to the point it looks at.
+    pub follow_offset: [f32; 3],
+    /// How fast the follow camera catches up with the player, per second.
+    pub follow_damping: f32,
+    /// Screen shake trauma added by each shot. Trauma is capped at 1.
+    pub fire_trauma: f32,
+    /// Trauma added by an enemy exploding right next to the player, fading out to `explosion_shake_radius`.
+    pub explosion_trauma: f32,
+    pub explosion_shake_radius: f32,
+    /// Trauma lost per second.
+    pub trauma_decay: f32,
+}
+
+impl Default for GameplayConfig {
+    fn default() -> Self {
+        Self {
+            player_speed: 5.0,
+            player_collision_radius: 0.35,
+            enemy_speed: 0.6,
+            enemy_turn_rate: 450.0,
+            enemy_separation_radius: 0.6,
+            enemy_separation_weight: 1.5,
+        }
+    }
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        Self {
+            light_direction: [-1.0, 1.0, -1.0],
+            light_factor: 0.8,
+            non_blue: 0.9,
+            floor_light_factor: 0.35,
+            floor_non_blue: 0.7,
+        }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            follow_offset: [-4.0, 4.3, 0.0],
+            follow_damping: 6.0,
+            fire_trauma: 0.06,
+            explosion_trauma: 0.45,
+            explosion_shake_radius: 4.0,
+            trauma_decay: 1.2,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Parsing config file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let config: GameConfig = ron::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let gameplay = &self.gameplay;
+        check_positive("gameplay.player_speed", gameplay.player_speed)?;
+        check_positive("gameplay.player_collision_radius", gameplay.player_collision_radius)?;
+        check_not_negative("gameplay.enemy_speed", gameplay.enemy_speed)?;
+        check_positive("gameplay.enemy_turn_rate", gameplay.enemy_turn_rate)?;
+        check_positive("gameplay.enemy_separation_radius", gameplay.enemy_separation_radius)?;
+        check_not_negative("gameplay.enemy_separation_weight", gameplay.enemy_separation_weight)?;
+
+        let lighting = &self.lighting;
+        let light_direction = Vec3::from(lighting.light_direction);
+        if !light_direction.is_finite() || light_direction.length_squared() < 0.0001 {
+            return Err(anyhow::anyhow!("lighting.light_direction must be a non zero vector"));
+        }
+        check_not_negative("lighting.light_factor", lighting.light_factor)?;
+        check_not_negative("lighting.non_blue", lighting.non_blue)?;
+        check_not_negative("lighting.floor_light_factor", lighting.floor_light_factor)?;
+        check_not_negative("lighting.floor_non_blue", lighting.floor_non_blue)?;
+
+        let camera = &self.camera;
+        if !Vec3::from(camera.follow_offset).is_finite() {
+            return Err(anyhow::anyhow!("camera.follow_offset must be finite"));
+        }
+        check_positive("camera.follow_damping", camera.follow_damping)?;
+        check_not_negative("camera.fire_trauma", camera.fire_trauma)?;
+        check_not_negative("camera.explosion_trauma", camera.explosion_trauma)?;
+        check_positive("camera.explosion_shake_radius", camera.explosion_shake_radius)?;
+        check_positive("camera.trauma_decay", camera.trauma_decay)?;
+
+        Ok(())
+    }
+}
+
+fn check_positive(name: &str, value: f32) -> anyhow::Result<()> {
+    if !(value.is_finite() && value > 0.0) {
+        return Err(anyhow::anyhow!("{} must be above zero, got {}", name, value));
+    }
+    Ok(())
+}
+
+fn check_not_negative(name: &str, value: f32) -> anyhow::Result<()> {
+    if !(value.is_finite() && value >= 0.0) {
+        return Err(anyhow::anyhow!("{} must not be negative, got {}", name, value));
+    }
+    Ok(())
+}
+
+impl LightingConfig {
+    pub fn light_direction(&self) -> Vec3 {
+        Vec3::from(self.light_direction).normalize_or_zero()
+    }
+
+    pub fn light_color(&self) -> Vec3 {
+        self.light_factor * vec3(self.non_blue * 0.406, self.non_blue * 0.723, 1.0)
+    }
+
+    pub fn ambient_color(&self) -> Vec3 {
+        self.light_factor * 0.10 * vec3(self.non_blue * 0.7, self.non_blue * 0.7, 0.7)
+    }
+
+    pub fn floor_light_color(&self) -> Vec3 {
+        self.floor_light_factor * vec3(self.floor_non_blue * 0.406, self.floor_non_blue * 0.723, 1.0)
+    }
+
+    pub fn floor_ambient_color(&self) -> Vec3 {
+        self.floor_light_factor * 0.50 * vec3(self.floor_non_blue * 0.7, self.floor_non_blue * 0.7, 0.7)
+    }
+}
+
+/// Polls the config file for changes, by modification time.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_scan: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let mut watcher = Self {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+            last_scan: Instant::now(),
+        };
+        watcher.scan();
+        watcher
+    }
+
+    /// Whether the file changed since the last poll, false between scans.
+    pub fn poll(&mut self) -> bool {
+        if self.last_scan.elapsed() < SCAN_INTERVAL {
+            return false;
+        }
+        self.last_scan = Instant::now();
+        self.scan()
+    }
+
+    fn scan(&mut self) -> bool {
+        // editors can briefly remove a file while saving, it shows up on a later scan
+        let Ok(modified) = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+        self.modified.replace(modified) != Some(modified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{GameConfig, CONFIG_FILE};
+
+    #[test]
+    fn test_config_file_matches_defaults() {
+        assert_eq!(GameConfig::load(CONFIG_FILE).unwrap(), GameConfig::default());
+    }
+
+    #[test]
+    fn test_missing_keys_default_and_bad_keys_are_errors() {
+        let config = GameConfig::parse("(gameplay: (player_speed: 7.5))").unwrap();
+        assert_eq!(config.gameplay.player_speed, 7.5);
+        assert_eq!(config.lighting, GameConfig::default().lighting);
+
+        let unknown = GameConfig::parse("(gameplay: (player_sped: 7.5))").unwrap_err();
+        assert!(format!("{:#}", unknown).contains("player_sped"), "{:#}", unknown);
+
+        let invalid = GameConfig::parse("(camera: (follow_damping: 0.0))").unwrap_err();
+        assert!(invalid.to_string().contains("camera.follow_damping"), "{}", invalid);
+    }
+}
@@ -6,25 +6,20 @@ use glam::{vec2, vec3, Mat4, Vec3};
 use spark_gap::gpu_context::GpuContext;
 use spark_gap::model::Model;
 use spark_gap::model_builder::ModelBuilder;
-use spark_gap::utils::rand_float;
-use wgpu::{BindGroup, Buffer, BufferAddress};
+use wgpu::{Buffer, BufferAddress};
 
 use crate::capsule::Capsule;
+use crate::config::GameplayConfig;
 use crate::geom::distance_between_point_and_line_segment;
-use crate::render::buffers::{
-    create_buffer_bind_group, create_uniform_bind_group_layout, create_uniform_buffer, create_uniform_buffer_init, create_vertex_buffer_init,
-    get_or_create_bind_group_layout, update_uniform_buffer,
-};
+use crate::player::PlayerState;
+use crate::render::buffers::{create_vertex_buffer, update_uniform_buffer};
 use crate::small_mesh::SmallMeshVertex;
-use crate::world::{World, MONSTER_SPEED, MONSTER_Y, PLAYER_COLLISION_RADIUS};
+use crate::spatial_hash::SpatialHash;
+use crate::world::MONSTER_Y;
 
-pub const MAX_ENEMIES: usize = 100;
+/// Enemy cap when the waves file doesn't set max_enemies.
+pub const DEFAULT_MAX_ENEMIES: usize = 100;
 pub const ENEMY_COLLIDER: Capsule = Capsule { height: 0.4, radius: 0.08 };
-const ENEMY_SPAWN_INTERVAL: f32 = 1.0; // seconds
-const SPAWNS_PER_INTERVAL: i32 = 1;
-const SPAWN_RADIUS: f32 = 10.0; // from player
-
-pub const ENEMY_UNIFORMS_BIND_GROUP_LAYOUT: &str = "enemy instances bind group layout";
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -35,17 +30,136 @@ pub struct EnemyUniform {
 
 pub struct Enemy {
     pub position: Vec3,
+    // position at the start of the last simulation tick, for render interpolation
+    pub previous_position: Vec3,
     pub direction: Vec3,
     pub is_alive: bool,
 }
 
 pub struct EnemySystem {
-    pub count_down: f32,
-    pub monster_y: f32,
     pub enemy_model: Model,
     pub instances_uniforms: Vec<EnemyUniform>,
-    pub instances_uniform_buffer: Buffer,
-    pub instances_bind_group: BindGroup,
+    // instance vertex buffer of instances_uniforms, reallocated when the enemies outgrow it
+    pub instances_buffer: Buffer,
+    pub instances_capacity: usize,
+}
+
+/// Adds an enemy on the floor at `position` facing the player.
+pub fn spawn_enemy(enemies: &mut Vec<Enemy>, position: Vec3, player_position: Vec3) {
+    let position = vec3(position.x, MONSTER_Y, position.z);
+    let mut dir = player_position - position;
+    dir.y = 0.0;
+
+    let enemy = Enemy {
+        position,
+        previous_position: position,
+        direction: dir.normalize_or_zero(),
+        is_alive: true,
+    };
+
+    enemies.push(enemy);
+}
+
+/// Steers the enemies toward the player while keeping them apart, then moves them at `speed`.
+/// Any enemy touching the player kills them.
+pub fn chase_player(enemies: &mut [Enemy], player: &mut PlayerState, gameplay: &GameplayConfig, speed: f32, delta_time: f32, frame_time: f32) {
+    let player_collision_position = vec3(player.position.x, MONSTER_Y, player.position.z);
+    let separation_radius = gameplay.enemy_separation_radius;
+
+    let mut grid = SpatialHash::new(separation_radius);
+    for (index, enemy) in enemies.iter().enumerate() {
+        grid.insert(index, enemy.position);
+    }
+    grid.build();
+
+    let desired_directions: Vec<Vec3> = enemies
+        .iter()
+        .enumerate()
+        .map(|(index, enemy)| {
+            let mut seek = player.position - enemy.position;
+            seek.y = 0.0;
+            let seek = seek.normalize_or_zero();
+
+            let separation = separation_from_neighbors(index, enemies, &grid, separation_radius);
+
+            let desired = seek + separation * gameplay.enemy_separation_weight;
+            if desired.length_squared() > 0.0001 {
+                desired.normalize()
+            } else {
+                seek
+            }
+        })
+        .collect();
+
+    let max_turn = gameplay.enemy_turn_rate.to_radians() * delta_time;
+
+    for (enemy, desired) in enemies.iter_mut().zip(desired_directions) {
+        enemy.direction = turn_toward(enemy.direction, desired, max_turn);
+        enemy.position += enemy.direction * delta_time * speed;
+
+        if player.is_alive {
+            let p1 = enemy.position - enemy.direction * (ENEMY_COLLIDER.height / 2.0);
+            let p2 = enemy.position + enemy.direction * (ENEMY_COLLIDER.height / 2.0);
+            let dist = distance_between_point_and_line_segment(&player_collision_position, &p1, &p2);
+
+            if dist <= (gameplay.player_collision_radius + ENEMY_COLLIDER.radius) {
+                // println!("GOTTEM!");
+                player.is_alive = false;
+                player.set_player_death_time(frame_time);
+                player.direction = vec2(0.0, 0.0);
+            }
+        }
+    }
+}
+
+/// Sum of pushes away from enemies closer than `separation_radius`, stronger the closer they are.
+/// Only the enemies in the grid cells around this one can be close enough.
+fn separation_from_neighbors(index: usize, enemies: &[Enemy], grid: &SpatialHash, separation_radius: f32) -> Vec3 {
+    let position = enemies[index].position;
+    let mut push = Vec3::ZERO;
+
+    for other_index in grid.nearby(position) {
+        if other_index == index {
+            continue;
+        }
+        let other = &enemies[other_index];
+
+        let mut away = position - other.position;
+        away.y = 0.0;
+        let distance = away.length();
+
+        if distance < separation_radius {
+            if distance > 0.0001 {
+                push += (away / distance) * (1.0 - distance / separation_radius);
+            } else {
+                // exactly stacked, split them apart by index
+                let angle = index as f32 * 2.399_963; // golden angle
+                push += vec3(angle.sin(), 0.0, angle.cos());
+            }
+        }
+    }
+
+    push
+}
+
+/// Rotates `current` around the y axis toward `desired` by at most `max_angle` radians.
+fn turn_toward(current: Vec3, desired: Vec3, max_angle: f32) -> Vec3 {
+    if current.length_squared() < 0.0001 {
+        return desired;
+    }
+
+    let current_heading = current.x.atan2(current.z);
+    let desired_heading = desired.x.atan2(desired.z);
+
+    let mut delta = desired_heading - current_heading;
+    if delta > PI {
+        delta -= 2.0 * PI;
+    } else if delta < -PI {
+        delta += 2.0 * PI;
+    }
+
+    let heading = current_heading + delta.clamp(-max_angle, max_angle);
+    vec3(heading.sin(), 0.0, heading.cos())
 }
 
 impl EnemySystem {
@@ -53,39 +167,27 @@ impl EnemySystem {
         // EelDog model has diffuse and height materials
         let enemy_model = ModelBuilder::new("enemy", "assets/Models/Eeldog/EelDog.FBX").build(context).unwrap();
 
-        let instances_uniform_buffer = create_uniform_buffer(context, mem::size_of::<EnemyUniform>() * MAX_ENEMIES, "enemies instances uniform vec");
-
-        let layout = get_or_create_bind_group_layout(context, ENEMY_UNIFORMS_BIND_GROUP_LAYOUT, create_uniform_bind_group_layout);
-        let instances_bind_group = create_buffer_bind_group(context, &layout, &instances_uniform_buffer, "enemies instances bind group");
+        let instances_capacity = DEFAULT_MAX_ENEMIES;
+        let instances_buffer = create_vertex_buffer(context, mem::size_of::<EnemyUniform>() * instances_capacity, "enemies instances buffer");
 
         Self {
-            count_down: ENEMY_SPAWN_INTERVAL,
-            monster_y: MONSTER_Y,
             enemy_model,
             instances_uniforms: vec![],
-            instances_uniform_buffer,
-            instances_bind_group,
+            instances_buffer,
+            instances_capacity,
         }
     }
 
-    pub fn update(&mut self, context: &mut GpuContext, world: &mut World) {
-        world.enemies.retain(|e| e.is_alive);
-
-        self.count_down -= world.delta_time;
-
-        if self.count_down <= 0.0 {
-            for _i in 0..SPAWNS_PER_INTERVAL {
-                self.spawn_enemy(world)
-            }
-            self.count_down += ENEMY_SPAWN_INTERVAL;
-        }
-
+    /// Rebuilds the per-instance transforms from the simulation's enemies and uploads them.
+    /// `alpha` interpolates between the previous and current tick positions.
+    pub fn update(&mut self, context: &GpuContext, enemies: &[Enemy], alpha: f32) {
         self.instances_uniforms.clear();
 
-        for e in world.enemies.iter_mut() {
+        for e in enemies.iter() {
             let monster_theta = (e.direction.x / e.direction.z).atan() + (if e.direction.z < 0.0 { 0.0 } else { PI });
 
-            let mut model_transform = Mat4::from_translation(e.position);
+            let position = e.previous_position.lerp(e.position, alpha);
+            let mut model_transform = Mat4::from_translation(position);
 
             model_transform *= Mat4::from_scale(Vec3::splat(0.01));
             model_transform *= Mat4::from_axis_angle(vec3(0.0, 1.0, 0.0), monster_theta);
@@ -99,88 +201,16 @@ impl EnemySystem {
             self.instances_uniforms.push(uniform);
         }
 
-        update_uniform_buffer(context, &self.instances_uniform_buffer, self.instances_uniforms.as_slice());
-    }
-
-    pub fn spawn_enemy(&mut self, world: &mut World) {
-        if world.enemies.len() == MAX_ENEMIES {
-            return;
+        if self.instances_uniforms.len() > self.instances_capacity {
+            self.grow_instances_buffer(context, self.instances_uniforms.len());
         }
 
-        let theta = (rand_float() * 360.0).to_radians();
-        let x = theta.sin().mul_add(SPAWN_RADIUS, world.player.borrow().position.x);
-        let z = theta.cos().mul_add(SPAWN_RADIUS, world.player.borrow().position.z);
-
-        let position = vec3(x, self.monster_y, z);
-        let mut dir = world.player.borrow_mut().position - position;
-        dir.y = 0.0;
-
-        let enemy = Enemy {
-            position,
-            direction: dir.normalize_or_zero(),
-            is_alive: true,
-        };
-
-        world.enemies.push(enemy);
+        update_uniform_buffer(context, &self.instances_buffer, self.instances_uniforms.as_slice());
     }
 
-    pub fn chase_player(&self, world: &mut World) {
-        let mut player = world.player.borrow_mut();
-        let player_collision_position = vec3(player.position.x, MONSTER_Y, player.position.z);
-
-        for enemy in world.enemies.iter_mut() {
-            let mut dir = player.position - enemy.position;
-            dir.y = 0.0;
-            enemy.direction = dir.normalize_or_zero();
-            enemy.position += enemy.direction * world.delta_time * MONSTER_SPEED;
-
-            if player.is_alive {
-                let p1 = enemy.position - enemy.direction * (ENEMY_COLLIDER.height / 2.0);
-                let p2 = enemy.position + enemy.direction * (ENEMY_COLLIDER.height / 2.0);
-                let dist = distance_between_point_and_line_segment(&player_collision_position, &p1, &p2);
-
-                if dist <= (PLAYER_COLLISION_RADIUS + ENEMY_COLLIDER.radius) {
-                    // println!("GOTTEM!");
-                    player.is_alive = false;
-                    player.set_player_death_time(world.frame_time);
-                    player.direction = vec2(0.0, 0.0);
-                }
-            }
-        }
+    // Doubles the capacity until `count` instances fit, so a growing horde only reallocates a few times
+    fn grow_instances_buffer(&mut self, context: &GpuContext, count: usize) {
+        self.instances_capacity = count.next_power_of_two().max(self.instances_capacity * 2);
+        self.instances_buffer = create_vertex_buffer(context, mem::size_of::<EnemyUniform>() * self.instances_capacity, "enemies instances buffer");
     }
 }
-
-// fn create_enemy_instances_bind_group_layout(context: &GpuContext) -> BindGroupLayout {
-//     context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-//         entries: &[
-//             wgpu::BindGroupLayoutEntry {
-//                 binding: 0,
-//                 visibility: wgpu::ShaderStages::VERTEX,
-//                 ty: wgpu::BindingType::Buffer {
-//                     ty: wgpu::BufferBindingType::Uniform,
-//                     has_dynamic_offset: false,
-//                     min_binding_size: wgpu::BufferSize::new((MAX_ENEMIES * 16 * 2) as _),
-//                 },
-//                 count: None,
-//             },
-//         ],
-//         label: Some("enemy instances bind group layout"),
-//     })
-// }
-
-// fn create_enemy_instances_bind_group(
-//     context: &GpuContext,
-//     bind_group_layout: &BindGroupLayout,
-//     enemy_instances: &Buffer,
-// ) -> BindGroup {
-//     context.device.create_bind_group(&wgpu::BindGroupDescriptor {
-//         layout: bind_group_layout,
-//         entries: &[
-//             wgpu::BindGroupEntry {
-//                 binding: 2,
-//                 resource: enemy_instances.as_entire_binding(),
-//             },
-//         ],
-//         label: Some("enemy instances bind group"),
-//     })
-// }
@@ -1,16 +1,32 @@
 use crate::bullets::BulletSystem;
 use crate::burn_marks::BurnMarks;
+use crate::camera_rig::{CameraRig, CameraType, SIDE_OFFSET, TOP_DOWN_OFFSET};
+use crate::config::{ConfigWatcher, GameConfig, CONFIG_FILE};
 use crate::enemy::EnemySystem;
 use crate::floor::Floor;
+use crate::game_phase::{attract_input, GamePhase, PhaseEvent, ATTRACT_RESTART_DELAY};
+use crate::game_state::{GameState, TickInput};
 use crate::muzzle_flash::MuzzleFlash;
 use crate::params::common::{DirectionLight, PointLight};
 use crate::params::shader_params::{ShaderParametersHandler, ShaderParametersUniform};
 use crate::player::Player;
+use crate::point_lights::collect_point_lights;
 use crate::quads::{create_more_obnoxious_quad, create_obnoxious_quad, create_unit_square};
+use crate::render::bullet_compute::BulletCompute;
 use crate::render::main_render::WorldRender;
+use crate::render::msaa::next_sample_count;
+use crate::render::shader_loader::SHADER_DIR;
+use crate::render::shader_watcher::ShaderWatcher;
+use crate::render::shadow_material::SHADOW_MAP_SIZE;
+use crate::replay::{Replay, ReplayMode};
+use crate::score::{HighScore, HighScores};
+use crate::shadow_cascades::fit_cascades;
 use crate::sound_system::SoundSystem;
-use crate::world::{World, FIRE_INTERVAL, FLOOR_LIGHT_FACTOR, FLOOR_NON_BLUE, LIGHT_FACTOR, MONSTER_Y, NON_BLUE, PLAYER_MODEL_SCALE, SPREAD_AMOUNT};
-use glam::{vec3, vec4, Mat4, Vec3};
+use crate::timestep::{FixedTimestep, SIMULATION_TICK};
+use crate::waves::{WaveEvent, WaveSet, WAVES_FILE};
+use crate::weapons::{WeaponSet, WeaponSwitch, WEAPONS_FILE};
+use crate::world::{World, GAME_CAMERA_FAR, GAME_CAMERA_NEAR, MONSTER_Y, PLAYER_MODEL_SCALE};
+use glam::{vec3, Mat4, Vec3};
 use spark_gap::camera::camera::Camera;
 use spark_gap::camera::camera_handler::{CameraHandler, CameraUniform};
 use spark_gap::camera::fly_camera_controller::FlyCameraController;
@@ -25,13 +41,18 @@ use std::f32::consts::PI;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Instant;
-use winit::event::{Event, WindowEvent};
+use winit::event::{ElementState, Event, MouseScrollDelta, WindowEvent};
 use winit::event_loop::EventLoop;
 use winit::keyboard;
 use winit::keyboard::NamedKey::Escape;
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 
-const PARALLELISM: i32 = 4;
+// Threads in the rayon pool used by the parallel bullet update
+const PARALLELISM: usize = 4;
+
+// F12 screenshots are saved here
+const SCREENSHOT_DIR: &str = "screenshots";
 
 // Viewport
 pub(crate) const VIEW_PORT_WIDTH: i32 = 1500;
@@ -41,13 +62,6 @@ pub(crate) const VIEW_PORT_HEIGHT: i32 = 1000;
 
 // Player
 
-pub enum CameraType {
-    Game,
-    Floating,
-    TopDown,
-    Side,
-}
-
 pub const BACKGROUND_COLOR: wgpu::Color = wgpu::Color {
     r: 0.1,
     g: 0.2,
@@ -59,25 +73,137 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
     info!("Game started.");
     info!("Loading assets");
 
+    if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(PARALLELISM).build_global() {
+        warn!("Could not configure the rayon thread pool: {}", e);
+    }
+
     let mut context = GpuContext::new(window).await;
     let mut frame_counter = FrameCounter::new();
 
+    let replay = load_replay_mode();
+    let record_path = std::env::var("ANGRY_RECORD").ok();
+
+    let seed = match &replay {
+        ReplayMode::Playing { replay, .. } => replay.seed,
+        _ => simulation_seed(),
+    };
+    info!("Simulation seed: {}", seed);
+
+    let replay = match (replay, &record_path) {
+        (ReplayMode::Live, Some(_)) => ReplayMode::Recording(Replay::new(seed, SIMULATION_TICK)),
+        (replay, _) => replay,
+    };
+
+    let config = load_config();
+    let state = GameState::with_definitions(seed, load_wave_set(), load_weapon_set(), config.gameplay);
+    let mut world = create_world(&mut context, state, config, replay);
+    world.high_scores = load_high_scores();
+
+    let (width, height, surface_format) = (context.config.width, context.config.height, context.config.format);
+    let mut scene_render = WorldRender::new(&mut context, width, height, surface_format);
+
+    let mut shader_watcher = load_shader_watcher();
+    let mut config_watcher = load_config_watcher();
+
+    event_loop
+        .run(move |event, target| {
+            match event {
+                Event::WindowEvent { event, .. } => {
+                    world.input.handle_window_event(&event);
+                    match event {
+                        WindowEvent::RedrawRequested => {
+                            frame_counter.update();
+                            world.update_time();
+
+                            if let Some(watcher) = shader_watcher.as_mut() {
+                                let changed_files = watcher.poll();
+                                if !changed_files.is_empty() {
+                                    scene_render.reload_shaders(&context, &changed_files);
+                                }
+                            }
+
+                            if config_watcher.as_mut().is_some_and(|watcher| watcher.poll()) {
+                                match GameConfig::load(CONFIG_FILE) {
+                                    Ok(config) => {
+                                        info!("Reloaded {}", CONFIG_FILE);
+                                        apply_config(&mut world, config);
+                                    }
+                                    Err(e) => error!("Keeping the current config: {:#}", e),
+                                }
+                            }
+
+                            game_run(&mut context, &mut world, &mut scene_render);
+
+                            context.window.request_redraw();
+                        }
+                        WindowEvent::KeyboardInput { event, .. } => {
+                            // if event.state == ElementState::Pressed {
+                            if event.logical_key == keyboard::Key::Named(Escape) {
+                                save_recording(&world, &record_path);
+                                target.exit()
+                            } else if event.state == ElementState::Pressed && !event.repeat {
+                                match event.physical_key {
+                                    PhysicalKey::Code(KeyCode::F4) => cycle_sample_count(&context, &mut scene_render),
+                                    PhysicalKey::Code(KeyCode::F12) => save_screenshot(&context, &mut world, &mut scene_render),
+                                    PhysicalKey::Code(key_code) => handle_key_pressed(&mut world, key_code),
+                                    _ => {}
+                                }
+                            }
+                            // }
+                        }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            let y = match delta {
+                                MouseScrollDelta::LineDelta(_, y) => y,
+                                MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                            };
+                            if y > 0.0 {
+                                world.weapon_switch = Some(WeaponSwitch::Next);
+                            } else if y < 0.0 {
+                                world.weapon_switch = Some(WeaponSwitch::Previous);
+                            }
+                        }
+                        WindowEvent::Resized(new_size) => {
+                            context.resize(new_size);
+                            if new_size.width > 0 && new_size.height > 0 {
+                                world.set_aspect_ratio(new_size.width as f32 / new_size.height as f32);
+                            }
+                            world.camera_controller.resize(&context);
+                            world.camera_handler.update_camera(&context, &world.camera_controller);
+                            scene_render.resize(&context, context.config.width, context.config.height);
+                            context.window.request_redraw();
+                        }
+                        WindowEvent::CloseRequested => {
+                            save_recording(&world, &record_path);
+                            target.exit()
+                        }
+                        _ => {}
+                    }
+                }
+                Event::DeviceEvent { event, .. } => {
+                    world.input.handle_device_event(&event);
+                }
+                _ => {}
+            }
+        })
+        .unwrap();
+}
+
+/// Loads the models and sets up the cameras, lights and GPU buffers around a simulation state.
+pub(crate) fn create_world(context: &mut GpuContext, state: GameState, config: GameConfig, replay: ReplayMode) -> World {
     // --- Lighting ---
 
     // let light_dir: Vec3 = vec3(-0.8, 0.0, -1.0).normalize_or_zero();
-    let light_direction: Vec3 = vec3(-1.0, 1.0, -1.0).normalize_or_zero();
-    
-    let muzzle_point_light_color = vec3(1.0, 0.2, 0.0);
+    let light_direction: Vec3 = config.lighting.light_direction();
 
-    let light_color: Vec3 = LIGHT_FACTOR * 1.0 * vec3(NON_BLUE * 0.406, NON_BLUE * 0.723, 1.0);
-    let ambient_color: Vec3 = LIGHT_FACTOR * 0.10 * vec3(NON_BLUE * 0.7, NON_BLUE * 0.7, 0.7);
+    let light_color: Vec3 = config.lighting.light_color();
+    let ambient_color: Vec3 = config.lighting.ambient_color();
 
-    let floor_light_color: Vec3 = FLOOR_LIGHT_FACTOR * 1.0 * vec3(FLOOR_NON_BLUE * 0.406, FLOOR_NON_BLUE * 0.723, 1.0);
-    let floor_ambient_color: Vec3 = FLOOR_LIGHT_FACTOR * 0.50 * vec3(FLOOR_NON_BLUE * 0.7, FLOOR_NON_BLUE * 0.7, 0.7);
+    let floor_light_color: Vec3 = config.lighting.floor_light_color();
+    let floor_ambient_color: Vec3 = config.lighting.floor_ambient_color();
 
     // --- Cameras ---
 
-    let camera_follow_vec = vec3(-4.0, 4.3, 0.0);
+    let camera_rig = CameraRig::new(config.camera, state.player.position);
     let _camera_up = vec3(0.0, 1.0, 0.0);
 
     let game_camera = Camera::camera_vec3_up_yaw_pitch(
@@ -96,50 +222,58 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
 
     let ortho_camera = Camera::camera_vec3_up_yaw_pitch(vec3(0.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0, -90.0);
 
-    let ortho_width = VIEW_PORT_WIDTH as f32 / 130.0;
-    let ortho_height = VIEW_PORT_HEIGHT as f32 / 130.0;
     // let aspect_ratio = VIEW_PORT_WIDTH as f32 / VIEW_PORT_HEIGHT as f32;
     let aspect_ratio = context.config.width as f32 / context.config.height as f32;
-    
-    let game_projection = Mat4::perspective_rh(game_camera.zoom.to_radians(), 1.0, 0.1, 100.0);
-    let floating_projection = Mat4::perspective_rh(floating_camera.zoom.to_radians(), aspect_ratio, 0.1, 100.0);
-    let orthographic_projection = Mat4::orthographic_rh(-ortho_width, ortho_width, -ortho_height, ortho_height, 0.1, 100.0);
 
     let camera_position = vec3(0.0, 100.0, 300.0);
     let camera_controller = FlyCameraController::new(aspect_ratio, camera_position, 0.0, 0.0);
-    let camera_handler = CameraHandler::new(&mut context, &camera_controller);
+    let camera_handler = CameraHandler::new(context, &camera_controller);
 
     let view_position = vec3(100.0, 100.0, 300.0);
 
-    let mut shader_params = ShaderParametersHandler::new(&mut context);
+    let mut shader_params = ShaderParametersHandler::new(context);
 
     shader_params.set_direction_light_color(light_color);
     shader_params.set_direction_light_direction(light_direction.clone());
     shader_params.set_view_position(view_position.clone());
     shader_params.set_ambient_color(ambient_color);
+    shader_params.set_floor_light_color(floor_light_color);
+    shader_params.set_floor_ambient_color(floor_ambient_color);
     shader_params.set_use_light(true);
-    shader_params.set_use_point_light(true);
     shader_params.set_use_emissive(true);
     shader_params.set_use_specular(true);
 
     // --- quads ---
 
-    let unit_square_quad = create_unit_square(&mut context);
-    let _obnoxious_quad = create_obnoxious_quad(&mut context);
-    let more_obnoxious_quad = create_more_obnoxious_quad(&mut context);
-
-    let mut player = Player::new(&mut context);
-    let floor = Floor::new(&mut context);
-    let enemy_system = EnemySystem::new(&mut context);
-    let muzzle_flash = MuzzleFlash::new(&mut context, unit_square_quad.clone());
-    let bullet_system = BulletSystem::new(&mut context, unit_square_quad.clone());
+    let unit_square_quad = create_unit_square(context);
+    let _obnoxious_quad = create_obnoxious_quad(context);
+    let more_obnoxious_quad = create_more_obnoxious_quad(context);
+
+    let mut player = Player::new(context);
+    let floor = Floor::new(context);
+    let enemy_system = EnemySystem::new(context);
+    let muzzle_flash = MuzzleFlash::new(context, unit_square_quad.clone(), &state.weapons);
+    let bullet_system = BulletSystem::new(context, unit_square_quad.clone(), &state.weapons);
+
+    let bullet_compute = if context
+        .adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+    {
+        Some(BulletCompute::new(&context.device))
+    } else {
+        info!("Adapter has no compute shaders, bullets stay on the CPU");
+        None
+    };
 
-    let mut scene_render = WorldRender::new(&mut context);
+    let replay_is_playing = replay.is_playing();
 
     let mut world = World {
         start_instant: Instant::now(),
         delta_time: 0.0,
         frame_time: 0.0,
+        timestep: FixedTimestep::new(SIMULATION_TICK),
         first_mouse: false,
         run: true,
         window_scale: (0.0, 0.0),
@@ -147,16 +281,20 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
         mouse_x: 0.0,
         mouse_y: 0.0,
         input: Input::default(),
+        weapon_switch: None,
         camera_controller,
         camera_handler,
-        camera_follow_vec,
+        camera_rig,
         game_camera,
         floating_camera,
         ortho_camera,
         active_camera: CameraType::Game,
-        game_projection,
-        floating_projection,
-        orthographic_projection,
+        // replays go straight into play
+        phase: if replay_is_playing { GamePhase::Playing } else { GamePhase::Title },
+        // set from the aspect ratio below
+        game_projection: Mat4::IDENTITY,
+        floating_projection: Mat4::IDENTITY,
+        orthographic_projection: Mat4::IDENTITY,
         light_direction,
         player: player.into(),
         // scene_render: scene_render.into(),
@@ -165,51 +303,149 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
         enemy_system: RefCell::new(enemy_system).into(),
         muzzle_flash: RefCell::new(muzzle_flash).into(),
         bullet_system: RefCell::new(bullet_system).into(),
-        enemies: vec![],
-        burn_marks: BurnMarks::new(&mut context, unit_square_quad.clone()),
+        bullet_compute,
+        use_gpu_bullets: false,
+        burn_marks: BurnMarks::new(context, unit_square_quad.clone()),
+        config,
+        state,
+        high_scores: HighScores::default(),
+        replay,
         // sound_system: SoundSystem::new(),
     };
 
-    event_loop
-        .run(move |event, target| {
-            match event {
-                Event::WindowEvent { event, .. } => {
-                    world.input.handle_window_event(&event);
-                    match event {
-                        WindowEvent::RedrawRequested => {
-                            frame_counter.update();
-                            world.update_time();
-
-                            game_run(&mut context, &mut world, &mut scene_render);
+    world.set_aspect_ratio(aspect_ratio);
+    world
+}
 
-                            context.window.request_redraw();
-                        }
-                        WindowEvent::KeyboardInput { event, .. } => {
-                            // if event.state == ElementState::Pressed {
-                            if event.logical_key == keyboard::Key::Named(Escape) {
-                                target.exit()
-                            } else {
-                            }
-                            // }
-                        }
-                        WindowEvent::Resized(new_size) => {
-                            context.resize(new_size);
-                            world.camera_controller.resize(&context);
-                            world.camera_handler.update_camera(&context, &world.camera_controller);
-                            scene_render.resize(&context);
-                            context.window.request_redraw();
-                        }
-                        WindowEvent::CloseRequested => target.exit(),
-                        _ => {}
-                    }
-                }
-                Event::DeviceEvent { event, .. } => {
-                    world.input.handle_device_event(&event);
-                }
-                _ => {}
+/// One shot hotkeys, held keys are read from `world.input` each frame.
+fn handle_key_pressed(world: &mut World, key_code: KeyCode) {
+    match key_code {
+        KeyCode::Enter | KeyCode::Space => change_phase(world, PhaseEvent::Start),
+        KeyCode::KeyP => change_phase(world, PhaseEvent::TogglePause),
+        KeyCode::KeyR => change_phase(world, PhaseEvent::Restart),
+        KeyCode::KeyC => {
+            world.active_camera = world.active_camera.next();
+            info!("Camera: {:?}", world.active_camera);
+        }
+        KeyCode::F2 => {
+            let bullets = &mut world.state.bullets;
+            bullets.update_mode = bullets.update_mode.toggled();
+            info!("Bullet update mode: {:?}", bullets.update_mode);
+        }
+        KeyCode::F3 => {
+            if world.bullet_compute.is_some() {
+                world.use_gpu_bullets = !world.use_gpu_bullets;
+                info!("GPU bullets: {}", world.use_gpu_bullets);
             }
-        })
-        .unwrap();
+        }
+        KeyCode::Digit1 => world.weapon_switch = Some(WeaponSwitch::Select(0)),
+        KeyCode::Digit2 => world.weapon_switch = Some(WeaponSwitch::Select(1)),
+        KeyCode::Digit3 => world.weapon_switch = Some(WeaponSwitch::Select(2)),
+        KeyCode::Digit4 => world.weapon_switch = Some(WeaponSwitch::Select(3)),
+        KeyCode::Digit5 => world.weapon_switch = Some(WeaponSwitch::Select(4)),
+        KeyCode::Digit6 => world.weapon_switch = Some(WeaponSwitch::Select(5)),
+        KeyCode::Digit7 => world.weapon_switch = Some(WeaponSwitch::Select(6)),
+        KeyCode::Digit8 => world.weapon_switch = Some(WeaponSwitch::Select(7)),
+        KeyCode::Digit9 => world.weapon_switch = Some(WeaponSwitch::Select(8)),
+        _ => {}
+    }
+}
+
+fn cycle_sample_count(context: &GpuContext, scene_render: &mut WorldRender) {
+    let sample_count = next_sample_count(context, scene_render.sample_count());
+    scene_render.set_sample_count(context, sample_count);
+    info!("MSAA samples: {}", scene_render.sample_count());
+}
+
+/// Renders the current frame again offscreen and saves it under SCREENSHOT_DIR.
+fn save_screenshot(context: &GpuContext, world: &mut World, scene_render: &mut WorldRender) {
+    // milliseconds so quick presses don't overwrite each other
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let path = std::path::Path::new(SCREENSHOT_DIR).join(format!("screenshot_{}.png", millis));
+
+    let result = scene_render.render_to_image(context, world).and_then(|image| {
+        std::fs::create_dir_all(SCREENSHOT_DIR)?;
+        image.save(&path)?;
+        Ok(())
+    });
+
+    match result {
+        Ok(_) => info!("Saved screenshot {}", path.display()),
+        Err(e) => error!("Failed to save screenshot {}: {:#}", path.display(), e),
+    }
+}
+
+fn change_phase(world: &mut World, event: PhaseEvent) {
+    let Some(next_phase) = world.phase.next(event) else {
+        return;
+    };
+
+    if world.phase.starts_new_run(event) {
+        restart_game(world);
+    }
+
+    if event == PhaseEvent::PlayerDied {
+        record_high_score(world);
+    }
+
+    info!("{:?} -> {:?}", world.phase, next_phase);
+    world.phase = next_phase;
+}
+
+fn load_high_scores() -> HighScores {
+    let Some(path) = HighScores::default_path() else {
+        return HighScores::default();
+    };
+    HighScores::load(&path).unwrap_or_else(|e| {
+        warn!("Starting a new high score table: {:#}", e);
+        HighScores::default()
+    })
+}
+
+fn record_high_score(world: &mut World) {
+    let score = &world.state.score;
+    info!(
+        "Score: {}  kills: {}  accuracy: {:.0}%  survived: {:.1}s",
+        score.points,
+        score.kills,
+        score.accuracy() * 100.0,
+        score.survival_time
+    );
+
+    // replays would just repeat a score already in the table
+    if world.replay.is_playing() {
+        return;
+    }
+
+    let Some(rank) = world.high_scores.insert(HighScore::from_score(score, world.state.seed)) else {
+        return;
+    };
+    info!("New high score, rank {}", rank + 1);
+
+    if let Some(path) = HighScores::default_path() {
+        if let Err(e) = world.high_scores.save(&path) {
+            error!("Could not save high scores: {:#}", e);
+        }
+    }
+}
+
+/// Resets the simulation, timers and player animation for a new run. GPU resources are kept,
+/// they are refilled from the new state on the next frame.
+fn restart_game(world: &mut World) {
+    let seed = match &world.replay {
+        ReplayMode::Playing { replay, .. } => replay.seed,
+        _ => simulation_seed(),
+    };
+    info!("Simulation seed: {}", seed);
+
+    world.replay.restart(seed);
+    world.state.reset(seed);
+    world.timestep.reset();
+    world.player.borrow_mut().reset();
+    world.camera_rig.snap_to(world.state.player.position);
 }
 
 fn game_run(context: &mut GpuContext, world: &mut World, scene_render: &mut WorldRender) {
@@ -218,36 +454,103 @@ fn game_run(context: &mut GpuContext, world: &mut World, scene_render: &mut Worl
     world.camera_controller.update(&world.input, world.delta_time);
     world.camera_handler.update_camera(&context, &world.camera_controller);
 
-    world.player.borrow_mut().handle_input(&world.input, world.delta_time);
+    // Aim with the view the player is currently looking at, before the simulation moves them.
+    let aim_point = get_aim_point(context, world);
+    let mut tick_input = TickInput::from_input(&world.input, aim_point);
 
-    world.game_camera.position = world.player.borrow().position + world.camera_follow_vec; // + vec3(world.game_params_handler.uniform.time, 0.0, 0.0);
+    let ticks = if world.phase.is_simulating() { world.timestep.advance(world.delta_time) } else { 0 };
+    let mut bullet_compute = if world.use_gpu_bullets { world.bullet_compute.as_mut() } else { None };
 
-    let game_view = Mat4::look_at_rh(world.game_camera.position, world.player.borrow().position, world.game_camera.up);
+    // a weapon switch waits for the next tick and only goes to that one, switches while stopped are dropped
+    if !world.phase.is_simulating() {
+        world.weapon_switch = None;
+    }
+    if ticks > 0 {
+        tick_input.switch_weapon = world.weapon_switch.take();
+    }
 
-    let (projection, view) = match world.active_camera {
-        CameraType::Game => (world.game_projection, game_view),
-        CameraType::Floating => {
-            let view = Mat4::look_at_rh(world.floating_camera.position, world.player.borrow().position, world.floating_camera.up);
-            (world.floating_projection, view)
+    for _ in 0..ticks {
+        let input = match world.phase {
+            GamePhase::Title => attract_input(&world.state),
+            GamePhase::Playing => world.replay.next_input(tick_input),
+            GamePhase::Paused | GamePhase::GameOver => TickInput::default(),
+        };
+        tick_input.switch_weapon = None;
+        let groups_fired = world.state.score.groups_fired;
+
+        match bullet_compute.as_deref_mut() {
+            Some(compute) => world.state.step_with_bullet_update(world.timestep.tick, &input, |bullets, delta_time, enemies| {
+                if let Err(e) = compute.update_bullets(&context.device, &context.queue, bullets, delta_time, enemies) {
+                    warn!("GPU bullet update failed, falling back to the CPU: {}", e);
+                    bullets.update_bullets(delta_time, enemies);
+                }
+            }),
+            None => world.state.step(world.timestep.tick, &input),
         }
-        CameraType::TopDown => {
-            let view = Mat4::look_at_rh(
-                vec3(world.player.borrow().position.x, 1.0, world.player.borrow().position.z),
-                world.player.borrow().position,
-                vec3(0.0, 0.0, -1.0),
-            );
-            (world.orthographic_projection, view)
+
+        let shots_fired = world.state.score.groups_fired - groups_fired;
+        world.camera_rig.add_tick_trauma(&world.state, shots_fired);
+
+        for event in world.state.wave_events.iter() {
+            match event {
+                WaveEvent::Started(wave) => info!("Wave {} started: {}", wave + 1, world.state.wave_director.current_wave_name()),
+                WaveEvent::Cleared(wave) => info!("Wave {} cleared", wave + 1),
+            }
         }
-        CameraType::Side => {
-            let view = Mat4::look_at_rh(vec3(0.0, 0.0, -3.0), world.player.borrow().position, vec3(0.0, 1.0, 0.0));
-            (world.orthographic_projection, view)
+    }
+
+    if !world.state.player.is_alive {
+        match world.phase {
+            GamePhase::Playing => change_phase(world, PhaseEvent::PlayerDied),
+            GamePhase::Title if world.state.frame_time - world.state.player.death_time > ATTRACT_RESTART_DELAY => {
+                // keep the demo going, without touching the recording
+                let seed = simulation_seed();
+                world.state.reset(seed);
+                world.player.borrow_mut().reset();
+                world.camera_rig.snap_to(world.state.player.position);
+            }
+            _ => {}
         }
+    }
+
+    // animations stop with the simulation
+    let animation_delta_time = if world.phase.is_simulating() { world.delta_time } else { 0.0 };
+
+    update_scene(context, world, scene_render, animation_delta_time);
+
+    scene_render.render(&context, world);
+}
+
+/// Fills the camera, light and instance buffers for drawing the current state.
+pub(crate) fn update_scene(context: &GpuContext, world: &mut World, scene_render: &WorldRender, animation_delta_time: f32) {
+    // render between the last two simulation ticks
+    let alpha = world.timestep.alpha();
+    let render_lag = (1.0 - alpha) * world.timestep.tick;
+
+    let player_position = world.state.interpolated_player_position(alpha);
+
+    // every camera looks at the smoothed focus, the shake stops with the animations
+    world.camera_rig.follow(player_position, animation_delta_time);
+    world.camera_rig.update_shake(animation_delta_time);
+    let focus = world.camera_rig.focus;
+
+    world.game_camera.position = world.camera_rig.position();
+
+    let game_view = Mat4::look_at_rh(world.game_camera.position, focus, world.game_camera.up);
+
+    // projection, eye and up of the active camera
+    let (projection, eye, up) = match world.active_camera {
+        CameraType::Game => (world.game_projection, world.game_camera.position, world.game_camera.up),
+        CameraType::Floating => (world.floating_projection, world.floating_camera.position, world.floating_camera.up),
+        CameraType::TopDown => (world.orthographic_projection, focus + TOP_DOWN_OFFSET, vec3(0.0, 0.0, -1.0)),
+        CameraType::Side => (world.orthographic_projection, focus + SIDE_OFFSET, vec3(0.0, 1.0, 0.0)),
     };
+    let view = world.camera_rig.shake_view(Mat4::look_at_rh(eye, focus, up));
 
     let camera_uniform = CameraUniform {
         projection,
         view,
-        position: world.game_camera.position,
+        position: eye,
         _padding: 0,
     };
 
@@ -255,115 +558,166 @@ fn game_run(context: &mut GpuContext, world: &mut World, scene_render: &mut Worl
 
     world.camera_handler.update_camera_buffer(context, camera_uniform);
 
-    let projection_view = projection * view;
+    let aim_rotation = Mat4::from_axis_angle(vec3(0.0, 1.0, 0.0), world.state.player.aim_theta);
 
-    let mut dx: f32 = 0.0;
-    let mut dz: f32 = 0.0;
-    let mut aim_theta = 0.0f32;
+    let mut player_transform = Mat4::from_translation(player_position);
+    player_transform *= Mat4::from_scale(Vec3::splat(PLAYER_MODEL_SCALE));
+    player_transform *= aim_rotation;
 
-    if world.player.borrow().is_alive {
-        let world_ray = get_world_ray_from_mouse(
-            world.mouse_x,
-            world.mouse_y,
-            context.size.width as f32,
-            context.size.height as f32,
-            &game_view,
-            &world.game_projection,
-        );
+    let muzzle_transform = world.player.borrow().get_muzzle_position(&player_transform);
 
-        let xz_plane_point = vec3(0.0, 0.0, 0.0);
-        let xz_plane_normal = vec3(0.0, 1.0, 0.0);
+    world.muzzle_flash.borrow_mut().update(context, &world.state.muzzle_flash_ages, &muzzle_transform);
+    world.bullet_system.borrow_mut().update_buffers(context, &world.state.bullets, render_lag);
+    world.bullet_system.borrow_mut().update_impacts(context, &world.state.impact_sprites);
+    world.burn_marks.update(context, &world.state.burn_marks);
+    world.enemy_system.borrow_mut().update(context, &world.state.enemies, alpha);
 
-        let some_world_point = ray_plane_intersection(world.game_camera.position, world_ray, xz_plane_point, xz_plane_normal);
+    let muzzle_world_position = muzzle_transform.project_point3(Vec3::ZERO);
+    let point_lights = collect_point_lights(&world.state, muzzle_world_position);
+    world.shader_params.set_point_lights(&point_lights);
 
-        if let Some(world_point) = some_world_point {
-            dx = world_point.x - world.player.borrow().position.x;
-            dz = world_point.z - world.player.borrow().position.z;
-            aim_theta = (dx / dz).atan() + if dz < 0.0 { PI } else { 0.0 };
+    // shadow cascades follow the game camera
+    let cascades = fit_cascades(&world.game_projection, &game_view, GAME_CAMERA_NEAR, GAME_CAMERA_FAR, world.light_direction, SHADOW_MAP_SIZE);
+    world.shader_params.set_shadow_cascades(&cascades);
+    scene_render.update_shadow_cascades(context, &cascades);
+    
+    world.shader_params.set_model_rotation(aim_rotation);
+    world.shader_params.set_view_position(eye);
+    world.shader_params.set_time(world.frame_time);
+    
+    world.shader_params.update_buffer(context);
 
-            if world.mouse_x.abs() < 0.005 && world.mouse_y.abs() < 0.005 {
-                aim_theta = 0.0;
-            }
+    world
+        .player
+        .borrow_mut()
+        .update(context, &world.state.player, animation_delta_time, world.state.frame_time, &player_transform);
+}
+
+/// Set ANGRY_REPLAY to the path of a replay file to play it back instead of taking live input.
+fn load_replay_mode() -> ReplayMode {
+    let Ok(path) = std::env::var("ANGRY_REPLAY") else {
+        return ReplayMode::Live;
+    };
 
-            // println!("aim_theta: {}", aim_theta);
+    match Replay::load(&path) {
+        Ok(replay) => {
+            if replay.tick != SIMULATION_TICK {
+                warn!("Replay {} was recorded with a tick of {}s, running at {}s", path, replay.tick, SIMULATION_TICK);
+            }
+            info!("Playing replay {} with {} ticks", path, replay.inputs.len());
+            ReplayMode::Playing { replay, cursor: 0 }
+        }
+        Err(e) => {
+            error!("Failed to load replay {}: {}", path, e);
+            ReplayMode::Live
         }
     }
+}
 
-    let aim_rotation = Mat4::from_axis_angle(vec3(0.0, 1.0, 0.0), aim_theta);
-
-    let mut player_transform = Mat4::from_translation(world.player.borrow().position);
-    player_transform *= Mat4::from_scale(Vec3::splat(PLAYER_MODEL_SCALE));
-    player_transform *= aim_rotation;
-
-    let muzzle_transform = world.player.borrow().get_muzzle_position(&player_transform);
+/// Set ANGRY_SHADER_RELOAD to rebuild pipelines when their shaders change on disk.
+fn load_shader_watcher() -> Option<ShaderWatcher> {
+    std::env::var("ANGRY_SHADER_RELOAD").ok()?;
+    info!("Watching {} for shader changes", SHADER_DIR);
+    Some(ShaderWatcher::new(SHADER_DIR))
+}
 
-    if world.player.borrow().is_alive && world.player.borrow().is_trying_to_fire && (world.player.borrow().last_fire_time + FIRE_INTERVAL) < world.frame_time {
-        world.player.borrow_mut().last_fire_time = world.frame_time;
-        if world.bullet_system.borrow_mut().create_bullets(dx, dz, &muzzle_transform, SPREAD_AMOUNT) {
-            world.muzzle_flash.borrow_mut().add_flash();
-            // world.sound_system.play_player_shooting();
-            // println!("firing");
+fn save_recording(world: &World, record_path: &Option<String>) {
+    if let (ReplayMode::Recording(replay), Some(path)) = (&world.replay, record_path) {
+        match replay.save(path) {
+            Ok(_) => info!("Saved replay of {} ticks to {}", replay.inputs.len(), path),
+            Err(e) => error!("Failed to save replay to {}: {}", path, e),
         }
     }
+}
 
-    world.muzzle_flash.borrow_mut().update(context, world.delta_time, &muzzle_transform);
-
-    let bullet_system = world.bullet_system.clone();
-    let enemy_system = world.enemy_system.clone();
+/// Re-applies a reloaded config. Lighting, camera and speed values take effect on the next frame.
+fn apply_config(world: &mut World, config: GameConfig) {
+    let lighting = &config.lighting;
+    world.light_direction = lighting.light_direction();
+    world.shader_params.set_direction_light_color(lighting.light_color());
+    world.shader_params.set_direction_light_direction(world.light_direction);
+    world.shader_params.set_ambient_color(lighting.ambient_color());
+    world.shader_params.set_floor_light_color(lighting.floor_light_color());
+    world.shader_params.set_floor_ambient_color(lighting.floor_ambient_color());
+
+    world.camera_rig.config = config.camera;
+    world.state.set_gameplay(config.gameplay);
+    world.config = config;
+}
 
-    bullet_system.borrow_mut().update_bullets(context, world);
+/// Set ANGRY_CONFIG_RELOAD to re-apply the config file when it changes on disk.
+fn load_config_watcher() -> Option<ConfigWatcher> {
+    std::env::var("ANGRY_CONFIG_RELOAD").ok()?;
+    info!("Watching {} for changes", CONFIG_FILE);
+    Some(ConfigWatcher::new(CONFIG_FILE))
+}
 
-    if world.player.borrow().is_alive {
-        enemy_system.borrow_mut().update(context, world);
-        // TODO: remove for game
-        // enemy_system.borrow_mut().chase_player(&mut world);
+fn load_config() -> GameConfig {
+    match GameConfig::load(CONFIG_FILE) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Using the default config: {:#}", e);
+            GameConfig::default()
+        }
     }
+}
 
-    let mut use_point_light = true; // false;
-    let mut muzzle_world_position = Vec3::default();
-
-    if !world.muzzle_flash.borrow().sprites_age.is_empty() {
-        let min_age = world.muzzle_flash.borrow().get_min_age();
-        let muzzle_world_position_vec4 = muzzle_transform * vec4(0.0, 0.0, 0.0, 1.0);
+fn load_wave_set() -> WaveSet {
+    match WaveSet::load(WAVES_FILE) {
+        Ok(wave_set) => wave_set,
+        Err(e) => {
+            warn!("Using the default endless wave: {:#}", e);
+            WaveSet::default()
+        }
+    }
+}
 
-        muzzle_world_position = vec3(
-            muzzle_world_position_vec4.x / muzzle_world_position_vec4.w,
-            muzzle_world_position_vec4.y / muzzle_world_position_vec4.w,
-            muzzle_world_position_vec4.z / muzzle_world_position_vec4.w,
-        );
+fn load_weapon_set() -> WeaponSet {
+    match WeaponSet::load(WEAPONS_FILE) {
+        Ok(weapon_set) => weapon_set,
+        Err(e) => {
+            error!("Using the default weapon only: {:#}", e);
+            WeaponSet::default()
+        }
+    }
+}
 
-        use_point_light = min_age < 0.03;
+/// Seed for the simulation RNG. Set ANGRY_SEED to replay a run, otherwise it comes from the clock.
+fn simulation_seed() -> u64 {
+    if let Some(seed) = std::env::var("ANGRY_SEED").ok().and_then(|s| s.parse::<u64>().ok()) {
+        return seed;
     }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
 
-    let player_position = world.player.borrow().position;
+/// Returns the point on the floor under the mouse, as seen from the game camera without shake. The other
+/// cameras are for looking around, aiming always goes through the game camera.
+fn get_aim_point(context: &GpuContext, world: &World) -> Option<Vec3> {
+    if !world.state.player.is_alive {
+        return None;
+    }
 
-    // let light_direction = vec3(-1.0, 1.0, -1.0).normalize_or_zero();
+    if world.mouse_x.abs() < 0.005 && world.mouse_y.abs() < 0.005 {
+        return None;
+    }
 
-    let light_view = Mat4::look_at_rh(player_position + 10.0 * world.light_direction, player_position, vec3(0.0, 1.0, 0.0));
-    
-    /*
-    let top = 3.0;
-    let bottom = -3.0;
-    let left = -3.0;
-    let right = 3.0;
-    let ortho_projection = Mat4::orthographic_rh(left, right, bottom, top, 2.0, 20.0);
-    let light_space_matrix = ortho_projection * light_view;
-     */
-    
-    // let aspect_ratio = context.config.width as f32 / context.config.height as f32;
-    let light_projection = Mat4::perspective_rh(50.0_f32.to_radians(), 1.0, 1.0, 50.0);
-    let light_space_matrix = light_projection * light_view;
+    // the view from the last rendered frame
+    let game_view = Mat4::look_at_rh(world.game_camera.position, world.camera_rig.focus, world.game_camera.up);
 
-    world.shader_params.set_light_space_matrix(light_space_matrix);
-    
-    world.shader_params.set_model_rotation(aim_rotation);
-    world.shader_params.set_view_position(world.game_camera.position.clone());
-    world.shader_params.set_use_point_light(use_point_light);
-    world.shader_params.set_time(world.frame_time);
-    
-    world.shader_params.update_buffer(context);
+    let world_ray = get_world_ray_from_mouse(
+        world.mouse_x,
+        world.mouse_y,
+        context.size.width as f32,
+        context.size.height as f32,
+        &game_view,
+        &world.game_projection,
+    );
 
-    world.player.borrow_mut().update(context, &world, &player_transform, aim_theta);
+    let xz_plane_point = vec3(0.0, 0.0, 0.0);
+    let xz_plane_normal = vec3(0.0, 1.0, 0.0);
 
-    scene_render.render(&context, world);
+    ray_plane_intersection(world.game_camera.position, world_ray, xz_plane_point, xz_plane_normal)
 }
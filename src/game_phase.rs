@@ -0,0 +1,84 @@
+use glam::vec3;
+
+use crate::game_state::{GameState, TickInput};
+
+// Seconds the attract mode lingers on the dead player before starting over
+pub const ATTRACT_RESTART_DELAY: f32 = 3.0;
+
+// Attract mode aim, radians per second and distance from the player
+const ATTRACT_AIM_SPEED: f32 = 0.8;
+const ATTRACT_AIM_DISTANCE: f32 = 5.0;
+
+/// Which screen the game is on. The simulation steps while on the title (as a demo),
+/// playing and game over, and is frozen while paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamePhase {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseEvent {
+    Start,
+    TogglePause,
+    Restart,
+    PlayerDied,
+}
+
+impl GamePhase {
+    /// The phase after `event`, or None if the event doesn't apply in this phase.
+    pub fn next(self, event: PhaseEvent) -> Option<GamePhase> {
+        match (self, event) {
+            (GamePhase::Title | GamePhase::GameOver, PhaseEvent::Start) => Some(GamePhase::Playing),
+            (GamePhase::Playing, PhaseEvent::TogglePause) => Some(GamePhase::Paused),
+            (GamePhase::Paused, PhaseEvent::TogglePause) => Some(GamePhase::Playing),
+            (GamePhase::Playing | GamePhase::Paused | GamePhase::GameOver, PhaseEvent::Restart) => Some(GamePhase::Playing),
+            (GamePhase::Playing, PhaseEvent::PlayerDied) => Some(GamePhase::GameOver),
+            _ => None,
+        }
+    }
+
+    /// Entering playing from any of these starts a fresh run.
+    pub fn starts_new_run(self, event: PhaseEvent) -> bool {
+        matches!(event, PhaseEvent::Start | PhaseEvent::Restart) && self.next(event).is_some()
+    }
+
+    pub fn is_simulating(self) -> bool {
+        self != GamePhase::Paused
+    }
+}
+
+/// Input for the title screen demo: stand still and sweep the gun around while firing.
+pub fn attract_input(state: &GameState) -> TickInput {
+    let theta = state.frame_time * ATTRACT_AIM_SPEED;
+    let aim_point = state.player.position + vec3(theta.sin(), 0.0, theta.cos()) * ATTRACT_AIM_DISTANCE;
+
+    TickInput {
+        is_firing: true,
+        aim_point: Some(aim_point),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_phase::{GamePhase, PhaseEvent};
+
+    #[test]
+    fn test_phase_transitions() {
+        assert_eq!(GamePhase::Title.next(PhaseEvent::Start), Some(GamePhase::Playing));
+        assert_eq!(GamePhase::Title.next(PhaseEvent::TogglePause), None);
+        assert_eq!(GamePhase::Playing.next(PhaseEvent::TogglePause), Some(GamePhase::Paused));
+        assert_eq!(GamePhase::Paused.next(PhaseEvent::TogglePause), Some(GamePhase::Playing));
+        assert_eq!(GamePhase::Playing.next(PhaseEvent::PlayerDied), Some(GamePhase::GameOver));
+        assert_eq!(GamePhase::Paused.next(PhaseEvent::PlayerDied), None);
+        assert_eq!(GamePhase::GameOver.next(PhaseEvent::Start), Some(GamePhase::Playing));
+        assert_eq!(GamePhase::GameOver.next(PhaseEvent::Restart), Some(GamePhase::Playing));
+
+        assert!(GamePhase::Paused.starts_new_run(PhaseEvent::Restart));
+        assert!(!GamePhase::Paused.starts_new_run(PhaseEvent::TogglePause));
+        assert!(!GamePhase::Title.starts_new_run(PhaseEvent::Restart));
+    }
+}
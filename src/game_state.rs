@@ -0,0 +1,502 @@
+use glam::Vec3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use spark_gap::input::Input;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use crate::bullets::{BulletStore, IMPACT_SPRITE_DURATION};
+use crate::burn_marks::{BurnMark, MAX_BURN_MARKS};
+use crate::config::GameplayConfig;
+use crate::enemy::{chase_player, Enemy};
+use crate::muzzle_flash::MUZZLE_FLASH_DURATION;
+use crate::player::PlayerState;
+use crate::score::Score;
+use crate::sprite_sheet::SpriteSheetSprite;
+use crate::waves::{WaveDirector, WaveEvent, WaveSet};
+use crate::weapons::{Weapon, WeaponSet, WeaponSwitch};
+
+//
+// Pure CPU simulation of the game. Nothing in here touches wgpu so it can be
+// stepped in tests, CI and on servers. The renderer only reads from it.
+//
+
+/// Movement keys held during a tick, as bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveKeys(pub u8);
+
+impl MoveKeys {
+    pub const FORWARD: MoveKeys = MoveKeys(1);
+    pub const BACK: MoveKeys = MoveKeys(1 << 1);
+    pub const LEFT: MoveKeys = MoveKeys(1 << 2);
+    pub const RIGHT: MoveKeys = MoveKeys(1 << 3);
+
+    pub fn contains(self, keys: MoveKeys) -> bool {
+        self.0 & keys.0 == keys.0
+    }
+
+    pub fn insert(&mut self, keys: MoveKeys) {
+        self.0 |= keys.0;
+    }
+}
+
+/// Player input for one simulation step.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickInput {
+    pub keys: MoveKeys,
+    pub is_firing: bool,
+    /// Point on the floor the player is aiming at.
+    pub aim_point: Option<Vec3>,
+    pub switch_weapon: Option<WeaponSwitch>,
+}
+
+impl TickInput {
+    pub fn from_input(input: &Input, aim_point: Option<Vec3>) -> Self {
+        let mut keys = MoveKeys::default();
+        for key in input.keys_held.iter() {
+            match key {
+                KeyCode::KeyW => keys.insert(MoveKeys::FORWARD),
+                KeyCode::KeyS => keys.insert(MoveKeys::BACK),
+                KeyCode::KeyA => keys.insert(MoveKeys::LEFT),
+                KeyCode::KeyD => keys.insert(MoveKeys::RIGHT),
+                _ => {}
+            }
+        }
+
+        Self {
+            keys,
+            is_firing: input.mouse_buttons_held.contains(&MouseButton::Left),
+            aim_point,
+            switch_weapon: None,
+        }
+    }
+
+    /// Sum of the held movement keys, not normalized.
+    pub fn move_direction(&self) -> Vec3 {
+        let mut direction = Vec3::splat(0.0);
+        if self.keys.contains(MoveKeys::LEFT) {
+            direction += Vec3::new(0.0, 0.0, -1.0);
+        }
+        if self.keys.contains(MoveKeys::RIGHT) {
+            direction += Vec3::new(0.0, 0.0, 1.0);
+        }
+        if self.keys.contains(MoveKeys::BACK) {
+            direction += Vec3::new(-1.0, 0.0, 0.0);
+        }
+        if self.keys.contains(MoveKeys::FORWARD) {
+            direction += Vec3::new(1.0, 0.0, 0.0);
+        }
+        direction
+    }
+}
+
+pub struct GameState {
+    pub seed: u64,
+    // All gameplay randomness comes from here so a seed and the inputs reproduce a run.
+    pub rng: StdRng,
+    pub frame_time: f32,
+    pub gameplay: GameplayConfig,
+    pub player: PlayerState,
+    pub enemies: Vec<Enemy>,
+    pub wave_director: WaveDirector,
+    // wave changes during the last step, for the UI and audio to react to
+    pub wave_events: Vec<WaveEvent>,
+    pub bullets: BulletStore,
+    pub weapons: Vec<Weapon>,
+    // index into weapons
+    pub active_weapon: usize,
+    pub impact_sprites: Vec<SpriteSheetSprite>,
+    pub burn_marks: Vec<BurnMark>,
+    pub muzzle_flash_ages: Vec<f32>,
+    pub score: Score,
+}
+
+impl GameState {
+    pub fn new(seed: u64) -> Self {
+        Self::with_waves(seed, WaveSet::default())
+    }
+
+    pub fn with_waves(seed: u64, wave_set: WaveSet) -> Self {
+        Self::with_definitions(seed, wave_set, WeaponSet::default(), GameplayConfig::default())
+    }
+
+    pub fn with_definitions(seed: u64, wave_set: WaveSet, weapon_set: WeaponSet, gameplay: GameplayConfig) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            frame_time: 0.0,
+            gameplay,
+            player: PlayerState::new(gameplay.player_speed),
+            enemies: vec![],
+            wave_director: WaveDirector::new(wave_set),
+            wave_events: vec![],
+            bullets: BulletStore::new(),
+            weapons: weapon_set.into_weapons(),
+            active_weapon: 0,
+            impact_sprites: vec![],
+            burn_marks: vec![],
+            muzzle_flash_ages: vec![],
+            score: Score::new(),
+        }
+    }
+
+    /// Starts a new run with `seed`, keeping the gameplay config, wave and weapon definitions, bullet settings and allocations.
+    pub fn reset(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.frame_time = 0.0;
+        self.player = PlayerState::new(self.gameplay.player_speed);
+        self.enemies.clear();
+        self.wave_director.reset();
+        self.wave_events.clear();
+        self.bullets.clear();
+        self.active_weapon = 0;
+        self.impact_sprites.clear();
+        self.burn_marks.clear();
+        self.muzzle_flash_ages.clear();
+        self.score = Score::new();
+    }
+
+    /// Changes the gameplay values mid run, for config hot reloading.
+    pub fn set_gameplay(&mut self, gameplay: GameplayConfig) {
+        self.gameplay = gameplay;
+        self.player.speed = gameplay.player_speed;
+    }
+
+    /// Advances the simulation by `delta_time` seconds.
+    pub fn step(&mut self, delta_time: f32, input: &TickInput) {
+        self.step_with_bullet_update(delta_time, input, |bullets, delta_time, enemies| bullets.update_bullets(delta_time, enemies));
+    }
+
+    /// Same as `step` with the bullet update supplied by the caller, which is how the GPU bullet path plugs in.
+    pub fn step_with_bullet_update(&mut self, delta_time: f32, input: &TickInput, update_bullets: impl FnOnce(&mut BulletStore, f32, &mut [Enemy])) {
+        self.frame_time += delta_time;
+        self.wave_events.clear();
+
+        self.player.previous_position = self.player.position;
+        for enemy in self.enemies.iter_mut() {
+            enemy.previous_position = enemy.position;
+        }
+
+        self.player.handle_input(input, delta_time);
+
+        let mut dx: f32 = 0.0;
+        let mut dz: f32 = 0.0;
+
+        if self.player.is_alive {
+            match input.aim_point {
+                Some(aim_point) => {
+                    dx = aim_point.x - self.player.position.x;
+                    dz = aim_point.z - self.player.position.z;
+                    self.player.aim_theta = (dx / dz).atan() + if dz < 0.0 { std::f32::consts::PI } else { 0.0 };
+                }
+                // the mouse ray missed the floor, keep firing the way the player faces
+                None => (dx, dz) = self.player.aim_theta.sin_cos(),
+            }
+        }
+
+        if let Some(switch) = input.switch_weapon {
+            self.active_weapon = switch.apply(self.active_weapon, self.weapons.len());
+        }
+
+        let weapon = &self.weapons[self.active_weapon];
+
+        if self.player.is_alive && self.player.is_trying_to_fire && (self.player.last_fire_time + weapon.definition.fire_interval) < self.frame_time {
+            self.player.last_fire_time = self.frame_time;
+            let spawn_point = self.player.muzzle_position();
+            self.bullets.create_bullets(dx, dz, spawn_point, self.active_weapon, weapon);
+            self.muzzle_flash_ages.push(0.0);
+            self.score.groups_fired += 1;
+        }
+
+        for age in self.muzzle_flash_ages.iter_mut() {
+            *age += delta_time;
+        }
+        self.muzzle_flash_ages.retain(|age| *age < MUZZLE_FLASH_DURATION);
+
+        update_bullets(&mut self.bullets, delta_time, &mut self.enemies);
+
+        for sprite in self.impact_sprites.iter_mut() {
+            sprite.age += delta_time;
+        }
+        self.impact_sprites.retain(|sprite| sprite.age < IMPACT_SPRITE_DURATION);
+
+        for mark in self.burn_marks.iter_mut() {
+            mark.time_left -= delta_time;
+        }
+        self.burn_marks.retain(|mark| mark.time_left > 0.0);
+
+        let mut kills = 0;
+        for enemy in self.enemies.iter() {
+            if !enemy.is_alive {
+                self.impact_sprites.push(SpriteSheetSprite::new(enemy.position));
+                self.burn_marks.push(BurnMark::new(enemy.position));
+                kills += 1;
+            }
+        }
+
+        if self.burn_marks.len() > MAX_BURN_MARKS {
+            let excess = self.burn_marks.len() - MAX_BURN_MARKS;
+            self.burn_marks.drain(..excess);
+        }
+
+        self.score.add_kills(kills);
+        self.score.groups_hit = self.bullets.groups_hit;
+        self.score.update(delta_time, self.player.is_alive);
+
+        self.enemies.retain(|e| e.is_alive);
+
+        if self.player.is_alive {
+            self.wave_director
+                .update(delta_time, &mut self.rng, &mut self.enemies, self.player.position, &mut self.wave_events);
+            let speed = self.gameplay.enemy_speed * self.wave_director.difficulty();
+            chase_player(&mut self.enemies, &mut self.player, &self.gameplay, speed, delta_time, self.frame_time);
+        }
+    }
+
+    /// Player position between the previous and current tick.
+    pub fn interpolated_player_position(&self, alpha: f32) -> Vec3 {
+        self.player.previous_position.lerp(self.player.position, alpha)
+    }
+
+    pub fn muzzle_flash_min_age(&self) -> f32 {
+        let mut min_age = 1000f32;
+        for age in self.muzzle_flash_ages.iter() {
+            min_age = min_age.min(*age);
+        }
+        min_age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use crate::config::GameplayConfig;
+    use crate::enemy::Enemy;
+    use crate::game_state::{GameState, MoveKeys, TickInput};
+    use crate::timestep::SIMULATION_TICK;
+    use crate::waves::WaveSet;
+    use crate::weapons::{WeaponSet, WeaponSwitch, WEAPONS_FILE};
+    use crate::world::MONSTER_Y;
+
+    const DT: f32 = SIMULATION_TICK;
+    const SEED: u64 = 1234;
+
+    #[test]
+    fn test_firing_creates_bullet_group() {
+        let mut state = GameState::new(SEED);
+        let input = TickInput {
+            is_firing: true,
+            aim_point: Some(vec3(0.0, 0.0, 10.0)),
+            ..Default::default()
+        };
+
+        for _ in 0..10 {
+            state.step(DT, &input);
+        }
+
+        assert_eq!(state.bullets.bullet_groups.len(), 1);
+        assert_eq!(state.bullets.len(), state.weapons[0].bullet_rotations.len());
+        assert_eq!(state.muzzle_flash_ages.len(), 1);
+    }
+
+    #[test]
+    fn test_firing_without_aim_point_follows_facing() {
+        let mut state = GameState::new(SEED);
+        let aim = TickInput {
+            aim_point: Some(vec3(10.0, 0.0, 0.0)),
+            ..Default::default()
+        };
+        state.step(DT, &aim);
+
+        let fire = TickInput {
+            is_firing: true,
+            aim_point: None,
+            ..Default::default()
+        };
+        for _ in 0..10 {
+            state.step(DT, &fire);
+        }
+
+        assert_eq!(state.bullets.bullet_groups.len(), 1);
+        let group = &state.bullets.bullet_groups[0];
+        let mean_direction = state.bullets.group_slots(group).map(|slot| state.bullets.bullet_directions[slot]).sum::<Vec3>();
+        assert!(mean_direction.normalize().dot(vec3(1.0, 0.0, 0.0)) > 0.99, "{:?}", mean_direction);
+    }
+
+    #[test]
+    fn test_switched_weapon_fires_its_pattern() {
+        let weapon_set = WeaponSet::load(WEAPONS_FILE).unwrap();
+        let mut state = GameState::with_definitions(SEED, WaveSet::default(), weapon_set, GameplayConfig::default());
+        let input = TickInput {
+            is_firing: true,
+            aim_point: Some(vec3(0.0, 0.0, 10.0)),
+            switch_weapon: Some(WeaponSwitch::Next),
+            ..Default::default()
+        };
+        state.step(DT, &input);
+        for _ in 0..10 {
+            state.step(DT, &TickInput { switch_weapon: None, ..input });
+        }
+
+        assert_eq!(state.active_weapon, 1);
+        let group = &state.bullets.bullet_groups[0];
+        assert_eq!(group.weapon, 1);
+        assert_eq!(group.group_size as usize, state.weapons[1].bullet_rotations.len());
+        assert_eq!(group.speed, state.weapons[1].definition.bullet_speed);
+    }
+
+    #[test]
+    fn test_bullets_kill_enemy_in_line_of_fire() {
+        let mut state = GameState::new(SEED);
+        state.enemies.push(Enemy {
+            position: vec3(0.0, MONSTER_Y, 3.0),
+            previous_position: vec3(0.0, MONSTER_Y, 3.0),
+            direction: vec3(0.0, 0.0, -1.0),
+            is_alive: true,
+        });
+
+        let input = TickInput {
+            is_firing: true,
+            aim_point: Some(vec3(0.0, 0.0, 10.0)),
+            ..Default::default()
+        };
+
+        for _ in 0..50 {
+            state.step(DT, &input);
+        }
+
+        assert!(state.enemies.iter().all(|e| e.position.distance(vec3(0.0, MONSTER_Y, 3.0)) > 0.01));
+        assert!(!state.burn_marks.is_empty());
+        assert!(!state.impact_sprites.is_empty());
+        assert_eq!(state.score.kills, 1);
+        assert!(state.score.points > 0);
+        assert!(state.score.groups_hit >= 1);
+        assert!(state.score.accuracy() > 0.0);
+    }
+
+    #[test]
+    fn test_every_kill_leaves_an_impact_sprite() {
+        let mut state = GameState::new(SEED);
+        let kills = 150;
+        for i in 0..kills {
+            let position = vec3(i as f32, MONSTER_Y, 40.0);
+            state.enemies.push(Enemy {
+                position,
+                previous_position: position,
+                direction: vec3(0.0, 0.0, -1.0),
+                is_alive: false,
+            });
+        }
+
+        state.step(DT, &TickInput::default());
+
+        assert_eq!(state.impact_sprites.len(), kills);
+        assert_eq!(state.score.kills, kills as u32);
+    }
+
+    #[test]
+    fn test_enemies_spawn_around_player() {
+        let mut state = GameState::new(SEED);
+        let input = TickInput::default();
+
+        for _ in 0..61 {
+            state.step(DT, &input);
+        }
+
+        assert_eq!(state.enemies.len(), 1);
+        let distance = state.enemies[0].position.distance(vec3(0.0, MONSTER_Y, 0.0));
+        assert!((distance - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_same_seed_and_inputs_are_deterministic() {
+        let run = |seed: u64| {
+            let mut state = GameState::new(seed);
+            for tick in 0..600 {
+                let input = TickInput {
+                    keys: MoveKeys::FORWARD,
+                    is_firing: tick % 3 == 0,
+                    aim_point: Some(vec3((tick as f32 * 0.1).sin() * 10.0, 0.0, 10.0)),
+                    switch_weapon: None,
+                };
+                state.step(DT, &input);
+            }
+            let enemies: Vec<_> = state.enemies.iter().map(|e| e.position).collect();
+            let marks: Vec<_> = state.burn_marks.iter().map(|m| m.position).collect();
+            (enemies, marks, state.player.position)
+        };
+
+        assert_eq!(run(SEED), run(SEED));
+        assert_ne!(run(SEED).0, run(SEED + 1).0);
+    }
+
+    #[test]
+    fn test_reset_matches_new_state() {
+        let input = TickInput {
+            keys: MoveKeys::RIGHT,
+            is_firing: true,
+            aim_point: Some(vec3(0.0, 0.0, 10.0)),
+            switch_weapon: None,
+        };
+
+        let mut state = GameState::new(SEED);
+        for _ in 0..300 {
+            state.step(DT, &input);
+        }
+        state.reset(SEED + 1);
+
+        let mut fresh = GameState::new(SEED + 1);
+        for _ in 0..300 {
+            state.step(DT, &input);
+            fresh.step(DT, &input);
+        }
+
+        assert_eq!(state.frame_time, fresh.frame_time);
+        assert_eq!(state.player.position, fresh.player.position);
+        assert_eq!(state.enemies.len(), fresh.enemies.len());
+        assert!(state.bullets.live_positions().eq(fresh.bullets.live_positions()));
+        assert_eq!(state.burn_marks.len(), fresh.burn_marks.len());
+    }
+
+    #[test]
+    fn test_enemy_catches_and_kills_player() {
+        let mut state = GameState::new(SEED);
+        state.enemies.push(Enemy {
+            position: vec3(2.0, MONSTER_Y, 0.0),
+            previous_position: vec3(2.0, MONSTER_Y, 0.0),
+            direction: vec3(0.0, 0.0, 1.0),
+            is_alive: true,
+        });
+
+        let input = TickInput::default();
+        for _ in 0..(5.0 / DT) as usize {
+            state.step(DT, &input);
+        }
+
+        assert!(!state.player.is_alive);
+        assert!(state.player.death_time > 0.0);
+    }
+
+    #[test]
+    fn test_stacked_enemies_separate() {
+        let mut state = GameState::new(SEED);
+        for _ in 0..2 {
+            state.enemies.push(Enemy {
+                position: vec3(8.0, MONSTER_Y, 0.0),
+                previous_position: vec3(8.0, MONSTER_Y, 0.0),
+                direction: vec3(-1.0, 0.0, 0.0),
+                is_alive: true,
+            });
+        }
+
+        let input = TickInput::default();
+        for _ in 0..60 {
+            state.step(DT, &input);
+        }
+
+        let distance = state.enemies[0].position.distance(state.enemies[1].position);
+        assert!(distance > 0.2, "enemies still stacked, distance {}", distance);
+    }
+}
@@ -0,0 +1,170 @@
+//
+// Golden image regression tests. Scenes are simulated from a fixed seed, rendered offscreen
+// and compared against the PNGs in GOLDEN_DIR.
+//
+// The scene test needs a GPU adapter and a display and is ignored by default. Run it on the lavapipe
+// software adapter so the output doesn't depend on the GPU:
+//
+//   VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json xvfb-run cargo test golden -- --ignored
+//
+// Set ANGRY_UPDATE_GOLDEN=1 to write the golden images, and again after an intended rendering
+// change, then commit them. None are committed yet, until then the scene test fails with
+// "No golden image" rather than checking anything.
+//
+
+use std::path::Path;
+
+use anyhow::bail;
+use image::RgbaImage;
+
+const GOLDEN_DIR: &str = "tests/golden";
+// Actual images of failed comparisons are written here
+const GOLDEN_OUTPUT_DIR: &str = "target/golden";
+
+const GOLDEN_WIDTH: u32 = 480;
+const GOLDEN_HEIGHT: u32 = 320;
+const GOLDEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const GOLDEN_SEED: u64 = 7;
+
+// Per channel difference allowed, rasterizers round differently
+const CHANNEL_TOLERANCE: u8 = 8;
+// Fraction of pixels allowed past the tolerance
+const MAX_MISMATCH: f32 = 0.002;
+
+#[derive(Debug)]
+struct ImageDiff {
+    mismatched: usize,
+    total: usize,
+}
+
+impl ImageDiff {
+    fn fraction(&self) -> f32 {
+        self.mismatched as f32 / self.total.max(1) as f32
+    }
+}
+
+fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> anyhow::Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        bail!("Image is {:?}, expected {:?}", actual.dimensions(), expected.dimensions());
+    }
+
+    let mismatched = actual
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, e)| a.0.iter().zip(e.0.iter()).any(|(a, e)| a.abs_diff(*e) > tolerance))
+        .count();
+
+    Ok(ImageDiff {
+        mismatched,
+        total: (actual.width() * actual.height()) as usize,
+    })
+}
+
+fn check_golden(name: &str, actual: &RgbaImage) -> anyhow::Result<()> {
+    let golden_path = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+
+    if std::env::var("ANGRY_UPDATE_GOLDEN").is_ok() {
+        std::fs::create_dir_all(GOLDEN_DIR)?;
+        actual.save(&golden_path)?;
+        return Ok(());
+    }
+
+    if !golden_path.exists() {
+        bail!("No golden image {}, run with ANGRY_UPDATE_GOLDEN=1 to create it", golden_path.display());
+    }
+
+    let expected = image::open(&golden_path)?.to_rgba8();
+    let diff = compare_images(actual, &expected, CHANNEL_TOLERANCE)?;
+
+    if diff.fraction() > MAX_MISMATCH {
+        let output_path = Path::new(GOLDEN_OUTPUT_DIR).join(format!("{}.png", name));
+        std::fs::create_dir_all(GOLDEN_OUTPUT_DIR)?;
+        actual.save(&output_path)?;
+        bail!(
+            "{}: {} of {} pixels differ, actual image saved to {}",
+            name,
+            diff.mismatched,
+            diff.total,
+            output_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compare_images_tolerates_small_differences() {
+    let expected = RgbaImage::from_pixel(4, 4, image::Rgba([100, 100, 100, 255]));
+
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, image::Rgba([100 + CHANNEL_TOLERANCE, 100, 100, 255]));
+    assert_eq!(compare_images(&actual, &expected, CHANNEL_TOLERANCE).unwrap().mismatched, 0);
+
+    actual.put_pixel(1, 0, image::Rgba([100, 100, 101 + CHANNEL_TOLERANCE, 255]));
+    let diff = compare_images(&actual, &expected, CHANNEL_TOLERANCE).unwrap();
+    assert_eq!(diff.mismatched, 1);
+    assert_eq!(diff.fraction(), 1.0 / 16.0);
+
+    assert!(compare_images(&RgbaImage::new(2, 2), &expected, CHANNEL_TOLERANCE).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[ignore = "needs a GPU adapter and a display"]
+fn test_golden_scenes() {
+    use std::sync::Arc;
+
+    use glam::vec3;
+    use spark_gap::gpu_context::GpuContext;
+    use winit::dpi::PhysicalSize;
+    use winit::event_loop::EventLoopBuilder;
+    use winit::platform::x11::EventLoopBuilderExtX11;
+    use winit::window::WindowBuilder;
+
+    use crate::config::GameConfig;
+    use crate::game_loop::{create_world, update_scene};
+    use crate::game_state::{GameState, TickInput};
+    use crate::render::main_render::WorldRender;
+    use crate::replay::ReplayMode;
+    use crate::timestep::SIMULATION_TICK;
+
+    // GpuContext still creates the device through a window, but nothing is sized from or drawn to it.
+    // Tests don't run on the main thread, and winit only allows one event loop per process
+    let event_loop = EventLoopBuilder::new().with_any_thread(true).build().unwrap();
+    let window = WindowBuilder::new()
+        .with_visible(false)
+        .with_inner_size(PhysicalSize::new(1, 1))
+        .build(&event_loop)
+        .unwrap();
+
+    let mut context = pollster::block_on(GpuContext::new(Arc::new(window)));
+    let mut world = create_world(&mut context, GameState::new(GOLDEN_SEED), GameConfig::default(), ReplayMode::Live);
+    world.set_aspect_ratio(GOLDEN_WIDTH as f32 / GOLDEN_HEIGHT as f32);
+    let mut scene_render = WorldRender::new(&mut context, GOLDEN_WIDTH, GOLDEN_HEIGHT, GOLDEN_FORMAT);
+
+    let firing = TickInput {
+        is_firing: true,
+        aim_point: Some(vec3(5.0, 0.0, 5.0)),
+        ..Default::default()
+    };
+
+    // name, ticks to simulate and their input
+    let scenes = [("start", 0, TickInput::default()), ("firing", 90, firing)];
+
+    let mut failures = vec![];
+
+    for (name, ticks, input) in scenes {
+        for _ in 0..ticks {
+            world.state.step(SIMULATION_TICK, &input);
+        }
+
+        update_scene(&context, &mut world, &scene_render, SIMULATION_TICK);
+        let image = scene_render.render_to_image(&context, &mut world).unwrap();
+
+        if let Err(e) = check_golden(name, &image) {
+            failures.push(format!("{:#}", e));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -1,21 +1,34 @@
 mod aabb;
 mod bullets;
-mod bullets_parallel;
 mod burn_marks;
+mod camera_rig;
 mod capsule;
+mod config;
 mod enemy;
 mod floor;
 mod framebuffers;
 mod game_loop;
+mod game_phase;
+mod game_state;
 mod geom;
+#[cfg(test)]
+mod golden_tests;
 mod muzzle_flash;
 mod params;
 mod player;
+mod point_lights;
 mod quads;
 mod render;
+mod replay;
+mod score;
+mod shadow_cascades;
 mod small_mesh;
 mod sound_system;
+mod spatial_hash;
 mod sprite_sheet;
+mod timestep;
+mod waves;
+mod weapons;
 mod world;
 
 use crate::game_loop::run;
@@ -10,27 +10,36 @@ use crate::render::buffers::{
 };
 use crate::small_mesh::SmallMesh;
 use crate::sprite_sheet::SpriteSheet;
+use crate::weapons::Weapon;
 
 const MAX_FLASHES: usize = 50;
 
+pub const MUZZLE_FLASH_COLUMNS: f32 = 6.0;
+pub const MUZZLE_FLASH_TIME_PER_SPRITE: f32 = 0.03;
+pub const MUZZLE_FLASH_DURATION: f32 = MUZZLE_FLASH_COLUMNS * MUZZLE_FLASH_TIME_PER_SPRITE;
+
 pub struct MuzzleFlash {
     pub sprite_mesh: SmallMesh,
-    pub impact_spritesheet: SpriteSheet,
-    pub sprites_age: Vec<f32>,
+    // one per weapon, in weapon order
+    pub spritesheets: Vec<SpriteSheet>,
     pub age_buffer: Buffer,
     pub transform_buffer: Buffer,
     pub transform_bind_group: BindGroup,
 }
 
 impl MuzzleFlash {
-    pub fn new(context: &mut GpuContext, unit_square: SmallMesh) -> Self {
+    pub fn new(context: &mut GpuContext, unit_square: SmallMesh, weapons: &[Weapon]) -> Self {
         let texture_config = TextureConfig::new().set_wrap(TextureWrap::Repeat);
-        let muzzle_flash_material = Material::new(context, "angrygl_assets/Player/muzzle_spritesheet.png", &texture_config).unwrap();
-        let muzzle_flash_impact_spritesheet = SpriteSheet::new(context, muzzle_flash_material, 6.0, 0.03);
+        let spritesheets = weapons
+            .iter()
+            .map(|weapon| {
+                let muzzle_flash_material = Material::new(context, &weapon.definition.muzzle_flash_texture, &texture_config).unwrap();
+                SpriteSheet::new(context, muzzle_flash_material, MUZZLE_FLASH_COLUMNS, MUZZLE_FLASH_TIME_PER_SPRITE)
+            })
+            .collect();
 
-        let mut sprites_age = vec![0.0_f32; MAX_FLASHES];
+        let sprites_age = vec![0.0_f32; MAX_FLASHES];
         let age_buffer = create_vertex_buffer_init(context, sprites_age.as_slice(), "sprite age vec");
-        sprites_age.clear();
 
         let transform_buffer = create_mat4_buffer_init(context, &Mat4::IDENTITY, "muzzle flash transform");
         let layout = get_or_create_bind_group_layout(context, TRANSFORM_BIND_GROUP_LAYOUT, create_uniform_bind_group_layout);
@@ -38,16 +47,16 @@ impl MuzzleFlash {
 
         Self {
             sprite_mesh: unit_square,
-            impact_spritesheet: muzzle_flash_impact_spritesheet,
-            sprites_age,
+            spritesheets,
             age_buffer,
             transform_buffer,
             transform_bind_group: bind_group,
         }
     }
 
-    pub fn update(&mut self, context: &GpuContext, delta_time: f32, muzzle_transform: &Mat4) {
-        if self.sprites_age.is_empty() {
+    /// Uploads the flash transform and the ages of the live flashes from the simulation.
+    pub fn update(&mut self, context: &GpuContext, sprites_age: &[f32], muzzle_transform: &Mat4) {
+        if sprites_age.is_empty() {
             return;
         }
 
@@ -58,25 +67,6 @@ impl MuzzleFlash {
 
         update_mat4_buffer(context, &self.transform_buffer, &model_transform);
 
-        for i in 0..self.sprites_age.len() {
-            self.sprites_age[i] += delta_time;
-        }
-        let max_age = self.impact_spritesheet.uniform.num_columns * self.impact_spritesheet.uniform.time_per_sprite;
-
-        self.sprites_age.retain(|age| *age < max_age);
-
-        update_uniform_buffer(context, &self.age_buffer, self.sprites_age.as_slice());
-    }
-
-    pub fn get_min_age(&self) -> f32 {
-        let mut min_age = 1000f32;
-        for age in self.sprites_age.iter() {
-            min_age = min_age.min(*age);
-        }
-        min_age
-    }
-
-    pub fn add_flash(&mut self) {
-        self.sprites_age.push(0.0);
+        update_uniform_buffer(context, &self.age_buffer, sprites_age);
     }
 }
@@ -1,4 +1,4 @@
-use glam::Vec4;
+use glam::{Vec3, Vec4};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -20,6 +20,23 @@ impl Default for DirectionLight {
 pub struct PointLight {
     pub world_pos: Vec4,
     pub color: Vec4,
+    /// Distance where the light reaches zero.
+    pub radius: f32,
+    /// Exponent of the fade toward the radius, higher falls off faster.
+    pub falloff: f32,
+    pub _pad: [f32; 2],
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, radius: f32, falloff: f32) -> Self {
+        PointLight {
+            world_pos: position.extend(1.0),
+            color: color.extend(1.0),
+            radius,
+            falloff,
+            _pad: [0.0; 2],
+        }
+    }
 }
 
 impl Default for PointLight {
@@ -27,6 +44,9 @@ impl Default for PointLight {
         PointLight {
             world_pos: Default::default(),
             color: Default::default(),
+            radius: 1.0,
+            falloff: 1.0,
+            _pad: [0.0; 2],
         }
     }
 }
@@ -1,4 +1,6 @@
 use crate::params::common::{DirectionLight, PointLight};
+use crate::point_lights::MAX_POINT_LIGHTS;
+use crate::shadow_cascades::{ShadowCascade, SHADOW_CASCADES};
 use glam::{vec4, Mat4, Vec3, Vec4};
 use spark_gap::gpu_context::GpuContext;
 use wgpu::util::DeviceExt;
@@ -10,15 +12,20 @@ pub const SHADER_PARAMETERS_BIND_GROUP_LAYOUT: &str = "shader_params_bind_group_
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ShaderParametersUniform {
     pub direction_light: DirectionLight,
-    pub point_light: PointLight,
+    pub point_lights: [PointLight; MAX_POINT_LIGHTS],
     pub model_rotation: Mat4,
-    pub light_space_matrix: Mat4,
+    pub light_space_matrices: [Mat4; SHADOW_CASCADES],
+    // view depth where each cascade ends
+    pub cascade_splits: Vec4,
     pub view_position: Vec4,
     pub ambient_color: Vec4,
+    // the floor is lit with its own light and ambient colors
+    pub floor_light_color: Vec4,
+    pub floor_ambient_color: Vec4,
     pub time: f32,
     pub depth_mode: i32,
     pub use_light: i32,
-    pub use_point_light: i32,
+    pub num_point_lights: i32,
     pub use_emissive: i32,
     pub use_specular: i32,
     pub _pad: [f32; 2],
@@ -34,15 +41,18 @@ impl ShaderParametersHandler {
     pub fn new(context: &mut GpuContext) -> Self {
         let uniform = ShaderParametersUniform {
             direction_light: Default::default(),
-            point_light: Default::default(),
+            point_lights: [PointLight::default(); MAX_POINT_LIGHTS],
             model_rotation: Default::default(),
-            light_space_matrix: Default::default(),
+            light_space_matrices: Default::default(),
+            cascade_splits: Default::default(),
             view_position: Default::default(),
             ambient_color: Default::default(),
+            floor_light_color: Default::default(),
+            floor_ambient_color: Default::default(),
             time: 0.0,
             depth_mode: 0,
             use_light: 1,
-            use_point_light: 0,
+            num_point_lights: 0,
             use_emissive: 0,
             use_specular: 0,
             _pad: [0.0; 2],
@@ -88,8 +98,11 @@ impl ShaderParametersHandler {
         self.uniform.model_rotation = val;
     }
 
-    pub fn set_light_space_matrix(&mut self, val: Mat4) {
-        self.uniform.light_space_matrix = val;
+    pub fn set_shadow_cascades(&mut self, cascades: &[ShadowCascade; SHADOW_CASCADES]) {
+        for (i, cascade) in cascades.iter().enumerate() {
+            self.uniform.light_space_matrices[i] = cascade.light_space_matrix();
+            self.uniform.cascade_splits[i] = cascade.far;
+        }
     }
 
     pub fn set_direction_light_direction(&mut self, val: Vec3) {
@@ -100,12 +113,11 @@ impl ShaderParametersHandler {
         self.uniform.direction_light.color = vec4(val.x, val.y, val.z, 1.0);
     }
 
-    pub fn set_point_light_position(&mut self, val: Vec3) {
-        self.uniform.point_light.world_pos = vec4(val.x, val.y, val.z, 1.0);
-    }
-
-    pub fn set_point_light_color(&mut self, val: Vec3) {
-        self.uniform.point_light.color = vec4(val.x, val.y, val.z, 1.0);
+    /// Lights past MAX_POINT_LIGHTS are dropped.
+    pub fn set_point_lights(&mut self, lights: &[PointLight]) {
+        let count = lights.len().min(MAX_POINT_LIGHTS);
+        self.uniform.point_lights[..count].copy_from_slice(&lights[..count]);
+        self.uniform.num_point_lights = count as i32;
     }
 
     pub fn set_view_position(&mut self, val: Vec3) {
@@ -116,6 +128,14 @@ impl ShaderParametersHandler {
         self.uniform.ambient_color = vec4(val.x, val.y, val.z, 1.0);
     }
 
+    pub fn set_floor_light_color(&mut self, val: Vec3) {
+        self.uniform.floor_light_color = vec4(val.x, val.y, val.z, 1.0);
+    }
+
+    pub fn set_floor_ambient_color(&mut self, val: Vec3) {
+        self.uniform.floor_ambient_color = vec4(val.x, val.y, val.z, 1.0);
+    }
+
     pub fn set_depth_mode(&mut self, val: bool) {
         self.uniform.depth_mode = if val { 1 } else { 0 };
     }
@@ -124,10 +144,6 @@ impl ShaderParametersHandler {
         self.uniform.use_light = if val { 1 } else { 0 };
     }
 
-    pub fn set_use_point_light(&mut self, val: bool) {
-        self.uniform.use_point_light = if val { 1 } else { 0 };
-    }
-
     pub fn set_use_emissive(&mut self, val: bool) {
         self.uniform.use_emissive = if val { 1 } else { 0 };
     }
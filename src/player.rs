@@ -14,10 +14,9 @@ use spark_gap::texture_config::TextureType;
 use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 
-use crate::world::World;
+use crate::game_state::TickInput;
+use crate::world::{PLAYER_MODEL_GUN_HEIGHT, PLAYER_MODEL_GUN_MUZZLE_OFFSET, PLAYER_MODEL_SCALE};
 
-const PLAYER_SPEED: f32 = 5.0;
-// 1.5;
 const ANIM_TRANSITION_TIME: f32 = 0.2;
 
 const IDLE: &str = "idle";
@@ -27,9 +26,12 @@ const BACK: &str = "back";
 const LEFT: &str = "left";
 const DEAD: &str = "dead";
 
-pub struct Player {
-    pub model: Model,
+/// Gameplay state of the player, kept free of GPU resources.
+#[derive(Debug, Clone)]
+pub struct PlayerState {
     pub position: Vec3,
+    // position at the start of the last simulation tick, for render interpolation
+    pub previous_position: Vec3,
     pub direction: Vec2,
     pub speed: f32,
     pub aim_theta: f32,
@@ -37,6 +39,10 @@ pub struct Player {
     pub is_trying_to_fire: bool,
     pub is_alive: bool,
     pub death_time: f32,
+}
+
+pub struct Player {
+    pub model: Model,
     pub animation_name: Rc<str>,
     pub animations: PlayerAnimations,
     pub anim_weights: AnimationWeights,
@@ -101,6 +107,46 @@ impl Default for AnimationWeights {
     }
 }
 
+impl PlayerState {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            position: vec3(0.0, 0.0, 0.0),
+            previous_position: vec3(0.0, 0.0, 0.0),
+            direction: vec2(0.0, 0.0),
+            speed,
+            aim_theta: 0.0,
+            last_fire_time: 0.0,
+            is_trying_to_fire: false,
+            is_alive: true,
+            death_time: -1.0,
+        }
+    }
+
+    pub fn set_player_death_time(&mut self, time: f32) {
+        if self.death_time < 0.0 {
+            self.death_time = time;
+        }
+    }
+
+    pub fn handle_input(&mut self, input: &TickInput, delta_time: f32) {
+        if self.is_alive {
+            let direction_vec = input.move_direction();
+            if direction_vec.length_squared() > 0.01 {
+                self.position += direction_vec.normalize() * self.speed * delta_time;
+            }
+            self.direction = vec2(direction_vec.x, direction_vec.z);
+
+            self.is_trying_to_fire = input.is_firing;
+        }
+    }
+
+    pub fn muzzle_position(&self) -> Vec3 {
+        let aim_rotation = Mat4::from_axis_angle(vec3(0.0, 1.0, 0.0), self.aim_theta);
+        let muzzle = aim_rotation.transform_point3(vec3(0.0, PLAYER_MODEL_GUN_HEIGHT, PLAYER_MODEL_GUN_MUZZLE_OFFSET));
+        self.position + muzzle * PLAYER_MODEL_SCALE
+    }
+}
+
 impl Player {
     pub fn new(context: &mut GpuContext) -> Self {
         let player_model = ModelBuilder::new("player", "assets/Models/Player/Player.fbx")
@@ -127,15 +173,7 @@ impl Player {
 
         let player = Self {
             model: player_model,
-            position: vec3(0.0, 0.0, 0.0),
-            direction: vec2(0.0, 0.0),
-            last_fire_time: 0.0,
-            is_trying_to_fire: false,
-            is_alive: true,
-            aim_theta: 0.0,
-            death_time: -1.0,
             animation_name,
-            speed: PLAYER_SPEED,
             animations: PlayerAnimations::new(),
             anim_weights: AnimationWeights::default(),
             anim_hash,
@@ -146,6 +184,13 @@ impl Player {
         player
     }
 
+    /// Back to idle with no blending history, for a new run. The model and its buffers are kept.
+    pub fn reset(&mut self) {
+        self.animation_name = Rc::from(IDLE);
+        self.anim_weights = AnimationWeights::default();
+        self.model.play_clip(&self.animations.idle);
+    }
+
     pub fn set_animation(&mut self, animation_name: &Rc<str>, seconds: u32) {
         if !self.animation_name.eq(animation_name) {
             self.animation_name = animation_name.clone();
@@ -170,21 +215,15 @@ impl Player {
         *player_model_transform * muzzle
     }
 
-    pub fn set_player_death_time(&mut self, time: f32) {
-        if self.death_time < 0.0 {
-            self.death_time = time;
-        }
-    }
-
-    pub fn update(&mut self, context: &GpuContext, world: &World, model_transform: &Mat4, aim_theta: f32) {
-        self.model.update_animation(world.delta_time);
-        let weight_animations = self.update_animation_weights(self.direction, aim_theta, world.frame_time);
-        self.model.play_weight_animations(weight_animations.as_slice(), world.frame_time);
+    pub fn update(&mut self, context: &GpuContext, state: &PlayerState, delta_time: f32, frame_time: f32, model_transform: &Mat4) {
+        self.model.update_animation(delta_time);
+        let weight_animations = self.update_animation_weights(state.direction, state.aim_theta, state.death_time, frame_time);
+        self.model.play_weight_animations(weight_animations.as_slice(), frame_time);
 
         self.model.update_model_buffers(context, &model_transform);
     }
 
-    fn update_animation_weights(&mut self, move_vec: Vec2, aim_theta: f32, frame_time: f32) -> [WeightedAnimation; 6] {
+    fn update_animation_weights(&mut self, move_vec: Vec2, aim_theta: f32, death_time: f32, frame_time: f32) -> [WeightedAnimation; 6] {
         let is_moving = move_vec.length_squared() > 0.1;
 
         let move_theta = (move_vec.x / move_vec.y).atan() + if move_vec.y < 0.0 { PI } else { 0.0 };
@@ -194,7 +233,7 @@ impl Player {
         let anim_delta_time = frame_time - self.anim_weights.last_anim_time;
         self.anim_weights.last_anim_time = frame_time;
 
-        let is_dead = self.death_time >= 0.0;
+        let is_dead = death_time >= 0.0;
 
         self.anim_weights.prev_idle_weight = max(0.0, self.anim_weights.prev_idle_weight - anim_delta_time / ANIM_TRANSITION_TIME);
         self.anim_weights.prev_right_weight = max(0.0, self.anim_weights.prev_right_weight - anim_delta_time / ANIM_TRANSITION_TIME);
@@ -230,30 +269,9 @@ impl Player {
             WeightedAnimation::new(back_weight, 159.0, 179.0, 10.0, 0.0),
             WeightedAnimation::new(right_weight, 184.0, 204.0, 10.0, 0.0),
             WeightedAnimation::new(left_weight, 209.0, 229.0, 0.0, 0.0),
-            WeightedAnimation::new(dead_weight, 234.0, 293.0, 0.0, self.death_time),
+            WeightedAnimation::new(dead_weight, 234.0, 293.0, 0.0, death_time),
         ]
     }
-
-    pub fn handle_input(&mut self, input: &Input, delta_time: f32) {
-        if self.is_alive {
-            let mut direction_vec = Vec3::splat(0.0);
-            for key in input.keys_held.iter() {
-                match key {
-                    KeyCode::KeyA => direction_vec += vec3(0.0, 0.0, -1.0),
-                    KeyCode::KeyD => direction_vec += vec3(0.0, 0.0, 1.0),
-                    KeyCode::KeyS => direction_vec += vec3(-1.0, 0.0, 0.0),
-                    KeyCode::KeyW => direction_vec += vec3(1.0, 0.0, 0.0),
-                    _ => {}
-                }
-            }
-            if direction_vec.length_squared() > 0.01 {
-                self.position += direction_vec.normalize() * self.speed * delta_time;
-            }
-            self.direction = vec2(direction_vec.x, direction_vec.z);
-
-            self.is_trying_to_fire = input.mouse_buttons_held.contains(&MouseButton::Left);
-        }
-    }
 }
 
 fn clamp0(value: f32) -> f32 {
@@ -0,0 +1,107 @@
+use glam::{vec3, Vec3};
+
+use crate::game_state::GameState;
+use crate::params::common::PointLight;
+
+//
+// Dynamic point lights gathered from the game state each frame.
+// Listed by priority, so when there are more than the shaders take the bullets go first.
+//
+
+// Also the MAX_POINT_LIGHTS shader def
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+// Muzzle flash, only lit for the first moments of the flash
+const MUZZLE_LIGHT_COLOR: Vec3 = vec3(1.0, 0.2, 0.0);
+const MUZZLE_LIGHT_TIME: f32 = 0.03;
+const MUZZLE_LIGHT_RADIUS: f32 = 4.0;
+const MUZZLE_LIGHT_FALLOFF: f32 = 2.0;
+
+// Enemy death explosions, fading out over the light time
+const EXPLOSION_LIGHT_COLOR: Vec3 = vec3(1.0, 0.5, 0.1);
+const EXPLOSION_LIGHT_TIME: f32 = 0.25;
+const EXPLOSION_LIGHT_RADIUS: f32 = 5.0;
+const EXPLOSION_LIGHT_FALLOFF: f32 = 2.0;
+
+// One light at the center of each bullet group
+const BULLET_LIGHT_COLOR: Vec3 = vec3(1.0, 0.6, 0.2);
+const BULLET_LIGHT_RADIUS: f32 = 2.0;
+const BULLET_LIGHT_FALLOFF: f32 = 3.0;
+
+pub fn collect_point_lights(state: &GameState, muzzle_position: Vec3) -> Vec<PointLight> {
+    let mut lights = Vec::with_capacity(MAX_POINT_LIGHTS);
+
+    if !state.muzzle_flash_ages.is_empty() && state.muzzle_flash_min_age() < MUZZLE_LIGHT_TIME {
+        lights.push(PointLight::new(muzzle_position, MUZZLE_LIGHT_COLOR, MUZZLE_LIGHT_RADIUS, MUZZLE_LIGHT_FALLOFF));
+    }
+
+    // an impact sprite starts where each enemy dies
+    for sprite in state.impact_sprites.iter().filter(|sprite| sprite.age < EXPLOSION_LIGHT_TIME) {
+        let fade = 1.0 - sprite.age / EXPLOSION_LIGHT_TIME;
+        lights.push(PointLight::new(
+            sprite.world_position,
+            EXPLOSION_LIGHT_COLOR * fade,
+            EXPLOSION_LIGHT_RADIUS,
+            EXPLOSION_LIGHT_FALLOFF,
+        ));
+    }
+
+    let bullets = &state.bullets;
+    for group in bullets.bullet_groups.iter() {
+        if group.group_size <= 0 || !group.is_live() {
+            continue;
+        }
+        let center = bullets.group_slots(group).map(|slot| bullets.bullet_positions[slot]).sum::<Vec3>() / group.group_size as f32;
+        lights.push(PointLight::new(center, BULLET_LIGHT_COLOR, BULLET_LIGHT_RADIUS, BULLET_LIGHT_FALLOFF));
+    }
+
+    lights.truncate(MAX_POINT_LIGHTS);
+    lights
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use crate::game_state::GameState;
+    use crate::point_lights::{collect_point_lights, EXPLOSION_LIGHT_TIME, MAX_POINT_LIGHTS, MUZZLE_LIGHT_COLOR};
+    use crate::sprite_sheet::SpriteSheetSprite;
+
+    #[test]
+    fn test_muzzle_light_only_while_flash_is_young() {
+        let mut state = GameState::new(1);
+        let muzzle = vec3(1.0, 0.5, 2.0);
+
+        state.muzzle_flash_ages.push(0.01);
+        let lights = collect_point_lights(&state, muzzle);
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].world_pos.truncate(), muzzle);
+        assert_eq!(lights[0].color.truncate(), MUZZLE_LIGHT_COLOR);
+
+        state.muzzle_flash_ages[0] = 0.05;
+        assert!(collect_point_lights(&state, muzzle).is_empty());
+    }
+
+    #[test]
+    fn test_explosion_lights_fade_and_are_capped() {
+        let mut state = GameState::new(1);
+
+        let mut sprite = SpriteSheetSprite::new(vec3(3.0, 0.0, 3.0));
+        sprite.age = EXPLOSION_LIGHT_TIME / 2.0;
+        state.impact_sprites.push(sprite);
+
+        let lights = collect_point_lights(&state, Vec3::ZERO);
+        assert_eq!(lights.len(), 1);
+        let brightness = lights[0].color.truncate().length();
+        assert!(brightness > 0.0);
+
+        state.impact_sprites[0].age = EXPLOSION_LIGHT_TIME * 0.9;
+        let lights = collect_point_lights(&state, Vec3::ZERO);
+        assert!(lights[0].color.truncate().length() < brightness);
+
+        for _ in 0..MAX_POINT_LIGHTS * 2 {
+            state.impact_sprites.push(SpriteSheetSprite::new(Vec3::ZERO));
+        }
+        assert_eq!(collect_point_lights(&state, Vec3::ZERO).len(), MAX_POINT_LIGHTS);
+    }
+}
@@ -0,0 +1,236 @@
+use spark_gap::gpu_context::{get_or_create_bind_group_layout, GpuContext};
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, RenderPipeline, Sampler, TextureView};
+
+use crate::load_shader;
+
+pub const BLOOM_TEXTURE_BIND_GROUP_LAYOUT: &str = "bloom texture bind group layout";
+
+// Float so bright emission can go above 1.0 before it is blurred
+pub const EMISSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// The forward pass draws the scene here rather than into the swapchain format,
+// so the scene pipelines are the same with or without a window
+pub const SCENE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// The render graph's bloom targets. The blur targets are 1 / BLUR_SCALE of the output size.
+pub struct BloomTargets<'a> {
+    pub scene: &'a TextureView,
+    pub emission: &'a TextureView,
+    pub horizontal_blur: &'a TextureView,
+    pub vertical_blur: &'a TextureView,
+}
+
+// Bind groups sampling the targets, recreated when the graph reallocates them
+struct BloomBindGroups {
+    scene: BindGroup,
+    emission: BindGroup,
+    horizontal_blur: BindGroup,
+    vertical_blur: BindGroup,
+}
+
+// The forward pass draws into the scene and emission targets instead of the swapchain.
+// The emission is blurred at 1 / BLUR_SCALE size then composited over the scene.
+pub struct BloomMaterial {
+    bind_groups: BloomBindGroups,
+    pub sampler: Sampler,
+    pub pipelines: BloomPipelines,
+}
+
+/// The blur and composite pipelines, recreated when bloom_shader.wgsl is reloaded.
+pub struct BloomPipelines {
+    pub blur_horizontal: RenderPipeline,
+    pub blur_vertical: RenderPipeline,
+    pub composite: RenderPipeline,
+}
+
+/// Second forward pass target, for pipelines drawing into the emission texture.
+pub fn emission_target(blend: Option<wgpu::BlendState>) -> Option<wgpu::ColorTargetState> {
+    Some(wgpu::ColorTargetState {
+        format: EMISSION_FORMAT,
+        blend,
+        write_mask: wgpu::ColorWrites::ALL,
+    })
+}
+
+/// `output_format` is the format of the view the composite draws into, the swapchain or an offscreen texture.
+pub fn create_bloom_material(context: &mut GpuContext, targets: &BloomTargets, output_format: wgpu::TextureFormat) -> BloomMaterial {
+    let layout = get_or_create_bind_group_layout(context, BLOOM_TEXTURE_BIND_GROUP_LAYOUT, create_bloom_texture_bind_group_layout);
+
+    let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    let bind_groups = create_bloom_bind_groups(context, &layout, targets, &sampler);
+    let pipelines = create_bloom_pipelines(context, output_format);
+
+    BloomMaterial {
+        bind_groups,
+        sampler,
+        pipelines,
+    }
+}
+
+/// Needs the bloom texture bind group layout, created with the material.
+pub fn create_bloom_pipelines(context: &GpuContext, output_format: wgpu::TextureFormat) -> BloomPipelines {
+    let layout = context.bind_layout_cache.get(BLOOM_TEXTURE_BIND_GROUP_LAYOUT).unwrap();
+
+    // the composite also reads the blurred glow from group 1
+    BloomPipelines {
+        blur_horizontal: create_post_process_pipeline(context, &[layout], "fs_blur_horizontal", EMISSION_FORMAT),
+        blur_vertical: create_post_process_pipeline(context, &[layout], "fs_blur_vertical", EMISSION_FORMAT),
+        composite: create_post_process_pipeline(context, &[layout, layout], "fs_composite", output_format),
+    }
+}
+
+impl BloomMaterial {
+    /// Binds the targets again after the graph reallocated them.
+    pub fn bind_targets(&mut self, context: &GpuContext, targets: &BloomTargets) {
+        let layout = context.bind_layout_cache.get(BLOOM_TEXTURE_BIND_GROUP_LAYOUT).unwrap();
+        self.bind_groups = create_bloom_bind_groups(context, layout, targets, &self.sampler);
+    }
+
+    /// Blurs the emission target and writes the scene with the glow added to `output_view`.
+    pub fn render(&self, encoder: &mut CommandEncoder, targets: &BloomTargets, output_view: &TextureView) {
+        let bind_groups = &self.bind_groups;
+        let pipelines = &self.pipelines;
+
+        post_process_pass(
+            encoder,
+            "bloom horizontal blur pass",
+            targets.horizontal_blur,
+            &pipelines.blur_horizontal,
+            &[&bind_groups.emission],
+        );
+
+        post_process_pass(
+            encoder,
+            "bloom vertical blur pass",
+            targets.vertical_blur,
+            &pipelines.blur_vertical,
+            &[&bind_groups.horizontal_blur],
+        );
+
+        post_process_pass(
+            encoder,
+            "bloom composite pass",
+            output_view,
+            &pipelines.composite,
+            &[&bind_groups.scene, &bind_groups.vertical_blur],
+        );
+    }
+}
+
+fn post_process_pass(encoder: &mut CommandEncoder, label: &str, view: &TextureView, pipeline: &RenderPipeline, bind_groups: &[&BindGroup]) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(index as u32, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+fn create_bloom_bind_groups(context: &GpuContext, layout: &BindGroupLayout, targets: &BloomTargets, sampler: &Sampler) -> BloomBindGroups {
+    BloomBindGroups {
+        scene: create_texture_bind_group(context, layout, targets.scene, sampler),
+        emission: create_texture_bind_group(context, layout, targets.emission, sampler),
+        horizontal_blur: create_texture_bind_group(context, layout, targets.horizontal_blur, sampler),
+        vertical_blur: create_texture_bind_group(context, layout, targets.vertical_blur, sampler),
+    }
+}
+
+fn create_texture_bind_group(context: &GpuContext, layout: &BindGroupLayout, view: &TextureView, sampler: &Sampler) -> BindGroup {
+    context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bloom texture bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn create_bloom_texture_bind_group_layout(context: &GpuContext, label: &str) -> BindGroupLayout {
+    context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            // texture
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            // sampler
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some(label),
+    })
+}
+
+fn create_post_process_pipeline(
+    context: &GpuContext,
+    bind_group_layouts: &[&BindGroupLayout],
+    fragment_entry: &str,
+    format: wgpu::TextureFormat,
+) -> RenderPipeline {
+    let shader = context.device.create_shader_module(load_shader!("bloom_shader.wgsl").into());
+
+    let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("bloom pipeline layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_entry),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: fragment_entry,
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -54,6 +54,12 @@ pub fn update_uniform_buffer<T: bytemuck::Pod>(context: &GpuContext, buffer: &Bu
     context.queue.write_buffer(buffer, 0, bytemuck::cast_slice(uniform));
 }
 
+/// Writes `data` into `buffer` starting at element `first`, leaving the rest of the buffer as it was.
+pub fn update_buffer_range<T: bytemuck::Pod>(context: &GpuContext, buffer: &Buffer, first: usize, data: &[T]) {
+    let offset = (first * std::mem::size_of::<T>()) as BufferAddress;
+    context.queue.write_buffer(buffer, offset, bytemuck::cast_slice(data));
+}
+
 pub fn update_uniform_box_buffer<T: bytemuck::Pod>(context: &GpuContext, buffer: &Buffer, uniform: &Box<[T]>) {
     context.queue.write_buffer(buffer, 0, bytemuck::cast_slice(uniform));
 }
@@ -0,0 +1,471 @@
+use std::mem;
+use std::sync::mpsc;
+
+use glam::Vec3;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferAddress, ComputePipeline, Device, Queue};
+
+use crate::bullets::{BulletStore, BULLET_COLLIDER, BULLET_ENEMY_MAX_COLLISION_DIST, INITIAL_BULLET_CAPACITY};
+use crate::enemy::{Enemy, ENEMY_COLLIDER, DEFAULT_MAX_ENEMIES};
+use crate::load_shader;
+use crate::world::BULLET_GROUPS_IN_FLIGHT;
+
+//
+// Runs BulletStore::update_bullets as a compute shader. The bullets, group lifetimes and enemy capsules
+// are uploaded each tick, advanced and tested on the GPU, and the new positions, lifetimes and hit flags
+// are read back so the CPU simulation state stays the source of truth.
+//
+// Takes the device and queue rather than a GpuContext so it can run on a headless adapter in tests.
+//
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BulletSimParams {
+    delta_time: f32,
+    bullet_count: u32,
+    group_count: u32,
+    enemy_count: u32,
+    max_collision_distance: f32,
+    bullet_half_length: f32,
+    enemy_half_length: f32,
+    hit_distance: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuBullet {
+    position: Vec3,
+    group: u32,
+    direction: Vec3,
+    // speed of the bullet's group
+    speed: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuEnemy {
+    position: Vec3,
+    _padding0: f32,
+    direction: Vec3,
+    _padding1: f32,
+}
+
+pub struct BulletCompute {
+    bind_group_layout: BindGroupLayout,
+    age_groups_pipeline: ComputePipeline,
+    update_bullets_pipeline: ComputePipeline,
+
+    params_buffer: Buffer,
+    buffers: ComputeBuffers,
+    bind_group: BindGroup,
+
+    // reused upload vecs
+    gpu_bullets: Vec<GpuBullet>,
+    // store slot of each uploaded bullet, to write the positions back
+    bullet_slots: Vec<usize>,
+    gpu_enemies: Vec<GpuEnemy>,
+    group_time_to_live: Vec<f32>,
+}
+
+// Storage buffers and their read back copies, recreated when something outgrows them.
+struct ComputeBuffers {
+    bullet_capacity: usize,
+    group_capacity: usize,
+    enemy_capacity: usize,
+    bullets: Buffer,
+    group_time_to_live: Buffer,
+    enemies: Buffer,
+    enemy_hits: Buffer,
+    group_hits: Buffer,
+    bullets_read: Buffer,
+    group_time_to_live_read: Buffer,
+    enemy_hits_read: Buffer,
+    group_hits_read: Buffer,
+}
+
+impl BulletCompute {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(load_shader!("bullet_compute_shader.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                compute_layout_entry(0, wgpu::BufferBindingType::Uniform),
+                compute_layout_entry(1, wgpu::BufferBindingType::Storage { read_only: false }),
+                compute_layout_entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                compute_layout_entry(3, wgpu::BufferBindingType::Storage { read_only: true }),
+                compute_layout_entry(4, wgpu::BufferBindingType::Storage { read_only: false }),
+                compute_layout_entry(5, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+            label: Some("bullet compute bind group layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("bullet compute pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let age_groups_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("bullet age groups pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "age_groups",
+        });
+
+        let update_bullets_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("bullet update pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "update_bullets",
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bullet compute params buffer"),
+            size: mem::size_of::<BulletSimParams>() as BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let buffers = ComputeBuffers::new(device, INITIAL_BULLET_CAPACITY, BULLET_GROUPS_IN_FLIGHT as usize, DEFAULT_MAX_ENEMIES);
+        let bind_group = buffers.create_bind_group(device, &bind_group_layout, &params_buffer);
+
+        Self {
+            bind_group_layout,
+            age_groups_pipeline,
+            update_bullets_pipeline,
+            params_buffer,
+            buffers,
+            bind_group,
+            gpu_bullets: Vec::with_capacity(INITIAL_BULLET_CAPACITY),
+            bullet_slots: Vec::with_capacity(INITIAL_BULLET_CAPACITY),
+            gpu_enemies: Vec::with_capacity(DEFAULT_MAX_ENEMIES),
+            group_time_to_live: Vec::with_capacity(BULLET_GROUPS_IN_FLIGHT as usize),
+        }
+    }
+
+    /// Same as `BulletStore::update_bullets` but on the GPU. On error the store is left untouched
+    /// so the caller can fall back to the CPU update for this tick.
+    pub fn update_bullets(&mut self, device: &Device, queue: &Queue, store: &mut BulletStore, delta_time: f32, enemies: &mut [Enemy]) -> anyhow::Result<()> {
+        if store.bullet_groups.is_empty() {
+            return Ok(());
+        }
+
+        self.gpu_bullets.clear();
+        self.bullet_slots.clear();
+        self.group_time_to_live.clear();
+        for (group_index, group) in store.bullet_groups.iter().enumerate() {
+            self.group_time_to_live.push(group.time_to_live);
+
+            // an expired group waiting behind older ones is not moved or tested
+            if !group.is_live() {
+                continue;
+            }
+
+            for slot in store.group_slots(group) {
+                self.gpu_bullets.push(GpuBullet {
+                    position: store.bullet_positions[slot],
+                    group: group_index as u32,
+                    direction: store.bullet_directions[slot],
+                    speed: group.speed,
+                });
+                self.bullet_slots.push(slot);
+            }
+        }
+
+        self.gpu_enemies.clear();
+        self.gpu_enemies.extend(enemies.iter().map(|enemy| GpuEnemy {
+            position: enemy.position,
+            _padding0: 0.0,
+            direction: enemy.direction,
+            _padding1: 0.0,
+        }));
+
+        let bullet_count = self.gpu_bullets.len();
+        let group_count = self.group_time_to_live.len();
+        let enemy_count = self.gpu_enemies.len();
+
+        if bullet_count > self.buffers.bullet_capacity || group_count > self.buffers.group_capacity || enemy_count > self.buffers.enemy_capacity {
+            self.buffers = ComputeBuffers::new(
+                device,
+                bullet_count.max(self.buffers.bullet_capacity),
+                group_count.max(self.buffers.group_capacity),
+                enemy_count.max(self.buffers.enemy_capacity),
+            );
+            self.bind_group = self.buffers.create_bind_group(device, &self.bind_group_layout, &self.params_buffer);
+        }
+
+        let params = BulletSimParams {
+            delta_time,
+            bullet_count: bullet_count as u32,
+            group_count: group_count as u32,
+            enemy_count: enemy_count as u32,
+            max_collision_distance: BULLET_ENEMY_MAX_COLLISION_DIST,
+            bullet_half_length: BULLET_COLLIDER.height / 2.0,
+            enemy_half_length: ENEMY_COLLIDER.height / 2.0,
+            hit_distance: BULLET_COLLIDER.radius + ENEMY_COLLIDER.radius,
+        };
+
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+        queue.write_buffer(&self.buffers.bullets, 0, bytemuck::cast_slice(&self.gpu_bullets));
+        queue.write_buffer(&self.buffers.group_time_to_live, 0, bytemuck::cast_slice(&self.group_time_to_live));
+        queue.write_buffer(&self.buffers.enemies, 0, bytemuck::cast_slice(&self.gpu_enemies));
+
+        let bullets_size = (bullet_count * mem::size_of::<GpuBullet>()) as BufferAddress;
+        let groups_size = (group_count * mem::size_of::<f32>()) as BufferAddress;
+        let hits_size = (enemy_count.max(1) * mem::size_of::<u32>()) as BufferAddress;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("bullet compute encoder"),
+        });
+
+        encoder.clear_buffer(&self.buffers.enemy_hits, 0, Some(hits_size));
+        encoder.clear_buffer(&self.buffers.group_hits, 0, Some(groups_size));
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("bullet compute pass"),
+                timestamp_writes: None,
+            });
+
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+
+            compute_pass.set_pipeline(&self.age_groups_pipeline);
+            compute_pass.dispatch_workgroups(workgroup_count(group_count), 1, 1);
+
+            compute_pass.set_pipeline(&self.update_bullets_pipeline);
+            compute_pass.dispatch_workgroups(workgroup_count(bullet_count), 1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(&self.buffers.bullets, 0, &self.buffers.bullets_read, 0, bullets_size);
+        encoder.copy_buffer_to_buffer(&self.buffers.group_time_to_live, 0, &self.buffers.group_time_to_live_read, 0, groups_size);
+        encoder.copy_buffer_to_buffer(&self.buffers.enemy_hits, 0, &self.buffers.enemy_hits_read, 0, hits_size);
+        encoder.copy_buffer_to_buffer(&self.buffers.group_hits, 0, &self.buffers.group_hits_read, 0, groups_size);
+
+        queue.submit(Some(encoder.finish()));
+
+        let gpu_bullets: Vec<GpuBullet> = read_buffer(device, &self.buffers.bullets_read, bullets_size)?;
+        let group_time_to_live: Vec<f32> = read_buffer(device, &self.buffers.group_time_to_live_read, groups_size)?;
+        let enemy_hits: Vec<u32> = read_buffer(device, &self.buffers.enemy_hits_read, hits_size)?;
+        let group_hits: Vec<u32> = read_buffer(device, &self.buffers.group_hits_read, groups_size)?;
+
+        for (slot, bullet) in self.bullet_slots.iter().zip(gpu_bullets.iter()) {
+            store.bullet_positions[*slot] = bullet.position;
+        }
+
+        for (group, time_to_live) in store.bullet_groups.iter_mut().zip(group_time_to_live) {
+            group.time_to_live = time_to_live;
+        }
+
+        for (enemy, hit) in enemies.iter_mut().zip(enemy_hits) {
+            if hit != 0 {
+                enemy.is_alive = false;
+            }
+        }
+
+        // the same rule as the CPU broadphases, every group with a bullet touching an enemy counts
+        for (group_index, hit) in group_hits.into_iter().enumerate() {
+            if hit != 0 {
+                store.mark_group_hit(group_index);
+            }
+        }
+
+        store.remove_expired_groups();
+
+        Ok(())
+    }
+}
+
+impl ComputeBuffers {
+    fn new(device: &Device, bullet_capacity: usize, group_capacity: usize, enemy_capacity: usize) -> Self {
+        // storage bindings can't be empty
+        let enemy_capacity = enemy_capacity.max(1);
+        let group_capacity = group_capacity.max(1);
+        let bullet_capacity = bullet_capacity.max(1);
+
+        let storage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let read_back = wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST;
+
+        let bullets_size = bullet_capacity * mem::size_of::<GpuBullet>();
+        let groups_size = group_capacity * mem::size_of::<f32>();
+        let hits_size = enemy_capacity * mem::size_of::<u32>();
+
+        Self {
+            bullet_capacity,
+            group_capacity,
+            enemy_capacity,
+            bullets: create_buffer(device, bullets_size, storage, "bullet compute bullets buffer"),
+            group_time_to_live: create_buffer(device, groups_size, storage, "bullet compute groups buffer"),
+            enemies: create_buffer(device, enemy_capacity * mem::size_of::<GpuEnemy>(), storage, "bullet compute enemies buffer"),
+            enemy_hits: create_buffer(device, hits_size, storage, "bullet compute hits buffer"),
+            group_hits: create_buffer(device, groups_size, storage, "bullet compute group hits buffer"),
+            bullets_read: create_buffer(device, bullets_size, read_back, "bullet compute bullets read buffer"),
+            group_time_to_live_read: create_buffer(device, groups_size, read_back, "bullet compute groups read buffer"),
+            enemy_hits_read: create_buffer(device, hits_size, read_back, "bullet compute hits read buffer"),
+            group_hits_read: create_buffer(device, groups_size, read_back, "bullet compute group hits read buffer"),
+        }
+    }
+
+    fn create_bind_group(&self, device: &Device, layout: &BindGroupLayout, params_buffer: &Buffer) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.bullets.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.group_time_to_live.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.enemies.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.enemy_hits.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.group_hits.as_entire_binding(),
+                },
+            ],
+            label: Some("bullet compute bind group"),
+        })
+    }
+}
+
+fn compute_layout_entry(binding: u32, ty: wgpu::BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn create_buffer(device: &Device, size: usize, usage: wgpu::BufferUsages, label: &str) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size as BufferAddress,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+fn workgroup_count(count: usize) -> u32 {
+    (count as u32).div_ceil(WORKGROUP_SIZE)
+}
+
+fn read_buffer<T: bytemuck::Pod>(device: &Device, buffer: &Buffer, size: BufferAddress) -> anyhow::Result<Vec<T>> {
+    let slice = buffer.slice(..size);
+
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    buffer.unmap();
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use crate::bullets::BulletStore;
+    use crate::enemy::Enemy;
+    use crate::render::bullet_compute::BulletCompute;
+    use crate::weapons::{Weapon, WeaponDefinition};
+    use crate::world::{BULLET_GROUPS_IN_FLIGHT, MONSTER_Y};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn software_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        }))?;
+
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("bullet compute test device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: adapter.limits(),
+        };
+
+        pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+    }
+
+    fn bullets_and_enemies() -> (BulletStore, Vec<Enemy>) {
+        let weapon = Weapon::new(WeaponDefinition::default());
+        let mut store = BulletStore::new();
+        for i in 0..BULLET_GROUPS_IN_FLIGHT {
+            let angle = (i as f32 * 36.0).to_radians();
+            store.create_bullets(angle.sin(), angle.cos(), vec3(0.0, MONSTER_Y, 0.0), 0, &weapon);
+        }
+
+        let mut enemies = vec![];
+        for ring in 1..6 {
+            for step in 0..24 {
+                let angle = (step as f32 * 15.0 + ring as f32 * 7.0).to_radians();
+                let position = vec3(angle.sin() * ring as f32, MONSTER_Y, angle.cos() * ring as f32);
+                enemies.push(Enemy {
+                    position,
+                    previous_position: position,
+                    direction: (-position).normalize_or_zero(),
+                    is_alive: true,
+                });
+            }
+        }
+
+        (store, enemies)
+    }
+
+    #[test]
+    fn test_gpu_bullets_match_cpu() {
+        // no display is needed, only an adapter, which not every machine has
+        let Some((device, queue)) = software_device() else {
+            eprintln!("Skipping test_gpu_bullets_match_cpu, no software adapter");
+            return;
+        };
+
+        let mut compute = BulletCompute::new(&device);
+
+        let (mut cpu_store, mut cpu_enemies) = bullets_and_enemies();
+        let (mut gpu_store, mut gpu_enemies) = bullets_and_enemies();
+
+        for _ in 0..70 {
+            cpu_store.update_bullets(DT, &mut cpu_enemies);
+            compute.update_bullets(&device, &queue, &mut gpu_store, DT, &mut gpu_enemies).unwrap();
+
+            assert_eq!(cpu_store.bullet_groups.len(), gpu_store.bullet_groups.len());
+            assert_eq!(cpu_store.bullet_positions.len(), gpu_store.bullet_positions.len());
+            for (cpu, gpu) in cpu_store.bullet_positions.iter().zip(gpu_store.bullet_positions.iter()) {
+                assert!(cpu.distance(*gpu) < 0.0001);
+            }
+        }
+
+        let cpu_alive: Vec<bool> = cpu_enemies.iter().map(|e| e.is_alive).collect();
+        let gpu_alive: Vec<bool> = gpu_enemies.iter().map(|e| e.is_alive).collect();
+
+        assert!(cpu_alive.iter().any(|alive| !alive));
+        assert_eq!(cpu_alive, gpu_alive);
+        assert_eq!(cpu_store.groups_hit, gpu_store.groups_hit);
+        assert!(gpu_store.is_empty());
+    }
+}
@@ -8,10 +8,11 @@ use wgpu::{IndexFormat, RenderPass, RenderPipeline};
 
 use crate::bullets::BulletSystem;
 use crate::load_shader;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
 use crate::small_mesh::SmallMesh;
 use crate::world::World;
 
-pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
+pub fn create_bullet_shader_pipeline(context: &GpuContext, sample_count: u32) -> RenderPipeline {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
 
@@ -26,9 +27,6 @@ pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
 
     let shader = context.device.create_shader_module(load_shader!("bullet_shader.wgsl").into());
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
     let instance_vec3_description = wgpu::VertexBufferLayout {
         array_stride: mem::size_of::<Vec3>() as wgpu::BufferAddress,
         step_mode: wgpu::VertexStepMode::Instance,
@@ -49,6 +47,19 @@ pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
         }],
     };
 
+    let blend = Some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    });
+
     let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Bullet Render Pipeline"),
         layout: Some(&pipeline_layout),
@@ -60,22 +71,14 @@ pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: swapchain_format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::SrcAlpha,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                    alpha: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::One,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: SCENE_FORMAT,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
                 }),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
+                emission_target(blend),
+            ],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleStrip,
@@ -93,7 +96,10 @@ pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -102,7 +108,6 @@ pub fn create_bullet_shader_pipeline(context: &GpuContext) -> RenderPipeline {
 
 pub fn render_bullets<'a>(world: &'a World, mut render_pass: RenderPass<'a>, bullet_system: &'a BulletSystem) -> RenderPass<'a> {
     render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
-    render_pass.set_bind_group(1, &bullet_system.bullet_material.bind_group, &[]);
 
     render_pass.set_vertex_buffer(0, bullet_system.vertex_buffer.slice(..));
     render_pass.set_vertex_buffer(1, bullet_system.bullet_positions_buffer.slice(..));
@@ -110,7 +115,18 @@ pub fn render_bullets<'a>(world: &'a World, mut render_pass: RenderPass<'a>, bul
 
     render_pass.set_index_buffer(bullet_system.index_buffer.slice(..), IndexFormat::Uint32);
 
-    render_pass.draw_indexed(0..12, 0, 0..bullet_system.bullet_positions.len() as u32);
+    // instances are the slots of each live group in the bullet ring, which can wrap around,
+    // drawn with the texture of the weapon that fired the group
+    let bullets = &world.state.bullets;
+    for group in bullets.bullet_groups.iter().filter(|group| group.is_live()) {
+        render_pass.set_bind_group(1, &bullet_system.bullet_materials[group.weapon].bind_group, &[]);
+
+        for range in bullets.group_ranges(group) {
+            if !range.is_empty() {
+                render_pass.draw_indexed(0..12, 0, range.start as u32..range.end as u32);
+            }
+        }
+    }
 
     render_pass
 }
@@ -0,0 +1,120 @@
+use std::mem;
+
+use glam::Vec4;
+use spark_gap::camera::camera_handler::CAMERA_BIND_GROUP_LAYOUT;
+use spark_gap::gpu_context::GpuContext;
+use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
+use wgpu::{RenderPass, RenderPipeline};
+
+use crate::burn_marks::BurnMarks;
+use crate::load_shader;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
+use crate::small_mesh::SmallMesh;
+use crate::world::World;
+
+pub fn create_burn_mark_shader_pipeline(context: &GpuContext, sample_count: u32) -> RenderPipeline {
+    let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
+    let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
+
+    let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("burn mark pipeline layout"),
+        bind_group_layouts: &[
+            camera_bind_group_layout,
+            material_bind_group_layout, // mark texture
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let shader = context.device.create_shader_module(load_shader!("burn_mark_shader.wgsl").into());
+
+    // x, z, scale, alpha
+    let instance_description = wgpu::VertexBufferLayout {
+        array_stride: mem::size_of::<Vec4>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x4,
+        }],
+    };
+
+    let blend = Some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    });
+
+    let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("burn mark render pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[SmallMesh::vertex_description(), instance_description],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: SCENE_FORMAT,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                emission_target(blend),
+            ],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        // decals test against the floor but don't write depth, so overlapping marks blend
+        // and the bias pulls them in front of the floor instead of z-fighting it
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: -2,
+                slope_scale: -2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    });
+
+    render_pipeline
+}
+
+pub fn render_burn_marks<'a>(world: &'a World, mut render_pass: RenderPass<'a>, burn_marks: &'a BurnMarks) -> RenderPass<'a> {
+    if burn_marks.num_marks == 0 {
+        return render_pass;
+    }
+
+    render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
+    render_pass.set_bind_group(1, &burn_marks.mark_material.bind_group, &[]);
+
+    render_pass.set_vertex_buffer(0, burn_marks.unit_square.vertex_buffer.slice(..));
+    render_pass.set_vertex_buffer(1, burn_marks.instance_buffer.slice(..));
+
+    render_pass.draw(0..burn_marks.unit_square.num_elements, 0..burn_marks.num_marks);
+
+    render_pass
+}
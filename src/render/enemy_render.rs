@@ -1,24 +1,26 @@
+use std::mem;
+
 use spark_gap::camera::camera_handler::CAMERA_BIND_GROUP_LAYOUT;
 use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
 use spark_gap::model_builder::MODEL_BIND_GROUP_LAYOUT;
 use spark_gap::model_mesh::ModelVertex;
 use spark_gap::texture_config::TextureType;
-use wgpu::{IndexFormat, RenderPass};
+use wgpu::{BindGroup, IndexFormat, RenderPass};
 
-use crate::enemy::{ENEMY_UNIFORMS_BIND_GROUP_LAYOUT, EnemySystem};
+use crate::enemy::{EnemySystem, EnemyUniform};
 use crate::load_shader;
 use crate::params::shader_params::SHADER_PARAMETERS_BIND_GROUP_LAYOUT;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
 use crate::render::main_render::Pipelines;
 use crate::render::shadow_material::{SHADOW_USE_BIND_GROUP_LAYOUT, ShadowMaterial};
 use crate::world::World;
 
-pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
+pub fn create_enemy_shader_pipeline(context: &GpuContext, sample_count: u32) -> Pipelines {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let model_bind_group_layout = context.bind_layout_cache.get(MODEL_BIND_GROUP_LAYOUT).unwrap();
     let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
     let params_bind_group_layout = context.bind_layout_cache.get(SHADER_PARAMETERS_BIND_GROUP_LAYOUT).unwrap();
-    let instances_bind_group_layout = context.bind_layout_cache.get(ENEMY_UNIFORMS_BIND_GROUP_LAYOUT).unwrap();
     let shadow_bind_group_layout = context.bind_layout_cache.get(SHADOW_USE_BIND_GROUP_LAYOUT).unwrap();
 
     let shadow_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -27,7 +29,6 @@ pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
             camera_bind_group_layout,
             model_bind_group_layout,
             params_bind_group_layout,
-            instances_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
@@ -40,7 +41,7 @@ pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
         vertex: wgpu::VertexState {
             module: &shader,
             entry_point: "vs_shadow",
-            buffers: &[ModelVertex::vertex_description()],
+            buffers: &[ModelVertex::vertex_description(), enemy_instance_buffer_layout()],
         },
         fragment: None,
         primitive: wgpu::PrimitiveState {
@@ -71,28 +72,24 @@ pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
             camera_bind_group_layout,
             model_bind_group_layout,
             params_bind_group_layout,
-            instances_bind_group_layout,
             material_bind_group_layout, // diffuse
             shadow_bind_group_layout,   // shadow
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
     let forward_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
         layout: Some(&forward_layout),
         vertex: wgpu::VertexState {
             module: &shader,
             entry_point: "vs_main",
-            buffers: &[ModelVertex::vertex_description()],
+            buffers: &[ModelVertex::vertex_description(), enemy_instance_buffer_layout()],
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(swapchain_format.into())],
+            targets: &[Some(SCENE_FORMAT.into()), emission_target(None)],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -110,7 +107,10 @@ pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -120,13 +120,40 @@ pub fn create_enemy_shader_pipeline(context: &GpuContext) -> Pipelines {
     }
 }
 
-pub fn shadow_render_enemies<'a>(context: &'a GpuContext, world: &'a World, mut render_pass: RenderPass<'a>, enemy_system: &'a EnemySystem) -> RenderPass<'a> {
+// The two matrices of each EnemyUniform as columns after the model's vertex attributes. An instance
+// vertex buffer instead of a storage buffer, which WebGL2 can't read in the vertex stage.
+const ENEMY_INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+    7 => Float32x4,
+    8 => Float32x4,
+    9 => Float32x4,
+    10 => Float32x4,
+    11 => Float32x4,
+    12 => Float32x4,
+    13 => Float32x4,
+    14 => Float32x4,
+];
+
+fn enemy_instance_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: mem::size_of::<EnemyUniform>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &ENEMY_INSTANCE_ATTRIBUTES,
+    }
+}
+
+pub fn shadow_render_enemies<'a>(
+    context: &'a GpuContext,
+    world: &'a World,
+    mut render_pass: RenderPass<'a>,
+    enemy_system: &'a EnemySystem,
+    light_camera: &'a BindGroup,
+) -> RenderPass<'a> {
     let model = &enemy_system.enemy_model;
 
-    render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
+    render_pass.set_bind_group(0, light_camera, &[]);
     render_pass.set_bind_group(1, &model.bind_group, &[]);
     render_pass.set_bind_group(2, &world.shader_params.bind_group, &[]);
-    render_pass.set_bind_group(3, &enemy_system.instances_bind_group, &[]);
+    render_pass.set_vertex_buffer(1, enemy_system.instances_buffer.slice(..));
 
     for mesh in model.meshes.iter() {
         model.update_mesh_buffers(context, &mesh);
@@ -152,14 +179,14 @@ pub fn forward_render_enemies<'a>(
     render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
     render_pass.set_bind_group(1, &model.bind_group, &[]);
     render_pass.set_bind_group(2, &world.shader_params.bind_group, &[]);
-    render_pass.set_bind_group(3, &enemy_system.instances_bind_group, &[]);
-    render_pass.set_bind_group(5, &shadow_map.shadow_use_bind_group, &[]);
+    render_pass.set_bind_group(4, &shadow_map.shadow_use_bind_group, &[]);
+    render_pass.set_vertex_buffer(1, enemy_system.instances_buffer.slice(..));
 
     for mesh in model.meshes.iter() {
         model.update_mesh_buffers(context, &mesh);
 
         let diffuse_bind_group = model.get_material_bind_group(&mesh, TextureType::Diffuse);
-        render_pass.set_bind_group(4, diffuse_bind_group, &[]);
+        render_pass.set_bind_group(3, diffuse_bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
 
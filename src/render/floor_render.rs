@@ -1,18 +1,19 @@
 use spark_gap::camera::camera_handler::CAMERA_BIND_GROUP_LAYOUT;
 use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
-use wgpu::RenderPass;
+use wgpu::{BindGroup, RenderPass};
 
 use crate::floor::Floor;
 use crate::load_shader;
 use crate::params::shader_params::SHADER_PARAMETERS_BIND_GROUP_LAYOUT;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
 use crate::render::buffers::TRANSFORM_BIND_GROUP_LAYOUT;
 use crate::render::main_render::Pipelines;
 use crate::render::shadow_material::{SHADOW_USE_BIND_GROUP_LAYOUT, ShadowMaterial};
 use crate::small_mesh::SmallMesh;
 use crate::world::World;
 
-pub fn create_floor_shader_pipeline(context: &GpuContext) -> Pipelines {
+pub fn create_floor_shader_pipeline(context: &GpuContext, sample_count: u32) -> Pipelines {
     let shader = context.device.create_shader_module(load_shader!("floor_shader.wgsl").into());
     
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
@@ -76,9 +77,6 @@ pub fn create_floor_shader_pipeline(context: &GpuContext) -> Pipelines {
         push_constant_ranges: &[],
     });
     
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
     let forward_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("floor forward render pipeline"),
         layout: Some(&forward_layout),
@@ -90,7 +88,7 @@ pub fn create_floor_shader_pipeline(context: &GpuContext) -> Pipelines {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(swapchain_format.into())],
+            targets: &[Some(SCENE_FORMAT.into()), emission_target(None)],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -108,7 +106,10 @@ pub fn create_floor_shader_pipeline(context: &GpuContext) -> Pipelines {
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -122,8 +123,9 @@ pub fn shadow_render_floor<'a>(
     world: &'a World,
     mut render_pass: RenderPass<'a>,
     floor: &'a Floor,
+    light_camera: &'a BindGroup,
 ) -> RenderPass<'a> {
-    render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
+    render_pass.set_bind_group(0, light_camera, &[]);
     render_pass.set_bind_group(1, &floor.transform_bind_group, &[]);
     render_pass.set_bind_group(2, &world.shader_params.bind_group, &[]);
 
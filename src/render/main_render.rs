@@ -1,17 +1,26 @@
 use glam::{Mat4, vec3};
+use image::RgbaImage;
+use std::path::PathBuf;
 use spark_gap::buffers::{update_mat4_buffer, update_u32_buffer};
 use spark_gap::gpu_context::GpuContext;
-use wgpu::{CommandEncoder, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, TextureView};
+use wgpu::{CommandEncoder, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, TextureFormat, TextureView};
 
+use crate::render::bloom::{create_bloom_material, create_bloom_pipelines, BloomMaterial, BloomTargets, EMISSION_FORMAT, SCENE_FORMAT};
 use crate::render::bullet_render::{create_bullet_shader_pipeline, render_bullets};
+use crate::render::burn_mark_render::{create_burn_mark_shader_pipeline, render_burn_marks};
 // use crate::render::debug_render::{create_debug_depth_render_pipeline, create_debug_test_render_pipeline, shadow_render_debug};
 use crate::render::enemy_render::{create_enemy_shader_pipeline, forward_render_enemies, shadow_render_enemies};
 use crate::render::floor_render::{create_floor_shader_pipeline, forward_render_floor, shadow_render_floor};
+use crate::render::msaa::supported_sample_count;
+use crate::render::offscreen::OffscreenTarget;
 use crate::render::player_render::{create_player_shader_pipeline, forward_render_player, shadow_render_player};
+use crate::render::render_graph::{RenderGraph, ResourceName, TargetDesc};
+use crate::render::shader_loader::{compose_shader, shader_dependencies, SHADER_DIR};
 use crate::render::shadow_material::{create_debug_depth_render_pipeline, create_shadow_map_material, shadow_render_debug, ShadowMaterial};
-use crate::render::sprite_render::{create_sprite_shader_pipeline, render_muzzle_flashes};
-use crate::render::textures::create_depth_texture_view;
-use crate::world::World;
+use crate::render::sprite_render::{create_impact_sprite_shader_pipeline, create_sprite_shader_pipeline, render_bullet_impacts, render_muzzle_flashes};
+use crate::render::textures::DEPTH_FORMAT;
+use crate::shadow_cascades::{ShadowCascade, SHADOW_CASCADES};
+use crate::world::{World, BLUR_SCALE, MSAA_SAMPLE_COUNT};
 
 pub const BACKGROUND_COLOR: wgpu::Color = wgpu::Color {
     r: 0.1,
@@ -25,27 +34,98 @@ pub struct Pipelines {
     pub(crate) forward_pipeline: RenderPipeline,
 }
 
+// Render graph resources
+const SHADOW_MAP: ResourceName = "shadow map";
+const DEPTH: ResourceName = "depth";
+const MSAA_SCENE: ResourceName = "msaa scene";
+const MSAA_EMISSION: ResourceName = "msaa emission";
+const SCENE: ResourceName = "scene";
+const EMISSION: ResourceName = "emission";
+const HORIZONTAL_BLUR: ResourceName = "horizontal blur";
+const VERTICAL_BLUR: ResourceName = "vertical blur";
+const OUTPUT: ResourceName = "output";
+
+// Pipelines rebuilt by shader hot reloading, with the shader each is made from
+#[derive(Debug, Clone, Copy)]
+enum ScenePipeline {
+    Player,
+    Floor,
+    Enemy,
+    Sprite,
+    Bullet,
+    BurnMark,
+    ImpactSprite,
+    Bloom,
+}
+
+impl ScenePipeline {
+    const ALL: [ScenePipeline; 8] = [
+        ScenePipeline::Player,
+        ScenePipeline::Floor,
+        ScenePipeline::Enemy,
+        ScenePipeline::Sprite,
+        ScenePipeline::Bullet,
+        ScenePipeline::BurnMark,
+        ScenePipeline::ImpactSprite,
+        ScenePipeline::Bloom,
+    ];
+
+    fn shader_file(self) -> &'static str {
+        match self {
+            ScenePipeline::Player => "shaders/player_shader.wgsl",
+            ScenePipeline::Floor => "shaders/floor_shader.wgsl",
+            ScenePipeline::Enemy => "shaders/wiggle_shader.wgsl",
+            ScenePipeline::Sprite => "shaders/sprite_shader.wgsl",
+            ScenePipeline::Bullet => "shaders/bullet_shader.wgsl",
+            ScenePipeline::BurnMark => "shaders/burn_mark_shader.wgsl",
+            ScenePipeline::ImpactSprite => "shaders/impact_sprite_shader.wgsl",
+            ScenePipeline::Bloom => "shaders/bloom_shader.wgsl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScenePass {
+    Shadow(usize),
+    Forward,
+    Bloom,
+}
+
 pub struct WorldRender {
     player_shader_pipelines: Pipelines,
     floor_shader_pipelines: Pipelines,
     enemy_shader_pipelines: Pipelines,
     sprite_shader_pipeline: RenderPipeline,
     bullet_shader_pipeline: RenderPipeline,
-    pub depth_texture_view: TextureView,
+    burn_mark_shader_pipeline: RenderPipeline,
+    impact_sprite_shader_pipeline: RenderPipeline,
+    sample_count: u32,
+    // size and format of the view the composite draws into
+    width: u32,
+    height: u32,
+    output_format: TextureFormat,
+    graph: RenderGraph<ScenePass>,
     shadow_map_material: ShadowMaterial,
+    bloom_material: BloomMaterial,
 }
 
 impl WorldRender {
-    pub fn new(context: &mut GpuContext) -> Self {
-        let depth_texture_view = create_depth_texture_view(&context);
+    /// Renders at `width` x `height` into views of `output_format`, the swapchain's or an offscreen texture's.
+    pub fn new(context: &mut GpuContext, width: u32, height: u32, output_format: TextureFormat) -> Self {
+        let sample_count = supported_sample_count(context, MSAA_SAMPLE_COUNT);
+
+        let graph = create_render_graph(context, sample_count, width, height);
 
         let shadow_map_material = create_shadow_map_material(context);
+        let bloom_material = create_bloom_material(context, &bloom_targets(&graph), output_format);
 
-        let player_shader_pipelines = create_player_shader_pipeline(context);
-        let floor_shader_pipelines = create_floor_shader_pipeline(context);
-        let enemy_shader_pipelines = create_enemy_shader_pipeline(context);
-        let sprite_shader_pipeline = create_sprite_shader_pipeline(context);
-        let bullet_shader_pipeline = create_bullet_shader_pipeline(context);
+        let player_shader_pipelines = create_player_shader_pipeline(context, sample_count);
+        let floor_shader_pipelines = create_floor_shader_pipeline(context, sample_count);
+        let enemy_shader_pipelines = create_enemy_shader_pipeline(context, sample_count);
+        let sprite_shader_pipeline = create_sprite_shader_pipeline(context, sample_count);
+        let bullet_shader_pipeline = create_bullet_shader_pipeline(context, sample_count);
+        let burn_mark_shader_pipeline = create_burn_mark_shader_pipeline(context, sample_count);
+        let impact_sprite_shader_pipeline = create_impact_sprite_shader_pipeline(context, sample_count);
 
         Self {
             player_shader_pipelines,
@@ -53,85 +133,141 @@ impl WorldRender {
             enemy_shader_pipelines,
             sprite_shader_pipeline,
             bullet_shader_pipeline,
-            depth_texture_view,
+            burn_mark_shader_pipeline,
+            impact_sprite_shader_pipeline,
+            sample_count,
+            width,
+            height,
+            output_format,
+            graph,
             shadow_map_material,
+            bloom_material,
         }
     }
 
-    pub fn resize(&mut self, context: &GpuContext) {
-        self.depth_texture_view = create_depth_texture_view(context);
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.graph.resize(context, width, height);
+        self.bloom_material.bind_targets(context, &bloom_targets(&self.graph));
     }
 
-    pub fn render(&mut self, context: &GpuContext, world: &mut World) {
-        world.shader_params.update_buffer(context);
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
 
-        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    /// Rebuilds the forward pipelines and targets for a new MSAA sample count,
+    /// lowered to what the adapter supports.
+    pub fn set_sample_count(&mut self, context: &GpuContext, requested: u32) {
+        let sample_count = supported_sample_count(context, requested);
+        if sample_count == self.sample_count {
+            return;
+        }
 
-        let frame = context.surface.get_current_texture().expect("Failed to acquire next swap chain texture");
-        let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.player_shader_pipelines = create_player_shader_pipeline(context, sample_count);
+        self.floor_shader_pipelines = create_floor_shader_pipeline(context, sample_count);
+        self.enemy_shader_pipelines = create_enemy_shader_pipeline(context, sample_count);
+        self.sprite_shader_pipeline = create_sprite_shader_pipeline(context, sample_count);
+        self.bullet_shader_pipeline = create_bullet_shader_pipeline(context, sample_count);
+        self.burn_mark_shader_pipeline = create_burn_mark_shader_pipeline(context, sample_count);
+        self.impact_sprite_shader_pipeline = create_impact_sprite_shader_pipeline(context, sample_count);
+
+        self.sample_count = sample_count;
+        self.graph = create_render_graph(context, sample_count, self.width, self.height);
+        self.bloom_material.bind_targets(context, &bloom_targets(&self.graph));
+    }
 
-        // shadow pass
-        {
-            let depth_stencil_attachment = RenderPassDepthStencilAttachment {
-                view: &self.shadow_map_material.stencil_views[0],
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
+    /// Rebuilds the pipelines whose shaders, or the modules they import, are among `changed_files`.
+    /// A shader that fails to compose or validate keeps its previous pipeline.
+    pub fn reload_shaders(&mut self, context: &GpuContext, changed_files: &[PathBuf]) {
+        for pipeline in ScenePipeline::ALL {
+            let shader_file = pipeline.shader_file();
+
+            let dependencies = match shader_dependencies(shader_file, SHADER_DIR) {
+                Ok(dependencies) => dependencies,
+                Err(e) => {
+                    error!("Could not read the imports of {}: {:#}", shader_file, e);
+                    continue;
+                }
             };
 
-            let shadow_pass_descriptor = RenderPassDescriptor {
-                label: Some("shadow render pass descriptor"),
-                color_attachments: &[],
-                depth_stencil_attachment: Some(depth_stencil_attachment),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            };
+            if !changed_files.iter().any(|file| dependencies.contains(file)) {
+                continue;
+            }
 
-            self.shadow_render_pass(context, world, &mut encoder, &shadow_pass_descriptor);
+            match self.rebuild_pipeline(context, pipeline) {
+                Ok(_) => info!("Reloaded {}", shader_file),
+                Err(e) => error!("Keeping the previous {:?} pipeline: {:#}", pipeline, e),
+            }
         }
+    }
 
-        // forward pass
-        {
-            let color_attachment = RenderPassColorAttachment {
-                view: &frame_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(BACKGROUND_COLOR),
-                    store: wgpu::StoreOp::Store,
-                },
-            };
-
-            let depth_attachment = RenderPassDepthStencilAttachment {
-                view: &self.depth_texture_view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            };
+    fn rebuild_pipeline(&mut self, context: &GpuContext, pipeline: ScenePipeline) -> anyhow::Result<()> {
+        // composer errors would panic inside the pipeline functions, so check first
+        compose_shader(pipeline.shader_file(), SHADER_DIR)?;
+
+        let sample_count = self.sample_count;
+        let output_format = self.output_format;
+        match pipeline {
+            ScenePipeline::Player => self.player_shader_pipelines = validated(context, || create_player_shader_pipeline(context, sample_count))?,
+            ScenePipeline::Floor => self.floor_shader_pipelines = validated(context, || create_floor_shader_pipeline(context, sample_count))?,
+            ScenePipeline::Enemy => self.enemy_shader_pipelines = validated(context, || create_enemy_shader_pipeline(context, sample_count))?,
+            ScenePipeline::Sprite => self.sprite_shader_pipeline = validated(context, || create_sprite_shader_pipeline(context, sample_count))?,
+            ScenePipeline::Bullet => self.bullet_shader_pipeline = validated(context, || create_bullet_shader_pipeline(context, sample_count))?,
+            ScenePipeline::BurnMark => self.burn_mark_shader_pipeline = validated(context, || create_burn_mark_shader_pipeline(context, sample_count))?,
+            ScenePipeline::ImpactSprite => {
+                self.impact_sprite_shader_pipeline = validated(context, || create_impact_sprite_shader_pipeline(context, sample_count))?
+            }
+            ScenePipeline::Bloom => self.bloom_material.pipelines = validated(context, || create_bloom_pipelines(context, output_format))?,
+        }
+        Ok(())
+    }
 
-            let forward_pass_description = RenderPassDescriptor {
-                label: Some("render pass"),
-                color_attachments: &[Some(color_attachment)],
-                depth_stencil_attachment: Some(depth_attachment),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            };
+    pub fn update_shadow_cascades(&self, context: &GpuContext, cascades: &[ShadowCascade; SHADOW_CASCADES]) {
+        self.shadow_map_material.update_cascades(context, cascades);
+    }
 
-            // self.shadow_debug_render(context, &mut encoder, &forward_pass_description);
+    pub fn render(&mut self, context: &GpuContext, world: &mut World) {
+        let frame = context.surface.get_current_texture().expect("Failed to acquire next swap chain texture");
+        let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-            self.forward_render_pass(context, world, &mut encoder, &forward_pass_description);
-        }
+        let encoder = self.encode_passes(context, world, &frame_view);
 
         context.queue.submit(Some(encoder.finish()));
         frame.present();
     }
 
+    /// Draws the same passes as `render` into an offscreen texture and reads back the pixels.
+    pub fn render_to_image(&mut self, context: &GpuContext, world: &mut World) -> anyhow::Result<RgbaImage> {
+        let target = OffscreenTarget::new(context, self.width, self.height, self.output_format)?;
+
+        let mut encoder = self.encode_passes(context, world, &target.view);
+        target.copy_to_readback(&mut encoder);
+
+        context.queue.submit(Some(encoder.finish()));
+        target.read_image(context)
+    }
+
+    fn encode_passes(&mut self, context: &GpuContext, world: &mut World, output_view: &TextureView) -> CommandEncoder {
+        world.shader_params.update_buffer(context);
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        for pass in self.graph.ordered_passes() {
+            match pass {
+                ScenePass::Shadow(cascade) => self.shadow_render_pass(context, world, &mut encoder, cascade),
+                ScenePass::Forward => self.forward_render_pass(context, world, &mut encoder),
+                // blur the emission and composite the glow over the scene
+                ScenePass::Bloom => self.bloom_material.render(&mut encoder, &bloom_targets(&self.graph), output_view),
+            }
+        }
+
+        encoder
+    }
+
     fn shadow_debug_render(&self, context: &GpuContext, encoder: &mut CommandEncoder, pass_description: &RenderPassDescriptor) {
-        let width = (context.config.width as f32 / 2.0) + 5.0;
-        let height = (context.config.height as f32 / 2.0) + 5.0;
+        let width = (self.width as f32 / 2.0) + 5.0;
+        let height = (self.height as f32 / 2.0) + 5.0;
 
         let orthographic_projection = Mat4::orthographic_rh(-width, width, -height, height, 0.1, 1000.0);
         let view = Mat4::look_at_rh(vec3(0.0, 0.0001, 200.0), vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0));
@@ -146,45 +282,115 @@ impl WorldRender {
         shadow_render_debug(render_pass, &self.shadow_map_material);
     }
     
-    fn shadow_render_pass(&self, context: &GpuContext, world: &mut World, encoder: &mut CommandEncoder, pass_description: &RenderPassDescriptor) {
-        
+    // one pass per cascade, into its layer of the shadow map
+    fn shadow_render_pass(&self, context: &GpuContext, world: &mut World, encoder: &mut CommandEncoder, cascade: usize) {
         world.shader_params.set_use_light(false);
-        
+
         let floor = &world.floor.borrow();
         let player = &world.player.borrow();
         let enemy_system = &world.enemy_system.borrow();
 
-        let mut render_pass = encoder.begin_render_pass(pass_description);
+        let depth_stencil_attachment = RenderPassDepthStencilAttachment {
+            view: &self.shadow_map_material.stencil_views[cascade],
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        };
+
+        let shadow_pass_descriptor = RenderPassDescriptor {
+            label: Some("shadow render pass descriptor"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(depth_stencil_attachment),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let light_camera = &self.shadow_map_material.cascade_camera_bind_groups[cascade];
+
+        let mut render_pass = encoder.begin_render_pass(&shadow_pass_descriptor);
 
         // floor
         render_pass.set_pipeline(&self.floor_shader_pipelines.shadow_pipeline);
-        render_pass = shadow_render_floor(world, render_pass, floor);
+        render_pass = shadow_render_floor(world, render_pass, floor, light_camera);
 
         // player
         render_pass.set_pipeline(&self.player_shader_pipelines.shadow_pipeline);
-        render_pass = shadow_render_player(context, world, render_pass, player);
+        render_pass = shadow_render_player(context, world, render_pass, player, light_camera);
 
         // enemies
         render_pass.set_pipeline(&self.enemy_shader_pipelines.shadow_pipeline);
-        render_pass = shadow_render_enemies(context, world, render_pass, enemy_system);
+        render_pass = shadow_render_enemies(context, world, render_pass, enemy_system, light_camera);
     }
 
-    fn forward_render_pass(&self, context: &GpuContext, world: &mut World, encoder: &mut CommandEncoder, pass_description: &RenderPassDescriptor) {
-        
+    // into the scene and emission targets, with MSAA it draws into the multisampled targets and resolves into them
+    fn forward_render_pass(&self, context: &GpuContext, world: &mut World, encoder: &mut CommandEncoder) {
         world.shader_params.set_use_light(true);
-        
+
         let floor = &world.floor.borrow();
         let player = &world.player.borrow();
         let flashes = &world.muzzle_flash.borrow();
         let enemy_system = &world.enemy_system.borrow();
         let bullet_system = &world.bullet_system.borrow();
 
-        let mut render_pass = encoder.begin_render_pass(pass_description);
+        let targets = bloom_targets(&self.graph);
+        let (scene_view, scene_resolve_target) = match self.graph.view(MSAA_SCENE) {
+            Some(msaa_view) => (msaa_view, Some(targets.scene)),
+            None => (targets.scene, None),
+        };
+        let (emission_view, emission_resolve_target) = match self.graph.view(MSAA_EMISSION) {
+            Some(msaa_view) => (msaa_view, Some(targets.emission)),
+            None => (targets.emission, None),
+        };
+
+        let color_attachment = RenderPassColorAttachment {
+            view: scene_view,
+            resolve_target: scene_resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(BACKGROUND_COLOR),
+                store: wgpu::StoreOp::Store,
+            },
+        };
+
+        let emission_attachment = RenderPassColorAttachment {
+            view: emission_view,
+            resolve_target: emission_resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        };
+
+        let depth_attachment = RenderPassDepthStencilAttachment {
+            view: self.graph.view(DEPTH).expect("depth target is allocated by the graph"),
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        };
+
+        let forward_pass_description = RenderPassDescriptor {
+            label: Some("render pass"),
+            color_attachments: &[Some(color_attachment), Some(emission_attachment)],
+            depth_stencil_attachment: Some(depth_attachment),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        // self.shadow_debug_render(context, &mut encoder, &forward_pass_description);
+
+        let mut render_pass = encoder.begin_render_pass(&forward_pass_description);
 
         // floor
         render_pass.set_pipeline(&self.floor_shader_pipelines.forward_pipeline);
         render_pass = forward_render_floor(world, render_pass, floor, &self.shadow_map_material);
 
+        // burn marks, on the floor and under everything else
+        render_pass.set_pipeline(&self.burn_mark_shader_pipeline);
+        render_pass = render_burn_marks(world, render_pass, &world.burn_marks);
+
         // player
         render_pass.set_pipeline(&self.player_shader_pipelines.forward_pipeline);
         render_pass = forward_render_player(context, world, render_pass, player, &self.shadow_map_material);
@@ -200,5 +406,65 @@ impl WorldRender {
         // enemies
         render_pass.set_pipeline(&self.enemy_shader_pipelines.forward_pipeline);
         render_pass = forward_render_enemies(context, world, render_pass, enemy_system, &self.shadow_map_material);
+
+        // bullet impacts, last as they don't write depth
+        render_pass.set_pipeline(&self.impact_sprite_shader_pipeline);
+        render_pass = render_bullet_impacts(world, render_pass, bullet_system);
+    }
+}
+
+// Runs `create` in a validation error scope, so a bad shader is an error instead of a panic
+fn validated<T>(context: &GpuContext, create: impl FnOnce() -> T) -> anyhow::Result<T> {
+    context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match pollster::block_on(context.device.pop_error_scope()) {
+        Some(e) => Err(anyhow::anyhow!("{}", e)),
+        None => Ok(value),
+    }
+}
+
+// The shadow cascades, forward and bloom passes. With MSAA the forward pass draws into
+// multisampled transients and resolves into the bloom scene and emission targets.
+fn create_render_graph(context: &GpuContext, sample_count: u32, width: u32, height: u32) -> RenderGraph<ScenePass> {
+    let mut graph = RenderGraph::new();
+
+    graph.add_import(SHADOW_MAP);
+    graph.add_import(OUTPUT);
+
+    graph.add_transient(DEPTH, TargetDesc::attachment(DEPTH_FORMAT, sample_count));
+    graph.add_transient(SCENE, TargetDesc::sampled(SCENE_FORMAT));
+    graph.add_transient(EMISSION, TargetDesc::sampled(EMISSION_FORMAT));
+
+    let blur_target = TargetDesc {
+        size_divisor: BLUR_SCALE,
+        ..TargetDesc::sampled(EMISSION_FORMAT)
+    };
+    graph.add_transient(HORIZONTAL_BLUR, blur_target);
+    graph.add_transient(VERTICAL_BLUR, blur_target);
+
+    let mut forward_writes = vec![DEPTH, SCENE, EMISSION];
+    if sample_count > 1 {
+        graph.add_transient(MSAA_SCENE, TargetDesc::attachment(SCENE_FORMAT, sample_count));
+        graph.add_transient(MSAA_EMISSION, TargetDesc::attachment(EMISSION_FORMAT, sample_count));
+        forward_writes.extend([MSAA_SCENE, MSAA_EMISSION]);
+    }
+
+    for cascade in 0..SHADOW_CASCADES {
+        graph.add_pass("shadow", ScenePass::Shadow(cascade), &[], &[SHADOW_MAP]);
+    }
+    graph.add_pass("forward", ScenePass::Forward, &[SHADOW_MAP], &forward_writes);
+    graph.add_pass("bloom", ScenePass::Bloom, &[SCENE, EMISSION], &[HORIZONTAL_BLUR, VERTICAL_BLUR, OUTPUT]);
+
+    graph.compile(context, width, height).expect("Invalid render graph");
+    graph
+}
+
+fn bloom_targets(graph: &RenderGraph<ScenePass>) -> BloomTargets<'_> {
+    let allocated = "bloom targets are allocated by the graph";
+    BloomTargets {
+        scene: graph.view(SCENE).expect(allocated),
+        emission: graph.view(EMISSION).expect(allocated),
+        horizontal_blur: graph.view(HORIZONTAL_BLUR).expect(allocated),
+        vertical_blur: graph.view(VERTICAL_BLUR).expect(allocated),
     }
 }
@@ -5,13 +5,20 @@ use std::io::Read;
 use wgpu::util::DeviceExt;
 use wgpu::Buffer;
 
+mod bloom;
 pub mod buffers;
+pub mod bullet_compute;
 mod bullet_render;
+mod burn_mark_render;
 pub mod enemy_render;
 pub mod floor_render;
 pub mod main_render;
+pub mod msaa;
+pub mod offscreen;
 pub mod player_render;
-mod shader_loader;
+pub mod shader_loader;
+pub mod shader_watcher;
 mod sprite_render;
 mod textures;
+mod render_graph;
 mod shadow_material;
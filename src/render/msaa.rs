@@ -0,0 +1,61 @@
+use spark_gap::gpu_context::GpuContext;
+
+use crate::render::bloom::{EMISSION_FORMAT, SCENE_FORMAT};
+use crate::render::textures::DEPTH_FORMAT;
+
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// Largest sample count up to `requested` that the scene, emission and depth formats all support.
+pub fn supported_sample_count(context: &GpuContext, requested: u32) -> u32 {
+    pick_sample_count(requested, |count| is_sample_count_supported(context, count))
+}
+
+/// The next supported sample count after `current`, wrapping back to one sample.
+pub fn next_sample_count(context: &GpuContext, current: u32) -> u32 {
+    SAMPLE_COUNTS
+        .iter()
+        .copied()
+        .filter(|count| *count > current)
+        .find(|count| is_sample_count_supported(context, *count))
+        .unwrap_or(1)
+}
+
+fn pick_sample_count(requested: u32, is_supported: impl Fn(u32) -> bool) -> u32 {
+    SAMPLE_COUNTS
+        .iter()
+        .rev()
+        .copied()
+        .filter(|count| *count <= requested)
+        .find(|count| is_supported(*count))
+        .unwrap_or(1)
+}
+
+fn is_sample_count_supported(context: &GpuContext, count: u32) -> bool {
+    // without adapter specific features only the WebGPU guaranteed counts are allowed
+    let adapter_specific = context.device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    [SCENE_FORMAT, EMISSION_FORMAT, DEPTH_FORMAT].iter().all(|format| {
+        let features = if adapter_specific {
+            context.adapter.get_texture_format_features(*format)
+        } else {
+            format.guaranteed_format_features(context.device.features())
+        };
+        features.flags.sample_count_supported(count)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::msaa::pick_sample_count;
+
+    #[test]
+    fn test_pick_sample_count_falls_back_to_supported() {
+        let webgpu_guaranteed = |count: u32| count == 1 || count == 4;
+
+        assert_eq!(pick_sample_count(8, webgpu_guaranteed), 4);
+        assert_eq!(pick_sample_count(4, webgpu_guaranteed), 4);
+        assert_eq!(pick_sample_count(2, webgpu_guaranteed), 1);
+        assert_eq!(pick_sample_count(8, |_| true), 8);
+        assert_eq!(pick_sample_count(0, |_| true), 1);
+    }
+}
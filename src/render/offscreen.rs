@@ -0,0 +1,176 @@
+use std::sync::mpsc;
+
+use anyhow::{anyhow, bail, Context};
+use image::RgbaImage;
+use spark_gap::gpu_context::GpuContext;
+use wgpu::{Buffer, CommandEncoder, Texture, TextureFormat, TextureView};
+
+//
+// A color texture that the final composite can draw into instead of the swap chain,
+// with a buffer to read the pixels back on the CPU.
+//
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+pub struct OffscreenTarget {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    readback_buffer: Buffer,
+    // rows in the readback buffer are padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    padded_bytes_per_row: u32,
+}
+
+impl OffscreenTarget {
+    /// `width`, `height` and `format` must match what the renderer's composite pipeline and graph targets were built for.
+    pub fn new(context: &GpuContext, width: u32, height: u32, format: TextureFormat) -> anyhow::Result<Self> {
+        let (width, height) = (width.max(1), height.max(1));
+
+        if swizzle_for(format).is_none() {
+            bail!("Can't read back offscreen format {:?}", format);
+        }
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_bytes_per_row = padded_bytes_per_row(width);
+
+        let readback_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen readback buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            width,
+            height,
+            format,
+            readback_buffer,
+            padded_bytes_per_row,
+        })
+    }
+
+    /// Records the copy of the rendered texture into the readback buffer.
+    pub fn copy_to_readback(&self, encoder: &mut CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Waits for the submitted copy and returns the pixels. Call after the encoder from
+    /// `copy_to_readback` has been submitted.
+    pub fn read_image(&self, context: &GpuContext) -> anyhow::Result<RgbaImage> {
+        let slice = self.readback_buffer.slice(..);
+
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        context.device.poll(wgpu::Maintain::Wait);
+
+        receiver
+            .recv()
+            .context("Readback buffer was dropped before mapping")?
+            .context("Failed to map the readback buffer")?;
+
+        let pixels = {
+            let data = slice.get_mapped_range();
+            let swap_red_blue = swizzle_for(self.format).unwrap_or_default();
+            unpad_rows(&data, self.width, self.height, self.padded_bytes_per_row, swap_red_blue)
+        };
+        self.readback_buffer.unmap();
+
+        RgbaImage::from_raw(self.width, self.height, pixels).ok_or_else(|| anyhow!("Readback size doesn't match {}x{}", self.width, self.height))
+    }
+}
+
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let bytes_per_row = width * BYTES_PER_PIXEL;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    bytes_per_row.div_ceil(align) * align
+}
+
+// Some(true) when red and blue need swapping to get rgba, None when the format can't be read back
+fn swizzle_for(format: TextureFormat) -> Option<bool> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Some(false),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Some(true),
+        _ => None,
+    }
+}
+
+// Drops the row padding and converts to tightly packed rgba
+fn unpad_rows(data: &[u8], width: u32, height: u32, padded_bytes_per_row: u32, swap_red_blue: bool) -> Vec<u8> {
+    let row_bytes = (width * BYTES_PER_PIXEL) as usize;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+
+    for row in data.chunks(padded_bytes_per_row as usize).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::offscreen::{padded_bytes_per_row, unpad_rows};
+
+    #[test]
+    fn test_unpad_rows_drops_padding_and_swaps_bgra() {
+        let width = 3;
+        let height = 2;
+        let padded = padded_bytes_per_row(width);
+        assert_eq!(padded % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT, 0);
+
+        let mut data = vec![0u8; (padded * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * padded + x * 4) as usize;
+                // bgra
+                data[i..i + 4].copy_from_slice(&[x as u8, y as u8, 100, 255]);
+            }
+        }
+
+        let pixels = unpad_rows(&data, width, height, padded, true);
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        // second pixel of the second row, now rgba
+        assert_eq!(&pixels[16..20], &[100, 1, 1, 255]);
+    }
+}
@@ -4,16 +4,17 @@ use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
 use spark_gap::model_builder::MODEL_BIND_GROUP_LAYOUT;
 use spark_gap::model_mesh::ModelVertex;
 use spark_gap::texture_config::TextureType;
-use wgpu::{IndexFormat, RenderPass};
+use wgpu::{BindGroup, IndexFormat, RenderPass};
 
 use crate::load_shader;
 use crate::params::shader_params::SHADER_PARAMETERS_BIND_GROUP_LAYOUT;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
 use crate::player::Player;
 use crate::render::main_render::Pipelines;
 use crate::render::shadow_material::{SHADOW_USE_BIND_GROUP_LAYOUT, ShadowMaterial};
 use crate::world::World;
 
-pub fn create_player_shader_pipeline(context: &GpuContext) -> Pipelines {
+pub fn create_player_shader_pipeline(context: &GpuContext, sample_count: u32) -> Pipelines {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let model_bind_group_layout = context.bind_layout_cache.get(MODEL_BIND_GROUP_LAYOUT).unwrap();
     let params_bind_group_layout = context.bind_layout_cache.get(SHADER_PARAMETERS_BIND_GROUP_LAYOUT).unwrap();
@@ -73,9 +74,6 @@ pub fn create_player_shader_pipeline(context: &GpuContext) -> Pipelines {
         push_constant_ranges: &[],
     });
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
     let forward_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("player forward pipeline"),
         layout: Some(&forward_layout),
@@ -87,7 +85,7 @@ pub fn create_player_shader_pipeline(context: &GpuContext) -> Pipelines {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(swapchain_format.into())],
+            targets: &[Some(SCENE_FORMAT.into()), emission_target(None)],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -105,7 +103,10 @@ pub fn create_player_shader_pipeline(context: &GpuContext) -> Pipelines {
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -115,8 +116,14 @@ pub fn create_player_shader_pipeline(context: &GpuContext) -> Pipelines {
     }
 }
 
-pub fn shadow_render_player<'a>(context: &'a GpuContext, world: &'a World, mut render_pass: RenderPass<'a>, player: &'a Player) -> RenderPass<'a> {
-    render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
+pub fn shadow_render_player<'a>(
+    context: &'a GpuContext,
+    world: &'a World,
+    mut render_pass: RenderPass<'a>,
+    player: &'a Player,
+    light_camera: &'a BindGroup,
+) -> RenderPass<'a> {
+    render_pass.set_bind_group(0, light_camera, &[]);
     render_pass.set_bind_group(1, &player.model.bind_group, &[]);
     render_pass.set_bind_group(2, &world.shader_params.bind_group, &[]);
 
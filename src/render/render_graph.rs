@@ -0,0 +1,237 @@
+use anyhow::bail;
+use hashbrown::HashMap;
+use spark_gap::gpu_context::GpuContext;
+use wgpu::{TextureFormat, TextureUsages, TextureView};
+
+//
+// A small render graph. Passes declare the resources they read and write, and the graph
+// runs every writer of a resource before its readers. Writers of the same resource keep the
+// order they were added in.
+//
+// Resources are either transient targets, allocated by the graph at the output size or a
+// fraction of it and reallocated on resize, like the depth, scene, emission and blur targets,
+// or imported ones owned elsewhere like the shadow map and the swapchain, which only take part
+// in the ordering.
+//
+// `P` is whatever the owner dispatches on when running a pass.
+//
+
+pub type ResourceName = &'static str;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TargetDesc {
+    pub format: TextureFormat,
+    pub sample_count: u32,
+    /// The target is 1 / size_divisor of the output size.
+    pub size_divisor: u32,
+    pub usage: TextureUsages,
+}
+
+impl TargetDesc {
+    /// Window sized attachment.
+    pub fn attachment(format: TextureFormat, sample_count: u32) -> Self {
+        Self {
+            format,
+            sample_count,
+            size_divisor: 1,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+
+    /// Window sized attachment that later passes sample from.
+    pub fn sampled(format: TextureFormat) -> Self {
+        Self {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            ..Self::attachment(format, 1)
+        }
+    }
+}
+
+struct TransientTarget {
+    name: ResourceName,
+    desc: TargetDesc,
+    view: Option<TextureView>,
+}
+
+struct GraphPass<P> {
+    name: &'static str,
+    pass: P,
+    reads: Vec<ResourceName>,
+    writes: Vec<ResourceName>,
+}
+
+pub struct RenderGraph<P> {
+    passes: Vec<GraphPass<P>>,
+    transients: Vec<TransientTarget>,
+    imports: Vec<ResourceName>,
+    order: Vec<usize>,
+}
+
+impl<P: Copy> RenderGraph<P> {
+    pub fn new() -> Self {
+        Self {
+            passes: vec![],
+            transients: vec![],
+            imports: vec![],
+            order: vec![],
+        }
+    }
+
+    pub fn add_transient(&mut self, name: ResourceName, desc: TargetDesc) {
+        self.transients.push(TransientTarget { name, desc, view: None });
+    }
+
+    pub fn add_import(&mut self, name: ResourceName) {
+        self.imports.push(name);
+    }
+
+    pub fn add_pass(&mut self, name: &'static str, pass: P, reads: &[ResourceName], writes: &[ResourceName]) {
+        self.passes.push(GraphPass {
+            name,
+            pass,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+    }
+
+    /// Checks the declared resources, orders the passes and allocates the transient targets at `width` x `height`.
+    pub fn compile(&mut self, context: &GpuContext, width: u32, height: u32) -> anyhow::Result<()> {
+        for pass in self.passes.iter() {
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                if !self.is_declared(resource) {
+                    bail!("Pass {} uses undeclared resource {}", pass.name, resource);
+                }
+            }
+        }
+
+        let accesses: Vec<_> = self.passes.iter().map(|pass| (pass.reads.as_slice(), pass.writes.as_slice())).collect();
+        self.order = order_passes(&accesses)?;
+
+        self.resize(context, width, height);
+        Ok(())
+    }
+
+    /// Reallocates the transient targets for a new output size.
+    pub fn resize(&mut self, context: &GpuContext, width: u32, height: u32) {
+        for target in self.transients.iter_mut() {
+            target.view = Some(create_target_view(context, target.name, &target.desc, width, height));
+        }
+    }
+
+    /// Passes in the order they run.
+    pub fn ordered_passes(&self) -> Vec<P> {
+        self.order.iter().map(|index| self.passes[*index].pass).collect()
+    }
+
+    /// Returns None for imported resources or before the graph is compiled.
+    pub fn view(&self, name: ResourceName) -> Option<&TextureView> {
+        self.transients
+            .iter()
+            .find(|target| target.name == name)
+            .and_then(|target| target.view.as_ref())
+    }
+
+    fn is_declared(&self, name: &ResourceName) -> bool {
+        self.imports.contains(name) || self.transients.iter().any(|target| target.name == *name)
+    }
+}
+
+// Kahn's algorithm over the read after write edges, taking the earliest added ready pass each step
+fn order_passes(accesses: &[(&[ResourceName], &[ResourceName])]) -> anyhow::Result<Vec<usize>> {
+    let count = accesses.len();
+    let mut dependents: Vec<Vec<usize>> = vec![vec![]; count];
+    let mut dependency_counts = vec![0usize; count];
+
+    let mut writers: HashMap<ResourceName, Vec<usize>> = HashMap::new();
+    for (index, (_, writes)) in accesses.iter().enumerate() {
+        for resource in writes.iter() {
+            writers.entry(*resource).or_default().push(index);
+        }
+    }
+
+    let mut add_edge = |from: usize, to: usize| {
+        if from != to && !dependents[from].contains(&to) {
+            dependents[from].push(to);
+            dependency_counts[to] += 1;
+        }
+    };
+
+    for (index, (reads, writes)) in accesses.iter().enumerate() {
+        for resource in reads.iter() {
+            for writer in writers.get(resource).into_iter().flatten() {
+                // a pass that reads and writes a resource runs after its other writers
+                if !writes.contains(resource) || *writer < index {
+                    add_edge(*writer, index);
+                }
+            }
+        }
+        for resource in writes.iter() {
+            for writer in writers[resource].iter().filter(|writer| **writer < index) {
+                add_edge(*writer, index);
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(count);
+    let mut done = vec![false; count];
+
+    while order.len() < count {
+        let Some(next) = (0..count).find(|index| !done[*index] && dependency_counts[*index] == 0) else {
+            bail!("Render graph has a cycle");
+        };
+        done[next] = true;
+        order.push(next);
+        for dependent in dependents[next].iter() {
+            dependency_counts[*dependent] -= 1;
+        }
+    }
+
+    Ok(order)
+}
+
+fn create_target_view(context: &GpuContext, label: &str, desc: &TargetDesc, output_width: u32, output_height: u32) -> TextureView {
+    let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: (output_width / desc.size_divisor).max(1),
+            height: (output_height / desc.size_divisor).max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: desc.sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: desc.format,
+        usage: desc.usage,
+        view_formats: &[],
+    });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::render_graph::order_passes;
+
+    #[test]
+    fn test_passes_run_after_the_writers_of_their_inputs() {
+        // added out of order: composite, forward, shadow
+        let accesses: [(&[&str], &[&str]); 3] = [(&["scene"], &["output"]), (&["shadow_map"], &["scene", "depth"]), (&[], &["shadow_map"])];
+        assert_eq!(order_passes(&accesses).unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_writers_of_one_resource_keep_their_order() {
+        let accesses: [(&[&str], &[&str]); 4] = [
+            (&["shadow_map"], &["scene"]),
+            (&[], &["shadow_map"]),
+            (&[], &["shadow_map"]),
+            (&["scene"], &["scene"]),
+        ];
+        assert_eq!(order_passes(&accesses).unwrap(), vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn test_cycles_are_rejected() {
+        let accesses: [(&[&str], &[&str]); 2] = [(&["a"], &["b"]), (&["b"], &["a"])];
+        assert!(order_passes(&accesses).is_err());
+    }
+}
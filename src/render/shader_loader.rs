@@ -1,80 +1,201 @@
-use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescriptor};
+use anyhow::{anyhow, bail, Context};
+use hashbrown::HashMap;
+use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescriptor, ShaderDefValue};
 use std::borrow::Cow;
-use std::io::Read;
-
-pub fn try_every_shader_file(composer: &mut Composer, for_shader: &str, shader_dir: &str, max_iters: usize) -> anyhow::Result<()> {
-    let mut try_again = true;
-    let mut iters = 0;
-    while try_again {
-        try_again = false;
-        let shader_dir = std::fs::read_dir(shader_dir)?;
-        for entry in shader_dir {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.is_file() {
-                if path.extension().unwrap() != "wgsl" {
-                    continue;
-                }
-                if path.to_str().unwrap() == for_shader {
-                    continue;
-                }
-
-                let mut file = std::fs::File::open(&path)?;
-                let mut shader = String::new();
-
-                file.read_to_string(&mut shader)?;
-
-                let result = composer.add_composable_module(ComposableModuleDescriptor {
-                    file_path: path.to_str().unwrap(),
-                    source: shader.as_str(),
-                    ..Default::default()
-                });
-
-                match result {
-                    Ok(_) => try_again = false,
-                    Err(e) => {
-                        println!("composer error: {:#?}\n", e);
-                        try_again = true
-                    }
-                }
-            } else if path.is_dir() {
-                try_every_shader_file(composer, for_shader, path.to_str().unwrap(), max_iters)?;
-            }
+use std::path::{Path, PathBuf};
+
+use crate::point_lights::MAX_POINT_LIGHTS;
+use crate::shadow_cascades::SHADOW_CASCADES;
+use crate::world::BLUR_SCALE;
+
+//
+// Shaders are composed with naga_oil from the modules they #import. Every wgsl file with a
+// #define_import_path is a module; a shader's imports are registered with the composer in
+// dependency order, each after the modules it imports itself.
+//
+// Rust constants the shaders also need are passed as shader defs and read as #{NAME},
+// so they are only defined once.
+//
+
+pub const SHADER_DIR: &str = "shaders";
+
+// A wgsl file defining a composable module
+struct ShaderModule {
+    path: PathBuf,
+    source: String,
+}
+
+pub fn preprocess_shader(file_path: &'static str, base_include_path: &'static str) -> wgpu::ShaderModuleDescriptor<'static> {
+    let mut descriptor = compose_shader(file_path, base_include_path).unwrap_or_else(|e| {
+        log::error!("Failed to compile shader {}: {:#}", file_path, e);
+        panic!("{:#}", e);
+    });
+    descriptor.label = Some(file_path);
+    descriptor
+}
+
+/// Composes a shader with the modules it imports, returning the composer error instead of panicking.
+pub fn compose_shader(file_path: &str, base_include_path: &str) -> anyhow::Result<wgpu::ShaderModuleDescriptor<'static>> {
+    let module = compose_module(file_path, base_include_path)?;
+
+    Ok(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+    })
+}
+
+/// Composes and validates a shader into a naga module.
+pub fn compose_module(file_path: &str, base_include_path: &str) -> anyhow::Result<naga::Module> {
+    let modules = load_modules(Path::new(base_include_path))?;
+    let source = std::fs::read_to_string(file_path).with_context(|| format!("Failed to read shader {}", file_path))?;
+
+    let mut composer = Composer::default();
+
+    for import_path in import_order(file_path, &source, &modules)? {
+        let module = &modules[&import_path];
+        let result = composer.add_composable_module(ComposableModuleDescriptor {
+            file_path: &module.path.to_string_lossy(),
+            source: &module.source,
+            ..Default::default()
+        });
+        if let Err(e) = result {
+            bail!("{}", e.emit_to_string(&composer));
+        }
+    }
+
+    let result = composer.make_naga_module(NagaModuleDescriptor {
+        file_path,
+        source: &source,
+        shader_defs: shader_defs(),
+        ..Default::default()
+    });
+    result.map_err(|e| anyhow!("{}", e.emit_to_string(&composer)))
+}
+
+fn shader_defs() -> std::collections::HashMap<String, ShaderDefValue> {
+    [
+        ("BLUR_SCALE", BLUR_SCALE),
+        ("MAX_POINT_LIGHTS", MAX_POINT_LIGHTS as u32),
+        ("SHADOW_CASCADES", SHADOW_CASCADES as u32),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), ShaderDefValue::UInt(value)))
+    .collect()
+}
+
+/// The shader files `entry_file` is built from: itself and every module it imports, directly or
+/// through other modules.
+pub fn shader_dependencies(entry_file: &str, shader_dir: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let modules = load_modules(Path::new(shader_dir))?;
+    let source = std::fs::read_to_string(entry_file)?;
+
+    let mut dependencies = vec![PathBuf::from(entry_file)];
+    for import_path in import_order(entry_file, &source, &modules)? {
+        dependencies.push(modules[&import_path].path.clone());
+    }
+    Ok(dependencies)
+}
+
+// Every module under shader_dir by import path
+fn load_modules(shader_dir: &Path) -> anyhow::Result<HashMap<String, ShaderModule>> {
+    let mut modules = HashMap::new();
+    add_modules(shader_dir, &mut modules)?;
+    Ok(modules)
+}
+
+fn add_modules(dir: &Path, modules: &mut HashMap<String, ShaderModule>) -> anyhow::Result<()> {
+    let entries = std::fs::read_dir(dir).with_context(|| format!("Failed to read shader directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            add_modules(&path, modules)?;
+            continue;
+        }
+        if !path.extension().is_some_and(|extension| extension == "wgsl") {
+            continue;
         }
 
-        iters += 1;
+        let source = std::fs::read_to_string(&path)?;
+        let Some(import_path) = define_import_path(&source) else {
+            continue;
+        };
 
-        if iters > max_iters {
-            return Err(anyhow::anyhow!("Max iterations reached"));
+        if let Some(existing) = modules.get(&import_path) {
+            bail!("{} is defined by both {} and {}", import_path, existing.path.display(), path.display());
         }
+        modules.insert(import_path, ShaderModule { path, source });
     }
 
     Ok(())
 }
 
-pub fn preprocess_shader(file_path: &'static str, base_include_path: &'static str) -> wgpu::ShaderModuleDescriptor<'static> {
-    let mut composer = Composer::non_validating();
+// Import paths of the modules a shader needs, each after the modules it imports
+fn import_order(file_path: &str, source: &str, modules: &HashMap<String, ShaderModule>) -> anyhow::Result<Vec<String>> {
+    let mut order = vec![];
+    // the chain of modules being visited, to report cycles
+    let mut chain = vec![define_import_path(source).unwrap_or_else(|| file_path.to_string())];
+    visit_imports(file_path, source, modules, &mut chain, &mut order)?;
+    Ok(order)
+}
 
-    println!("file_path: {:?}", &file_path);
-    let shader = std::fs::read_to_string(file_path).unwrap();
+fn visit_imports(
+    file_path: &str,
+    source: &str,
+    modules: &HashMap<String, ShaderModule>,
+    chain: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    for import in imports(source) {
+        let Some(import_path) = resolve_import(&import, modules) else {
+            bail!("{} imports {}, which no shader defines with #define_import_path", file_path, import);
+        };
 
-    try_every_shader_file(&mut composer, file_path, base_include_path, 100).unwrap();
+        if chain.iter().any(|path| path == import_path) {
+            bail!("Shader import cycle: {} -> {}", chain.join(" -> "), import_path);
+        }
+        if order.iter().any(|path| path == import_path) {
+            continue;
+        }
 
-    let module = composer
-        .make_naga_module(NagaModuleDescriptor {
-            file_path,
-            source: shader.as_str(),
-            ..Default::default()
-        })
-        .unwrap_or_else(|e| {
-            log::error!("Failed to compile shader {}: {}", file_path, e.inner);
-            panic!("{}", e.inner);
-        });
+        let module = &modules[import_path];
+        chain.push(import_path.to_string());
+        visit_imports(&module.path.to_string_lossy(), &module.source, modules, chain, order)?;
+        chain.pop();
 
-    wgpu::ShaderModuleDescriptor {
-        label: Some(file_path),
-        source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+        order.push(import_path.to_string());
+    }
+
+    Ok(())
+}
+
+fn define_import_path(source: &str) -> Option<String> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("#define_import_path"))
+        .map(|path| path.trim().to_string())
+}
+
+// Everything after #import up to the item list or alias, like spark::common from
+// `#import spark::common::{CameraUniform, FragmentOutput};`
+fn imports(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("#import"))
+        .filter_map(|import| import.split(|c: char| c == '{' || c == ';' || c.is_whitespace()).find(|part| !part.is_empty()))
+        .map(|import| import.trim_end_matches("::").to_string())
+        .collect()
+}
+
+// Imports can name an item inside a module, so the longest prefix that is a module wins
+fn resolve_import<'a, T>(import: &str, modules: &'a HashMap<String, T>) -> Option<&'a str> {
+    let mut path = import;
+    loop {
+        if let Some((import_path, _)) = modules.get_key_value(path) {
+            return Some(import_path);
+        }
+        path = &path[..path.rfind("::")?];
     }
 }
 
@@ -84,3 +205,108 @@ macro_rules! load_shader {
         $crate::render::shader_loader::preprocess_shader(concat!("shaders/", $file_path), "shaders")
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+    use std::path::PathBuf;
+
+    use crate::params::shader_params::ShaderParametersUniform;
+    use crate::render::shader_loader::{compose_module, define_import_path, import_order, imports, resolve_import, ShaderModule, SHADER_DIR};
+
+    // Every shader a pipeline is built from
+    const ENTRY_SHADERS: [&str; 10] = [
+        "player_shader.wgsl",
+        "floor_shader.wgsl",
+        "wiggle_shader.wgsl",
+        "sprite_shader.wgsl",
+        "bullet_shader.wgsl",
+        "burn_mark_shader.wgsl",
+        "impact_sprite_shader.wgsl",
+        "bloom_shader.wgsl",
+        "bullet_compute_shader.wgsl",
+        "debug_shadow_shader.wgsl",
+    ];
+
+    fn modules(sources: &[(&str, &str)]) -> HashMap<String, ShaderModule> {
+        sources
+            .iter()
+            .map(|(file, source)| {
+                let module = ShaderModule {
+                    path: PathBuf::from(file),
+                    source: source.to_string(),
+                };
+                (define_import_path(source).unwrap(), module)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_imports_resolve_to_modules() {
+        let source = "#define_import_path spark::floor_shader\n\
+                      #import spark::common::{CameraUniform, FragmentOutput};\n\
+                      #import spark::common::select_cascade\n\
+                      #import spark::lights\n";
+
+        assert_eq!(define_import_path(source).as_deref(), Some("spark::floor_shader"));
+        assert_eq!(imports(source), vec!["spark::common", "spark::common::select_cascade", "spark::lights"]);
+
+        let modules = HashMap::from([(String::from("spark::common"), ())]);
+        assert_eq!(resolve_import("spark::common::select_cascade", &modules), Some("spark::common"));
+        assert_eq!(resolve_import("spark::lights", &modules), None);
+    }
+
+    #[test]
+    fn test_modules_come_after_their_imports() {
+        let modules = modules(&[
+            ("lights.wgsl", "#define_import_path spark::lights\n#import spark::common::PointLight\n"),
+            ("common.wgsl", "#define_import_path spark::common\n"),
+        ]);
+        let source = "#import spark::lights::point_light_diffuse\n#import spark::common::CameraUniform\n";
+
+        assert_eq!(import_order("entry.wgsl", source, &modules).unwrap(), vec!["spark::common", "spark::lights"]);
+    }
+
+    #[test]
+    fn test_missing_modules_and_cycles_are_errors() {
+        let modules = modules(&[
+            ("a.wgsl", "#define_import_path spark::a\n#import spark::b::x\n"),
+            ("b.wgsl", "#define_import_path spark::b\n#import spark::a::y\n"),
+        ]);
+
+        let missing = import_order("entry.wgsl", "#import spark::c::z\n", &modules).unwrap_err();
+        assert!(missing.to_string().contains("spark::c::z"), "{}", missing);
+
+        let cycle = import_order("entry.wgsl", "#import spark::a::x\n", &modules).unwrap_err();
+        assert!(cycle.to_string().contains("spark::a -> spark::b -> spark::a"), "{}", cycle);
+    }
+
+    #[test]
+    fn test_entry_shaders_compose_and_validate() {
+        for shader in ENTRY_SHADERS {
+            let file_path = format!("{}/{}", SHADER_DIR, shader);
+            let module = compose_module(&file_path, SHADER_DIR).unwrap_or_else(|e| panic!("{}: {:#}", file_path, e));
+
+            naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+                .validate(&module)
+                .unwrap_or_else(|e| panic!("{}: {:?}", file_path, e));
+        }
+    }
+
+    // a mismatch would shift every field after it without any error
+    #[test]
+    fn test_shader_parameters_match_the_uniform() {
+        let module = compose_module("shaders/floor_shader.wgsl", SHADER_DIR).unwrap();
+
+        let span = module
+            .types
+            .iter()
+            .find_map(|(_, ty)| match ty.inner {
+                naga::TypeInner::Struct { span, .. } if ty.name.as_deref().is_some_and(|name| name.starts_with("ShaderParameters")) => Some(span),
+                _ => None,
+            })
+            .expect("floor shader uses ShaderParameters");
+
+        assert_eq!(span as usize, std::mem::size_of::<ShaderParametersUniform>());
+    }
+}
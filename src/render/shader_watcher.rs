@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use hashbrown::HashMap;
+
+// How often the shader directory is checked for changes
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls a directory and its subdirectories for changed wgsl files, by modification time.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_scan: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        let mut watcher = Self {
+            dir: dir.as_ref().to_path_buf(),
+            modified: HashMap::new(),
+            last_scan: Instant::now(),
+        };
+        watcher.scan();
+        watcher
+    }
+
+    /// Files changed or added since the last poll, empty between scans.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_scan.elapsed() < SCAN_INTERVAL {
+            return vec![];
+        }
+        self.last_scan = Instant::now();
+        self.scan()
+    }
+
+    fn scan(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        let dir = self.dir.clone();
+        self.scan_dir(&dir, &mut changed);
+        changed
+    }
+
+    // recurses like the shader loader, which finds modules in subdirectories too
+    fn scan_dir(&mut self, dir: &Path, changed: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                self.scan_dir(&path, changed);
+                continue;
+            }
+            if !path.extension().is_some_and(|extension| extension == "wgsl") {
+                continue;
+            }
+            // editors can briefly remove a file while saving, it shows up on a later scan
+            let Ok(modified) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if self.modified.insert(path.clone(), modified) != Some(modified) {
+                changed.push(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    use crate::render::shader_watcher::ShaderWatcher;
+
+    #[test]
+    fn test_scan_reports_modified_shaders() {
+        let dir = std::env::temp_dir().join(format!("shader_watcher_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shader = dir.join("floor_shader.wgsl");
+        std::fs::write(&shader, "// floor").unwrap();
+        std::fs::create_dir_all(dir.join("lighting")).unwrap();
+        let module = dir.join("lighting").join("shadows.wgsl");
+        std::fs::write(&module, "// shadows").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a shader").unwrap();
+
+        let mut watcher = ShaderWatcher::new(&dir);
+        assert!(watcher.scan().is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options().write(true).open(&shader).unwrap().set_modified(later).unwrap();
+        assert_eq!(watcher.scan(), vec![shader.clone()]);
+        assert!(watcher.scan().is_empty());
+
+        File::options().write(true).open(&module).unwrap().set_modified(later).unwrap();
+        assert_eq!(watcher.scan(), vec![module.clone()]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
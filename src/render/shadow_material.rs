@@ -2,16 +2,19 @@ use std::mem;
 
 use glam::{Mat4, vec3};
 use spark_gap::buffers::create_mat4_buffer_init;
+use spark_gap::camera::camera_handler::{CameraUniform, CAMERA_BIND_GROUP_LAYOUT};
 use spark_gap::gpu_context::{get_or_create_bind_group_layout, GpuContext};
 use spark_gap::small_mesh::{create_unit_square, SmallMesh};
 use wgpu::{BindGroup, BindGroupLayout, Buffer, RenderPass, RenderPipeline, Sampler, Texture, TextureView};
 use wgpu::util::DeviceExt;
 
 use crate::load_shader;
+use crate::render::bloom::SCENE_FORMAT;
+use crate::shadow_cascades::{ShadowCascade, SHADOW_CASCADES};
 
-pub const SHADOW_WIDTH: u32 = 6 * 1024;
-pub const SHADOW_HEIGHT: u32 = 6 * 1024;
-pub const SHADOW_TEXTURE_LAYERS: u32 = 2;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+// one layer per cascade
+pub const SHADOW_TEXTURE_LAYERS: u32 = SHADOW_CASCADES as u32;
 
 pub const SHADOW_USE_BIND_GROUP_LAYOUT: &str = "shadow use bind group layout";
 pub const SHADOW_DEBUG_BIND_GROUP_LAYOUT: &str = "shadow debug bind group layout";
@@ -29,6 +32,9 @@ pub struct ShadowMaterial {
     pub shadow_use_bind_group: BindGroup,
     pub shadow_debug_bind_group: BindGroup,
     pub shadow_debug_pipeline: RenderPipeline,
+    // light view and projection of each cascade, bound as the camera in the shadow passes
+    pub cascade_camera_buffers: Vec<Buffer>,
+    pub cascade_camera_bind_groups: Vec<BindGroup>,
 }
 
 pub fn create_shadow_map_material(context: &mut GpuContext) -> ShadowMaterial {
@@ -51,9 +57,9 @@ pub fn create_shadow_map_material(context: &mut GpuContext) -> ShadowMaterial {
     });
 
     let texture_size = wgpu::Extent3d {
-        width: 2048,
-        height: 2048,
-        depth_or_array_layers: 2,
+        width: SHADOW_MAP_SIZE,
+        height: SHADOW_MAP_SIZE,
+        depth_or_array_layers: SHADOW_TEXTURE_LAYERS,
     };
 
     // multi layered depth texture
@@ -137,6 +143,37 @@ pub fn create_shadow_map_material(context: &mut GpuContext) -> ShadowMaterial {
 
     let shadow_debug_pipeline = create_debug_depth_render_pipeline(context);
 
+    let camera_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
+
+    let cascade_camera_buffers = (0..SHADOW_CASCADES)
+        .map(|_| {
+            context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("shadow cascade camera"),
+                contents: bytemuck::bytes_of(&CameraUniform {
+                    projection: Mat4::IDENTITY,
+                    view: Mat4::IDENTITY,
+                    position: vec3(0.0, 0.0, 0.0),
+                    _padding: 0,
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        })
+        .collect::<Vec<Buffer>>();
+
+    let cascade_camera_bind_groups = cascade_camera_buffers
+        .iter()
+        .map(|buffer| {
+            context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("shadow cascade camera bind group"),
+                layout: camera_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            })
+        })
+        .collect::<Vec<BindGroup>>();
+
     ShadowMaterial {
         texture,
         texture_view,
@@ -149,6 +186,22 @@ pub fn create_shadow_map_material(context: &mut GpuContext) -> ShadowMaterial {
         shadow_use_bind_group,
         shadow_debug_bind_group,
         shadow_debug_pipeline,
+        cascade_camera_buffers,
+        cascade_camera_bind_groups,
+    }
+}
+
+impl ShadowMaterial {
+    pub fn update_cascades(&self, context: &GpuContext, cascades: &[ShadowCascade; SHADOW_CASCADES]) {
+        for (buffer, cascade) in self.cascade_camera_buffers.iter().zip(cascades.iter()) {
+            let camera_uniform = CameraUniform {
+                projection: cascade.projection,
+                view: cascade.view,
+                position: cascade.view.inverse().w_axis.truncate(),
+                _padding: 0,
+            };
+            context.queue.write_buffer(buffer, 0, bytemuck::bytes_of(&camera_uniform));
+        }
     }
 }
 
@@ -249,9 +302,6 @@ pub fn create_debug_depth_render_pipeline(gpu_context: &GpuContext) -> RenderPip
         push_constant_ranges: &[],
     });
 
-    let swapchain_capabilities = gpu_context.surface.get_capabilities(&gpu_context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
-
     let render_pipeline = gpu_context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("debug texture pipeline"),
         layout: Some(&pipeline_layout),
@@ -263,7 +313,7 @@ pub fn create_debug_depth_render_pipeline(gpu_context: &GpuContext) -> RenderPip
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(swapchain_format.into())],
+            targets: &[Some(SCENE_FORMAT.into())],
         }),
         primitive: wgpu::PrimitiveState::default(),
         depth_stencil: Some(wgpu::DepthStencilState {
@@ -3,14 +3,16 @@ use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
 use wgpu::{RenderPass, RenderPipeline};
 
+use crate::bullets::BulletSystem;
 use crate::load_shader;
 use crate::muzzle_flash::MuzzleFlash;
+use crate::render::bloom::{emission_target, SCENE_FORMAT};
 use crate::render::buffers::TRANSFORM_BIND_GROUP_LAYOUT;
 use crate::small_mesh::SmallMesh;
 use crate::sprite_sheet::SPRITE_BIND_GROUP_LAYOUT;
 use crate::world::World;
 
-pub fn create_sprite_shader_pipeline(context: &GpuContext) -> RenderPipeline {
+pub fn create_sprite_shader_pipeline(context: &GpuContext, sample_count: u32) -> RenderPipeline {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let transform_bind_group_layout = context.bind_layout_cache.get(TRANSFORM_BIND_GROUP_LAYOUT).unwrap();
     let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
@@ -29,8 +31,18 @@ pub fn create_sprite_shader_pipeline(context: &GpuContext) -> RenderPipeline {
 
     let shader = context.device.create_shader_module(load_shader!("sprite_shader.wgsl").into());
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
+    let blend = Some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    });
 
     let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("sprite render pipeline"),
@@ -43,22 +55,14 @@ pub fn create_sprite_shader_pipeline(context: &GpuContext) -> RenderPipeline {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: swapchain_format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::SrcAlpha,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                    alpha: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::One,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: SCENE_FORMAT,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
                 }),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
+                emission_target(blend),
+            ],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -76,7 +80,10 @@ pub fn create_sprite_shader_pipeline(context: &GpuContext) -> RenderPipeline {
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -100,13 +107,117 @@ pub fn render_muzzle_flashes<'a>(world: &'a World, mut render_pass: RenderPass<'
     render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
     render_pass.set_bind_group(1, &flash.transform_bind_group, &[]);
 
-    render_pass.set_bind_group(2, &flash.impact_spritesheet.material.bind_group, &[]);
-    render_pass.set_bind_group(3, &flash.impact_spritesheet.uniform_bind_group, &[]);
+    let spritesheet = &flash.spritesheets[world.state.active_weapon];
+    render_pass.set_bind_group(2, &spritesheet.material.bind_group, &[]);
+    render_pass.set_bind_group(3, &spritesheet.uniform_bind_group, &[]);
 
     render_pass.set_vertex_buffer(0, flash.sprite_mesh.vertex_buffer.slice(..));
     render_pass.set_vertex_buffer(1, flash.age_buffer.slice(..));
 
-    render_pass.draw(0..6, 0..(flash.sprites_age.len() as u32));
+    render_pass.draw(0..6, 0..(world.state.muzzle_flash_ages.len() as u32));
+
+    render_pass
+}
+
+pub fn create_impact_sprite_shader_pipeline(context: &GpuContext, sample_count: u32) -> RenderPipeline {
+    let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
+    let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
+    let sprite_bind_group_layout = context.bind_layout_cache.get(SPRITE_BIND_GROUP_LAYOUT).unwrap();
+
+    let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("impact sprite pipeline layout"),
+        bind_group_layouts: &[camera_bind_group_layout, material_bind_group_layout, sprite_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader = context.device.create_shader_module(load_shader!("impact_sprite_shader.wgsl").into());
+
+    let blend = Some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    });
+
+    let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("impact sprite render pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[SmallMesh::vertex_description(), impact_instance_buffer_layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: SCENE_FORMAT,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                emission_target(blend),
+            ],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        // overlapping explosions blend instead of cutting each other off
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    });
+
+    render_pipeline
+}
+
+fn impact_instance_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    use std::mem;
+    wgpu::VertexBufferLayout {
+        array_stride: mem::size_of::<glam::Vec4>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x4,
+        }],
+    }
+}
+
+pub fn render_bullet_impacts<'a>(world: &'a World, mut render_pass: RenderPass<'a>, bullet_system: &'a BulletSystem) -> RenderPass<'a> {
+    if bullet_system.num_impacts() == 0 {
+        return render_pass;
+    }
+
+    render_pass.set_bind_group(0, &world.camera_handler.bind_group, &[]);
+    render_pass.set_bind_group(1, &bullet_system.impact_spritesheet.material.bind_group, &[]);
+    render_pass.set_bind_group(2, &bullet_system.impact_spritesheet.uniform_bind_group, &[]);
+
+    render_pass.set_vertex_buffer(0, bullet_system.impact_mesh.vertex_buffer.slice(..));
+    render_pass.set_vertex_buffer(1, bullet_system.impact_instances_buffer.slice(..));
+
+    render_pass.draw(0..bullet_system.impact_mesh.num_elements, 0..bullet_system.num_impacts());
 
     render_pass
 }
@@ -1,29 +1 @@
-use spark_gap::gpu_context::GpuContext;
-use wgpu::{Texture, TextureView};
-
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-
-pub fn create_depth_texture_view(context: &GpuContext) -> TextureView {
-    let size = context.window.inner_size();
-
-    let size = wgpu::Extent3d {
-        width: size.width,
-        height: size.height,
-        depth_or_array_layers: 1,
-    };
-
-    let desc = wgpu::TextureDescriptor {
-        label: Some("depth_texture"),
-        size,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: DEPTH_FORMAT,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-        view_formats: &[DEPTH_FORMAT],
-    };
-
-    let texture = context.device.create_texture(&desc);
-    texture.create_view(&wgpu::TextureViewDescriptor::default())
-}
-
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use glam::vec3;
+
+use crate::game_state::{MoveKeys, TickInput};
+use crate::weapons::WeaponSwitch;
+
+//
+// Replay file layout, all little endian:
+//
+//   magic     8 bytes  "ANGRYRPL"
+//   version   u32
+//   seed      u64
+//   tick      f32      simulation tick length in seconds
+//   count     u32      number of ticks
+//   ticks     count * (keys u8, buttons u8, has_aim u8, weapon u8, aim_x f32, aim_y f32, aim_z f32)
+//
+// weapon is the weapon switch of the tick: 0 none, 1 next, 2 previous, 3 + n select weapon n.
+//
+
+const REPLAY_MAGIC: &[u8; 8] = b"ANGRYRPL";
+pub const REPLAY_VERSION: u32 = 2;
+
+const BUTTON_FIRE: u8 = 1;
+
+/// The seed and per-tick inputs of a run. Stepping a new `GameState` with these reproduces it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub tick: f32,
+    pub inputs: Vec<TickInput>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, tick: f32) -> Self {
+        Self { seed, tick, inputs: vec![] }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::read_from(&mut reader)
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&REPLAY_VERSION.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.tick.to_le_bytes())?;
+        writer.write_all(&(self.inputs.len() as u32).to_le_bytes())?;
+
+        for input in self.inputs.iter() {
+            let buttons = if input.is_firing { BUTTON_FIRE } else { 0 };
+            let aim = input.aim_point.unwrap_or_default();
+
+            writer.write_all(&[input.keys.0, buttons, input.aim_point.is_some() as u8, encode_weapon_switch(input.switch_weapon)])?;
+            writer.write_all(&aim.x.to_le_bytes())?;
+            writer.write_all(&aim.y.to_le_bytes())?;
+            writer.write_all(&aim.z.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(anyhow::anyhow!("Not a replay file"));
+        }
+
+        let version = u32::from_le_bytes(read_array(reader)?);
+        if version != REPLAY_VERSION {
+            return Err(anyhow::anyhow!("Unsupported replay version {}, expected {}", version, REPLAY_VERSION));
+        }
+
+        let seed = u64::from_le_bytes(read_array(reader)?);
+        let tick = f32::from_le_bytes(read_array(reader)?);
+        let count = u32::from_le_bytes(read_array(reader)?) as usize;
+
+        // count comes from the file, a corrupt one shouldn't reserve more than the records actually read
+        let mut inputs = vec![];
+
+        for _ in 0..count {
+            let [keys, buttons, has_aim, weapon] = read_array(reader)?;
+            let x = f32::from_le_bytes(read_array(reader)?);
+            let y = f32::from_le_bytes(read_array(reader)?);
+            let z = f32::from_le_bytes(read_array(reader)?);
+
+            inputs.push(TickInput {
+                keys: MoveKeys(keys),
+                is_firing: buttons & BUTTON_FIRE != 0,
+                aim_point: if has_aim != 0 { Some(vec3(x, y, z)) } else { None },
+                switch_weapon: decode_weapon_switch(weapon),
+            });
+        }
+
+        Ok(Self { seed, tick, inputs })
+    }
+}
+
+fn encode_weapon_switch(switch: Option<WeaponSwitch>) -> u8 {
+    match switch {
+        None => 0,
+        Some(WeaponSwitch::Next) => 1,
+        Some(WeaponSwitch::Previous) => 2,
+        Some(WeaponSwitch::Select(index)) => index.saturating_add(3),
+    }
+}
+
+fn decode_weapon_switch(byte: u8) -> Option<WeaponSwitch> {
+    match byte {
+        0 => None,
+        1 => Some(WeaponSwitch::Next),
+        2 => Some(WeaponSwitch::Previous),
+        _ => Some(WeaponSwitch::Select(byte - 3)),
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> anyhow::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Where the simulation's input comes from.
+pub enum ReplayMode {
+    Live,
+    Recording(Replay),
+    Playing { replay: Replay, cursor: usize },
+}
+
+impl ReplayMode {
+    /// Picks the input for the next tick. Recording keeps the live input, playback ignores it.
+    pub fn next_input(&mut self, live_input: TickInput) -> TickInput {
+        match self {
+            ReplayMode::Live => live_input,
+            ReplayMode::Recording(replay) => {
+                replay.inputs.push(live_input);
+                live_input
+            }
+            ReplayMode::Playing { replay, cursor } => {
+                let input = replay.inputs.get(*cursor).copied().unwrap_or_default();
+                if *cursor == replay.inputs.len() {
+                    info!("Replay finished after {} ticks", replay.inputs.len());
+                }
+                *cursor += 1;
+                input
+            }
+        }
+    }
+
+    /// A new run: recordings start over with `seed` and playback rewinds.
+    pub fn restart(&mut self, seed: u64) {
+        match self {
+            ReplayMode::Live => {}
+            ReplayMode::Recording(replay) => *replay = Replay::new(seed, replay.tick),
+            ReplayMode::Playing { cursor, .. } => *cursor = 0,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self, ReplayMode::Playing { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use crate::game_state::{GameState, MoveKeys, TickInput};
+    use crate::replay::{Replay, ReplayMode};
+    use crate::timestep::SIMULATION_TICK;
+    use crate::weapons::WeaponSwitch;
+
+    #[test]
+    fn test_replay_round_trip() {
+        let mut replay = Replay::new(42, SIMULATION_TICK);
+        replay.inputs.push(TickInput::default());
+        replay.inputs.push(TickInput {
+            keys: MoveKeys(MoveKeys::FORWARD.0 | MoveKeys::LEFT.0),
+            is_firing: true,
+            aim_point: Some(vec3(1.5, 0.0, -3.25)),
+            switch_weapon: Some(WeaponSwitch::Select(2)),
+        });
+
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+
+        let loaded = Replay::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, replay);
+    }
+
+    #[test]
+    fn test_truncated_replay_is_an_error() {
+        let mut bytes = vec![];
+        Replay::new(42, SIMULATION_TICK).write_to(&mut bytes).unwrap();
+        let count_offset = bytes.len() - 4;
+        bytes[count_offset..].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(Replay::read_from(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_playback_reproduces_recorded_run() {
+        let seed = 7;
+        let mut recording = ReplayMode::Recording(Replay::new(seed, SIMULATION_TICK));
+        let mut recorded_state = GameState::new(seed);
+
+        for tick in 0..300 {
+            let live_input = TickInput {
+                keys: if tick % 50 < 25 { MoveKeys::RIGHT } else { MoveKeys::BACK },
+                is_firing: tick % 2 == 0,
+                aim_point: Some(vec3(tick as f32 * 0.05, 0.0, 5.0)),
+                switch_weapon: if tick == 100 { Some(WeaponSwitch::Next) } else { None },
+            };
+            let input = recording.next_input(live_input);
+            recorded_state.step(SIMULATION_TICK, &input);
+        }
+
+        let ReplayMode::Recording(replay) = recording else { unreachable!() };
+
+        let mut playback = ReplayMode::Playing { replay, cursor: 0 };
+        let mut replayed_state = GameState::new(seed);
+
+        for _ in 0..300 {
+            let input = playback.next_input(TickInput::default());
+            replayed_state.step(SIMULATION_TICK, &input);
+        }
+
+        assert_eq!(replayed_state.player.position, recorded_state.player.position);
+        assert_eq!(replayed_state.enemies.len(), recorded_state.enemies.len());
+        assert_eq!(replayed_state.burn_marks.len(), recorded_state.burn_marks.len());
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+pub const POINTS_PER_KILL: u64 = 100;
+pub const MAX_COMBO: u32 = 8;
+// Seconds without a kill before the combo multiplier drops a step
+const COMBO_DECAY_TIME: f32 = 1.5;
+
+pub const MAX_HIGH_SCORES: usize = 10;
+const HIGH_SCORES_FILE: &str = "high_scores.ron";
+
+/// Score of the current run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    pub points: u64,
+    pub kills: u32,
+    /// Multiplier applied to the next kill, from 1 to MAX_COMBO.
+    pub combo: u32,
+    combo_time_left: f32,
+    pub groups_fired: u32,
+    pub groups_hit: u32,
+    /// Seconds the player has been alive.
+    pub survival_time: f32,
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Self {
+            points: 0,
+            kills: 0,
+            combo: 1,
+            combo_time_left: 0.0,
+            groups_fired: 0,
+            groups_hit: 0,
+            survival_time: 0.0,
+        }
+    }
+
+    /// Each kill scores at the current multiplier then raises it.
+    pub fn add_kills(&mut self, kills: u32) {
+        for _ in 0..kills {
+            self.points += POINTS_PER_KILL * self.combo as u64;
+            self.kills += 1;
+            self.combo = (self.combo + 1).min(MAX_COMBO);
+            self.combo_time_left = COMBO_DECAY_TIME;
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32, player_is_alive: bool) {
+        if player_is_alive {
+            self.survival_time += delta_time;
+        }
+
+        if self.combo > 1 {
+            self.combo_time_left -= delta_time;
+            if self.combo_time_left <= 0.0 {
+                self.combo -= 1;
+                self.combo_time_left += COMBO_DECAY_TIME;
+            }
+        }
+    }
+
+    /// Fraction of bullet groups fired that hit at least one enemy.
+    pub fn accuracy(&self) -> f32 {
+        if self.groups_fired == 0 {
+            return 0.0;
+        }
+        self.groups_hit as f32 / self.groups_fired as f32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub points: u64,
+    pub kills: u32,
+    pub accuracy: f32,
+    pub survival_time: f32,
+    pub seed: u64,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+}
+
+/// Best runs, highest first, kept in a file in the user's data directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScore {
+    pub fn from_score(score: &Score, seed: u64) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            points: score.points,
+            kills: score.kills,
+            accuracy: score.accuracy(),
+            survival_time: score.survival_time,
+            seed,
+            timestamp,
+        }
+    }
+}
+
+impl HighScores {
+    /// `<data dir>/angry_wgpu_rust/high_scores.ron`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("angry_wgpu_rust").join(HIGH_SCORES_FILE))
+    }
+
+    /// A missing file is an empty table.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading high scores {}", path.display()))?;
+        ron::from_str(&text).with_context(|| format!("Parsing high scores {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text).with_context(|| format!("Writing high scores {}", path.display()))
+    }
+
+    /// Adds the run if it makes the table and returns its rank, 0 being the best.
+    pub fn insert(&mut self, high_score: HighScore) -> Option<usize> {
+        let rank = self.entries.partition_point(|entry| entry.points >= high_score.points);
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+        self.entries.insert(rank, high_score);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::score::{HighScore, HighScores, Score, MAX_COMBO, MAX_HIGH_SCORES, POINTS_PER_KILL};
+
+    #[test]
+    fn test_combo_builds_and_decays() {
+        let mut score = Score::new();
+
+        score.add_kills(3);
+        assert_eq!(score.points, POINTS_PER_KILL * (1 + 2 + 3));
+        assert_eq!(score.combo, 4);
+
+        score.add_kills(20);
+        assert_eq!(score.combo, MAX_COMBO);
+
+        for _ in 0..900 {
+            score.update(1.0 / 60.0, true);
+        }
+        assert_eq!(score.combo, 1);
+        assert!((score.survival_time - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_high_scores_keep_best_and_round_trip() {
+        let mut high_scores = HighScores::default();
+        let entry = |points| HighScore {
+            points,
+            kills: 1,
+            accuracy: 0.5,
+            survival_time: 10.0,
+            seed: 1,
+            timestamp: 0,
+        };
+
+        for points in 0..(MAX_HIGH_SCORES as u64 + 5) {
+            high_scores.insert(entry(points * 10));
+        }
+
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries[0].points, (MAX_HIGH_SCORES as u64 + 4) * 10);
+        assert_eq!(high_scores.insert(entry(0)), None);
+        assert_eq!(high_scores.insert(entry(1000)), Some(0));
+
+        let path = std::env::temp_dir().join(format!("angry_high_scores_{}.ron", std::process::id()));
+        high_scores.save(&path).unwrap();
+        let loaded = HighScores::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, high_scores);
+    }
+}
@@ -0,0 +1,208 @@
+use glam::{vec3, vec4, Mat4, Vec3};
+
+//
+// Cascaded shadow maps for the directional light.
+// The game camera frustum is cut into slices by view depth and each slice gets its own
+// orthographic light projection, rendered into one layer of the shadow map array.
+//
+
+// Also the SHADOW_CASCADES shader def, at most 4
+pub const SHADOW_CASCADES: usize = 3;
+
+// Shadows are only drawn this far from the camera
+pub const SHADOW_DISTANCE: f32 = 30.0;
+
+// Blend between logarithmic (1.0) and even (0.0) split distances
+const SPLIT_LAMBDA: f32 = 0.75;
+
+// Extra depth toward the light so casters outside a slice still land in its map
+const CASTER_MARGIN: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowCascade {
+    pub projection: Mat4,
+    pub view: Mat4,
+    /// View depth where this cascade ends.
+    pub far: f32,
+}
+
+impl ShadowCascade {
+    pub fn light_space_matrix(&self) -> Mat4 {
+        self.projection * self.view
+    }
+}
+
+/// View depth at the far end of each cascade.
+pub fn split_distances(near: f32, far: f32) -> [f32; SHADOW_CASCADES] {
+    let mut splits = [0.0; SHADOW_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate() {
+        let t = (i + 1) as f32 / SHADOW_CASCADES as f32;
+        let log = near * (far / near).powf(t);
+        let even = near + (far - near) * t;
+        *split = SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * even;
+    }
+    splits
+}
+
+/// Fits a light projection around each slice of the camera frustum between `near` and `far`.
+/// `light_direction` points from the scene toward the light.
+pub fn fit_cascades(
+    camera_projection: &Mat4,
+    camera_view: &Mat4,
+    near: f32,
+    far: f32,
+    light_direction: Vec3,
+    map_size: u32,
+) -> [ShadowCascade; SHADOW_CASCADES] {
+    let camera_corners = frustum_corners(camera_projection, camera_view);
+    let shadow_far = far.min(SHADOW_DISTANCE);
+    let splits = split_distances(near, shadow_far);
+
+    let mut slice_near = near;
+
+    splits.map(|slice_far| {
+        let corners = slice_corners(&camera_corners, near, far, slice_near, slice_far);
+        slice_near = slice_far;
+        fit_slice(&corners, light_direction, map_size, slice_far)
+    })
+}
+
+// World space corners of the frustum, the near plane first then the far plane
+fn frustum_corners(projection: &Mat4, view: &Mat4) -> [Vec3; 8] {
+    let inverse = (*projection * *view).inverse();
+    let mut corners = [Vec3::ZERO; 8];
+    let mut i = 0;
+    for z in [0.0, 1.0] {
+        for y in [-1.0, 1.0] {
+            for x in [-1.0, 1.0] {
+                let corner = inverse * vec4(x, y, z, 1.0);
+                corners[i] = corner.truncate() / corner.w;
+                i += 1;
+            }
+        }
+    }
+    corners
+}
+
+// View depth is linear along each corner edge of the frustum, so a slice is found by lerping the edges
+fn slice_corners(corners: &[Vec3; 8], near: f32, far: f32, slice_near: f32, slice_far: f32) -> [Vec3; 8] {
+    let t_near = (slice_near - near) / (far - near);
+    let t_far = (slice_far - near) / (far - near);
+    let mut slice = [Vec3::ZERO; 8];
+    for i in 0..4 {
+        let edge = corners[i + 4] - corners[i];
+        slice[i] = corners[i] + edge * t_near;
+        slice[i + 4] = corners[i] + edge * t_far;
+    }
+    slice
+}
+
+fn fit_slice(corners: &[Vec3; 8], light_direction: Vec3, map_size: u32, far: f32) -> ShadowCascade {
+    let center = corners.iter().sum::<Vec3>() / 8.0;
+
+    // a bounding sphere keeps the projection size fixed as the camera turns,
+    // rounded so float noise doesn't change it either
+    let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0f32, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let light_direction = light_direction.normalize();
+    // a light straight above or below can't use y as up, the view would be degenerate
+    let up = if light_direction.y.abs() > 0.99 {
+        vec3(0.0, 0.0, 1.0)
+    } else {
+        vec3(0.0, 1.0, 0.0)
+    };
+
+    let eye = center + light_direction * (radius + CASTER_MARGIN);
+    let view = Mat4::look_at_rh(eye, center, up);
+    let mut projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASTER_MARGIN);
+
+    // snap to whole shadow map texels so shadow edges don't shimmer as the camera moves
+    let texels_per_unit = map_size as f32 / 2.0;
+    let origin = (projection * view).transform_point3(Vec3::ZERO) * texels_per_unit;
+    let offset = (origin.round() - origin) / texels_per_unit;
+    projection.w_axis.x += offset.x;
+    projection.w_axis.y += offset.y;
+
+    ShadowCascade { projection, view, far }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Mat4, Vec3};
+
+    use crate::shadow_cascades::{fit_cascades, split_distances, SHADOW_CASCADES};
+
+    const NEAR: f32 = 0.1;
+    const FAR: f32 = 30.0;
+    const MAP_SIZE: u32 = 2048;
+
+    fn camera(position: Vec3) -> (Mat4, Mat4) {
+        let projection = Mat4::perspective_rh(45.0f32.to_radians(), 1.5, NEAR, FAR);
+        let view = Mat4::look_at_rh(position, position + vec3(0.0, -4.0, -2.0), vec3(0.0, 1.0, 0.0));
+        (projection, view)
+    }
+
+    #[test]
+    fn test_splits_cover_the_range() {
+        let splits = split_distances(NEAR, FAR);
+        assert!(splits[0] > NEAR);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[SHADOW_CASCADES - 1] - FAR).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_slices_fit_inside_their_cascade() {
+        let (projection, view) = camera(vec3(3.0, 5.0, 8.0));
+        let light_direction = vec3(-1.0, 1.0, -1.0).normalize();
+        let cascades = fit_cascades(&projection, &view, NEAR, FAR, light_direction, MAP_SIZE);
+        let inverse_view = view.inverse();
+
+        // points along the view ray at the middle of each slice
+        let mut slice_near = NEAR;
+        for cascade in cascades.iter() {
+            let depth = (slice_near + cascade.far) / 2.0;
+            let world_point = inverse_view.transform_point3(vec3(0.0, 0.0, -depth));
+            let light_point = cascade.light_space_matrix().project_point3(world_point);
+
+            assert!(light_point.x.abs() <= 1.0 && light_point.y.abs() <= 1.0, "{:?}", light_point);
+            assert!((0.0..=1.0).contains(&light_point.z), "{:?}", light_point);
+            slice_near = cascade.far;
+        }
+    }
+
+    #[test]
+    fn test_straight_down_light_fits() {
+        let (projection, view) = camera(vec3(3.0, 5.0, 8.0));
+        let cascades = fit_cascades(&projection, &view, NEAR, FAR, vec3(0.0, 1.0, 0.0), MAP_SIZE);
+        let inverse_view = view.inverse();
+
+        let mut slice_near = NEAR;
+        for cascade in cascades.iter() {
+            assert!(cascade.light_space_matrix().is_finite(), "{:?}", cascade);
+
+            let depth = (slice_near + cascade.far) / 2.0;
+            let world_point = inverse_view.transform_point3(vec3(0.0, 0.0, -depth));
+            let light_point = cascade.light_space_matrix().project_point3(world_point);
+            assert!(light_point.x.abs() <= 1.0 && light_point.y.abs() <= 1.0, "{:?}", light_point);
+            slice_near = cascade.far;
+        }
+    }
+
+    #[test]
+    fn test_cascades_snap_to_texels() {
+        let light_direction = vec3(-1.0, 1.0, -1.0).normalize();
+        let texels_per_unit = MAP_SIZE as f32 / 2.0;
+
+        for step in 0..10 {
+            let (projection, view) = camera(vec3(step as f32 * 0.013, 5.0, 8.0));
+            let cascades = fit_cascades(&projection, &view, NEAR, FAR, light_direction, MAP_SIZE);
+
+            for cascade in cascades.iter() {
+                let origin = cascade.light_space_matrix().transform_point3(Vec3::ZERO) * texels_per_unit;
+                assert!((origin.x - origin.x.round()).abs() < 0.01, "{:?}", origin);
+                assert!((origin.y - origin.y.round()).abs() < 0.01, "{:?}", origin);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use glam::Vec3;
+
+/// Uniform grid over the xz plane for finding nearby points. Rebuilt from scratch each tick:
+/// `clear`, `insert` every point, then `build` before querying with `nearby`.
+///
+/// Entries are kept in one vec sorted by cell so a rebuild doesn't allocate once it has warmed up.
+pub struct SpatialHash {
+    cell_size: f32,
+    entries: Vec<(u64, u32)>,
+}
+
+impl SpatialHash {
+    /// `cell_size` should be at least the largest query distance so that the 3x3 cells around
+    /// a point hold everything within that distance.
+    pub fn new(cell_size: f32) -> Self {
+        Self { cell_size, entries: vec![] }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, index: usize, position: Vec3) {
+        let (x, z) = self.cell_of(position);
+        self.entries.push((cell_key(x, z), index as u32));
+    }
+
+    pub fn build(&mut self) {
+        self.entries.sort_unstable();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Indices of the points in the cell holding `position` and its eight neighbours.
+    pub fn nearby(&self, position: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let (x, z) = self.cell_of(position);
+
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dz| cell_key(x + dx, z + dz)))
+            .flat_map(move |key| {
+                let start = self.entries.partition_point(|entry| entry.0 < key);
+                let end = start + self.entries[start..].partition_point(|entry| entry.0 == key);
+                self.entries[start..end].iter().map(|entry| entry.1 as usize)
+            })
+    }
+
+    fn cell_of(&self, position: Vec3) -> (i32, i32) {
+        ((position.x / self.cell_size).floor() as i32, (position.z / self.cell_size).floor() as i32)
+    }
+}
+
+fn cell_key(x: i32, z: i32) -> u64 {
+    ((x as u32 as u64) << 32) | (z as u32 as u64)
+}
@@ -0,0 +1,45 @@
+// Simulation ticks per second
+pub const SIMULATION_TICK: f32 = 1.0 / 60.0;
+
+// Upper bound on catch up ticks after a long frame, so a stall doesn't snowball.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Turns variable frame times into a whole number of fixed simulation ticks.
+/// The leftover fraction of a tick is used to interpolate between the last two states when rendering.
+pub struct FixedTimestep {
+    pub tick: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub const fn new(tick: f32) -> Self {
+        Self { tick, accumulator: 0.0 }
+    }
+
+    /// Adds the frame's delta time and returns how many ticks to simulate.
+    pub fn advance(&mut self, delta_time: f32) -> u32 {
+        self.accumulator += delta_time;
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick && ticks < MAX_TICKS_PER_FRAME {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+
+        if ticks == MAX_TICKS_PER_FRAME {
+            // drop the time we couldn't catch up on
+            self.accumulator = self.accumulator.min(self.tick);
+        }
+
+        ticks
+    }
+
+    /// How far between the previous and current simulation state the frame is, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick).clamp(0.0, 1.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
@@ -0,0 +1,413 @@
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use anyhow::Context;
+use glam::{vec3, Vec3};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::enemy::{spawn_enemy, Enemy, DEFAULT_MAX_ENEMIES};
+
+//
+// Enemy waves are read from a RON file, see angrygl_assets/waves.ron.
+// Waves run in order and the last one repeats once the others are cleared.
+// Unknown keys are errors so a typo doesn't silently fall back to a default.
+//
+
+pub const WAVES_FILE: &str = "angrygl_assets/waves.ron";
+
+/// Where the enemies of one spawn appear.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum SpawnPattern {
+    /// Evenly around a circle centered on the player.
+    Ring { radius: f32 },
+    /// Bunched up around a point `distance` from the player.
+    Cluster { distance: f32, spread: f32 },
+    /// Side by side on a line `distance` from the player, facing them.
+    Line { distance: f32, length: f32 },
+    /// Along one edge of a square arena centered on the origin.
+    ArenaEdge { half_size: f32 },
+}
+
+/// Difficulty multiplier that ramps from `start` to `end` over `ramp_time` seconds into a wave.
+/// Scales enemy speed and shortens the delay between spawns.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DifficultyCurve {
+    pub start: f32,
+    pub end: f32,
+    pub ramp_time: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveDefinition {
+    pub name: String,
+    /// Total enemies in the wave.
+    pub count: u32,
+    /// Enemies placed by each spawn.
+    #[serde(default = "default_per_spawn")]
+    pub per_spawn: u32,
+    pub pattern: SpawnPattern,
+    /// Seconds between spawns, and before the first one.
+    pub spawn_delay: f32,
+    #[serde(default)]
+    pub difficulty: DifficultyCurve,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveSet {
+    pub waves: Vec<WaveDefinition>,
+    /// Most enemies alive at once. Spawns wait for room past it.
+    #[serde(default = "default_max_enemies")]
+    pub max_enemies: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveEvent {
+    Started(usize),
+    Cleared(usize),
+}
+
+pub struct WaveDirector {
+    waves: Vec<WaveDefinition>,
+    max_enemies: usize,
+    wave_index: usize,
+    spawned: u32,
+    count_down: f32,
+    wave_time: f32,
+    announced: bool,
+    // scratch vec for spawn positions
+    positions: Vec<Vec3>,
+}
+
+fn default_per_spawn() -> u32 {
+    1
+}
+
+fn default_max_enemies() -> usize {
+    DEFAULT_MAX_ENEMIES
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self {
+            start: 1.0,
+            end: 1.0,
+            ramp_time: 0.0,
+        }
+    }
+}
+
+impl DifficultyCurve {
+    pub fn at(&self, wave_time: f32) -> f32 {
+        if self.ramp_time <= 0.0 {
+            return self.end;
+        }
+        let t = (wave_time / self.ramp_time).min(1.0);
+        self.start + (self.end - self.start) * t
+    }
+}
+
+impl Default for WaveSet {
+    /// One endless wave, one enemy a second on a circle around the player.
+    fn default() -> Self {
+        Self {
+            waves: vec![WaveDefinition {
+                name: String::from("Endless"),
+                count: u32::MAX,
+                per_spawn: 1,
+                pattern: SpawnPattern::Ring { radius: 10.0 },
+                spawn_delay: 1.0,
+                difficulty: DifficultyCurve::default(),
+            }],
+            max_enemies: DEFAULT_MAX_ENEMIES,
+        }
+    }
+}
+
+impl WaveSet {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading waves file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Parsing waves file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let wave_set: WaveSet = ron::from_str(text)?;
+        wave_set.validate()?;
+        Ok(wave_set)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.waves.is_empty() {
+            return Err(anyhow::anyhow!("No waves defined"));
+        }
+        if self.max_enemies == 0 {
+            return Err(anyhow::anyhow!("max_enemies must be above zero"));
+        }
+        for wave in self.waves.iter() {
+            if wave.count == 0 || wave.per_spawn == 0 {
+                return Err(anyhow::anyhow!("Wave '{}' needs a count and per_spawn above zero", wave.name));
+            }
+            if !is_positive(wave.spawn_delay) {
+                return Err(anyhow::anyhow!("Wave '{}' needs a spawn_delay above zero", wave.name));
+            }
+            if !is_positive(wave.difficulty.start) || !is_positive(wave.difficulty.end) {
+                return Err(anyhow::anyhow!("Wave '{}' difficulty must stay above zero", wave.name));
+            }
+            if !(wave.difficulty.ramp_time.is_finite() && wave.difficulty.ramp_time >= 0.0) {
+                return Err(anyhow::anyhow!("Wave '{}' difficulty ramp_time must not be negative", wave.name));
+            }
+            for (field, value) in wave.pattern.fields() {
+                if !is_positive(value) {
+                    return Err(anyhow::anyhow!("Wave '{}' needs a pattern {} above zero, got {}", wave.name, field, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SpawnPattern {
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        match *self {
+            SpawnPattern::Ring { radius } => vec![("radius", radius)],
+            SpawnPattern::Cluster { distance, spread } => vec![("distance", distance), ("spread", spread)],
+            SpawnPattern::Line { distance, length } => vec![("distance", distance), ("length", length)],
+            SpawnPattern::ArenaEdge { half_size } => vec![("half_size", half_size)],
+        }
+    }
+}
+
+// false for NaN and infinity too
+fn is_positive(value: f32) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+impl WaveDirector {
+    pub fn new(wave_set: WaveSet) -> Self {
+        let count_down = wave_set.waves[0].spawn_delay;
+        Self {
+            waves: wave_set.waves,
+            max_enemies: wave_set.max_enemies,
+            wave_index: 0,
+            spawned: 0,
+            count_down,
+            wave_time: 0.0,
+            announced: false,
+            positions: vec![],
+        }
+    }
+
+    /// Back to the start of the first wave.
+    pub fn reset(&mut self) {
+        self.wave_index = 0;
+        self.spawned = 0;
+        self.count_down = self.waves[0].spawn_delay;
+        self.wave_time = 0.0;
+        self.announced = false;
+    }
+
+    /// Index of the wave being played, counting up through repeats of the last wave.
+    pub fn current_wave(&self) -> usize {
+        self.wave_index
+    }
+
+    pub fn current_wave_name(&self) -> &str {
+        &self.definition().name
+    }
+
+    /// Difficulty multiplier right now.
+    pub fn difficulty(&self) -> f32 {
+        self.definition().difficulty.at(self.wave_time)
+    }
+
+    pub fn update(&mut self, delta_time: f32, rng: &mut impl Rng, enemies: &mut Vec<Enemy>, player_position: Vec3, events: &mut Vec<WaveEvent>) {
+        if !self.announced {
+            self.announced = true;
+            events.push(WaveEvent::Started(self.wave_index));
+        }
+
+        self.wave_time += delta_time;
+
+        let wave = &self.waves[self.wave_index.min(self.waves.len() - 1)];
+
+        if self.spawned < wave.count {
+            self.count_down -= delta_time;
+
+            if self.count_down <= 0.0 {
+                // wait for room rather than dropping enemies from the wave
+                let room = self.max_enemies.saturating_sub(enemies.len()) as u32;
+                if room > 0 {
+                    let count = wave.per_spawn.min(wave.count - self.spawned).min(room);
+                    spawn_positions(&wave.pattern, count, rng, player_position, &mut self.positions);
+                    for position in self.positions.iter() {
+                        spawn_enemy(enemies, *position, player_position);
+                    }
+                    self.spawned += count;
+                }
+                self.count_down += wave.spawn_delay / wave.difficulty.at(self.wave_time);
+            }
+        } else if enemies.is_empty() {
+            events.push(WaveEvent::Cleared(self.wave_index));
+
+            self.wave_index += 1;
+            self.spawned = 0;
+            self.wave_time = 0.0;
+            self.count_down = self.definition().spawn_delay;
+
+            events.push(WaveEvent::Started(self.wave_index));
+        }
+    }
+
+    fn definition(&self) -> &WaveDefinition {
+        &self.waves[self.wave_index.min(self.waves.len() - 1)]
+    }
+}
+
+fn spawn_positions(pattern: &SpawnPattern, count: u32, rng: &mut impl Rng, player_position: Vec3, positions: &mut Vec<Vec3>) {
+    positions.clear();
+
+    match *pattern {
+        SpawnPattern::Ring { radius } => {
+            let start = (rng.gen::<f32>() * 360.0).to_radians();
+            for i in 0..count {
+                let theta = start + TAU * i as f32 / count as f32;
+                positions.push(around(player_position, theta, radius));
+            }
+        }
+        SpawnPattern::Cluster { distance, spread } => {
+            let center = around(player_position, rng.gen::<f32>() * TAU, distance);
+            for _ in 0..count {
+                let offset = around(Vec3::ZERO, rng.gen::<f32>() * TAU, spread * rng.gen::<f32>().sqrt());
+                positions.push(center + offset);
+            }
+        }
+        SpawnPattern::Line { distance, length } => {
+            let theta = rng.gen::<f32>() * TAU;
+            let center = around(player_position, theta, distance);
+            let across = vec3(theta.cos(), 0.0, -theta.sin());
+            for i in 0..count {
+                let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+                positions.push(center + across * (t * length));
+            }
+        }
+        SpawnPattern::ArenaEdge { half_size } => {
+            let side = rng.gen_range(0..4);
+            for _ in 0..count {
+                let along = rng.gen_range(-half_size..=half_size);
+                let (x, z) = match side {
+                    0 => (along, -half_size),
+                    1 => (along, half_size),
+                    2 => (-half_size, along),
+                    _ => (half_size, along),
+                };
+                positions.push(vec3(x, 0.0, z));
+            }
+        }
+    }
+}
+
+// point on the xz circle around center, same angle convention as the original spawner
+fn around(center: Vec3, theta: f32, radius: f32) -> Vec3 {
+    vec3(theta.sin().mul_add(radius, center.x), 0.0, theta.cos().mul_add(radius, center.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::waves::{WaveDirector, WaveEvent, WaveSet, WAVES_FILE};
+
+    #[test]
+    fn test_waves_file_parses() {
+        let wave_set = WaveSet::load(WAVES_FILE).unwrap();
+        assert!(!wave_set.waves.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_wave_is_rejected() {
+        let text = r#"(waves: [(name: "bad", count: 0, pattern: Ring(radius: 5.0), spawn_delay: 1.0)])"#;
+        assert!(WaveSet::parse(text).is_err());
+
+        let text = r#"(waves: [(name: "bad", count: 5, pattern: ArenaEdge(half_size: -8.0), spawn_delay: 1.0)])"#;
+        assert!(WaveSet::parse(text).is_err());
+
+        let text = r#"(waves: [(name: "bad", count: 5, pattern: Ring(radius: 5.0), spawn_delay: NaN)])"#;
+        assert!(WaveSet::parse(text).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_errors() {
+        let text = r#"(waves: [(name: "typo", count: 5, per_spwan: 2, pattern: Ring(radius: 5.0), spawn_delay: 1.0)])"#;
+        let error = WaveSet::parse(text).unwrap_err();
+        assert!(format!("{:#}", error).contains("per_spwan"), "{:#}", error);
+
+        let text = r#"(waves: [(name: "typo", count: 5, pattern: Ring(radius: 5.0, raduis: 5.0), spawn_delay: 1.0)])"#;
+        assert!(WaveSet::parse(text).is_err());
+
+        let text = r#"(max_enemy: 10, waves: [(name: "typo", count: 5, pattern: Ring(radius: 5.0), spawn_delay: 1.0)])"#;
+        assert!(WaveSet::parse(text).is_err());
+    }
+
+    #[test]
+    fn test_director_advances_when_wave_cleared() {
+        let text = r#"(waves: [
+            (name: "one", count: 3, per_spawn: 3, pattern: Line(distance: 8.0, length: 4.0), spawn_delay: 0.5),
+            (name: "two", count: 2, pattern: Cluster(distance: 8.0, spread: 1.0), spawn_delay: 0.5),
+        ])"#;
+        let mut director = WaveDirector::new(WaveSet::parse(text).unwrap());
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut enemies = vec![];
+        let mut events = vec![];
+
+        for _ in 0..40 {
+            director.update(1.0 / 60.0, &mut rng, &mut enemies, Vec3::ZERO, &mut events);
+        }
+
+        assert_eq!(enemies.len(), 3);
+        assert_eq!(director.current_wave(), 0);
+        assert_eq!(events, vec![WaveEvent::Started(0)]);
+
+        enemies.clear();
+        director.update(1.0 / 60.0, &mut rng, &mut enemies, Vec3::ZERO, &mut events);
+
+        assert_eq!(director.current_wave(), 1);
+        assert_eq!(director.current_wave_name(), "two");
+        assert_eq!(&events[1..], &[WaveEvent::Cleared(0), WaveEvent::Started(1)]);
+    }
+
+    #[test]
+    fn test_spawns_wait_for_room_under_max_enemies() {
+        let text = r#"(
+            max_enemies: 4,
+            waves: [(name: "horde", count: 10, per_spawn: 3, pattern: Ring(radius: 8.0), spawn_delay: 0.1)],
+        )"#;
+        let mut director = WaveDirector::new(WaveSet::parse(text).unwrap());
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut enemies = vec![];
+        let mut events = vec![];
+
+        for _ in 0..60 {
+            director.update(1.0 / 60.0, &mut rng, &mut enemies, Vec3::ZERO, &mut events);
+        }
+        assert_eq!(enemies.len(), 4);
+
+        // the rest of the wave arrives as enemies die
+        let mut spawned = enemies.len();
+        while spawned < 10 {
+            enemies.clear();
+            for _ in 0..60 {
+                director.update(1.0 / 60.0, &mut rng, &mut enemies, Vec3::ZERO, &mut events);
+            }
+            spawned += enemies.len();
+        }
+        assert_eq!(spawned, 10);
+        assert_eq!(director.current_wave(), 0);
+    }
+}
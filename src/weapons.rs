@@ -0,0 +1,214 @@
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use anyhow::Context;
+use glam::{vec3, Quat};
+use serde::Deserialize;
+
+//
+// Weapons are read from a RON file, see angrygl_assets/weapons.ron. The number keys pick a
+// weapon by its place in the file and the mouse wheel steps through them.
+//
+
+pub const WEAPONS_FILE: &str = "angrygl_assets/weapons.ron";
+
+/// How the bullets of one shot fan out around the aim direction.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum SpreadPattern {
+    /// `size` by `size` bullets, `spacing` degrees apart both across and up.
+    Square { size: u32, spacing: f32 },
+    /// `count` bullets side by side across `angle` degrees.
+    Fan { count: u32, angle: f32 },
+    Single,
+    /// `count` bullets evenly around a full circle.
+    Ring { count: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeaponDefinition {
+    pub name: String,
+    pub pattern: SpreadPattern,
+    /// Seconds between shots.
+    pub fire_interval: f32,
+    /// Game units per second.
+    pub bullet_speed: f32,
+    /// Seconds a shot's bullets live.
+    pub lifetime: f32,
+    pub bullet_texture: String,
+    pub muzzle_flash_texture: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeaponSet {
+    pub weapons: Vec<WeaponDefinition>,
+}
+
+/// A weapon ready to fire.
+#[derive(Debug, Clone)]
+pub struct Weapon {
+    pub definition: WeaponDefinition,
+    /// Rotation of each bullet of a shot relative to the aim direction.
+    pub bullet_rotations: Vec<Quat>,
+}
+
+/// Weapon change asked for by the player during a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponSwitch {
+    /// Index into the weapon list.
+    Select(u8),
+    Next,
+    Previous,
+}
+
+impl Default for WeaponDefinition {
+    /// The original 20 by 20 bullet blast.
+    fn default() -> Self {
+        Self {
+            name: String::from("Spreader"),
+            pattern: SpreadPattern::Square { size: 20, spacing: 3.0 },
+            fire_interval: 0.1,
+            bullet_speed: 5.0,
+            lifetime: 1.0,
+            bullet_texture: String::from("angrygl_assets/bullet/bullet_texture_transparent.png"),
+            muzzle_flash_texture: String::from("angrygl_assets/Player/muzzle_spritesheet.png"),
+        }
+    }
+}
+
+impl Default for WeaponSet {
+    fn default() -> Self {
+        Self {
+            weapons: vec![WeaponDefinition::default()],
+        }
+    }
+}
+
+impl WeaponSet {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading weapons file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Parsing weapons file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let weapon_set: WeaponSet = ron::from_str(text)?;
+        weapon_set.validate()?;
+        Ok(weapon_set)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.weapons.is_empty() {
+            return Err(anyhow::anyhow!("No weapons defined"));
+        }
+        for weapon in self.weapons.iter() {
+            let bullet_count = match weapon.pattern {
+                SpreadPattern::Square { size, .. } => size,
+                SpreadPattern::Fan { count, .. } | SpreadPattern::Ring { count } => count,
+                SpreadPattern::Single => 1,
+            };
+            if bullet_count == 0 {
+                return Err(anyhow::anyhow!("Weapon '{}' fires no bullets", weapon.name));
+            }
+            if weapon.fire_interval <= 0.0 || weapon.bullet_speed <= 0.0 || weapon.lifetime <= 0.0 {
+                return Err(anyhow::anyhow!("Weapon '{}' needs a fire_interval, bullet_speed and lifetime above zero", weapon.name));
+            }
+            // checked here so a typo falls back to the default weapon instead of failing texture loading
+            for texture in [&weapon.bullet_texture, &weapon.muzzle_flash_texture] {
+                if !Path::new(texture).is_file() {
+                    return Err(anyhow::anyhow!("Weapon '{}' texture {} not found", weapon.name, texture));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_weapons(self) -> Vec<Weapon> {
+        self.weapons.into_iter().map(Weapon::new).collect()
+    }
+}
+
+impl Weapon {
+    pub fn new(definition: WeaponDefinition) -> Self {
+        let bullet_rotations = definition.pattern.bullet_rotations();
+        Self { definition, bullet_rotations }
+    }
+}
+
+impl SpreadPattern {
+    pub fn bullet_rotations(&self) -> Vec<Quat> {
+        let around_y = |angle: f32| Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), angle);
+
+        match *self {
+            SpreadPattern::Square { size, spacing } => {
+                let size = size as i32;
+                let spacing = spacing.to_radians();
+                let spread_centering = spacing * (size as f32 - 1.0) / 4.0;
+                let offset = |i: i32| spacing.mul_add((i - size) as f32 / 2.0, spread_centering);
+
+                (0..size * size)
+                    .map(|count| {
+                        let y_rot = around_y(offset(count / size));
+                        let x_rot = Quat::from_axis_angle(vec3(1.0, 0.0, 0.0), offset(count % size));
+                        y_rot * x_rot
+                    })
+                    .collect()
+            }
+            SpreadPattern::Fan { count, angle } => {
+                let angle = angle.to_radians();
+                (0..count)
+                    .map(|i| {
+                        let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+                        around_y(angle * t)
+                    })
+                    .collect()
+            }
+            SpreadPattern::Single => vec![Quat::IDENTITY],
+            SpreadPattern::Ring { count } => (0..count).map(|i| around_y(TAU * i as f32 / count as f32)).collect(),
+        }
+    }
+}
+
+impl WeaponSwitch {
+    /// The weapon index after switching from `current`, of `count` weapons. Selecting a missing weapon keeps the current one.
+    pub fn apply(self, current: usize, count: usize) -> usize {
+        match self {
+            WeaponSwitch::Select(index) if (index as usize) < count => index as usize,
+            WeaponSwitch::Select(_) => current,
+            WeaponSwitch::Next => (current + 1) % count,
+            WeaponSwitch::Previous => (current + count - 1) % count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::weapons::{SpreadPattern, WeaponSet, WeaponSwitch, WEAPONS_FILE};
+
+    #[test]
+    fn test_weapons_file_parses() {
+        let weapon_set = WeaponSet::load(WEAPONS_FILE).unwrap();
+        assert!(weapon_set.weapons.len() > 1);
+    }
+
+    #[test]
+    fn test_patterns_fire_their_bullet_counts() {
+        assert_eq!(SpreadPattern::Square { size: 20, spacing: 3.0 }.bullet_rotations().len(), 400);
+        assert_eq!(SpreadPattern::Fan { count: 7, angle: 40.0 }.bullet_rotations().len(), 7);
+        assert_eq!(SpreadPattern::Single.bullet_rotations().len(), 1);
+        assert_eq!(SpreadPattern::Ring { count: 24 }.bullet_rotations().len(), 24);
+
+        let text = r#"(weapons: [(name: "empty", pattern: Ring(count: 0), fire_interval: 0.1, bullet_speed: 5.0, lifetime: 1.0,
+            bullet_texture: "angrygl_assets/bullet/bullet_texture_transparent.png", muzzle_flash_texture: "angrygl_assets/Player/muzzle_spritesheet.png")])"#;
+        assert!(WeaponSet::parse(text).is_err());
+        assert!(WeaponSet::parse(&text.replace("Ring(count: 0)", "Single")).is_ok());
+        assert!(WeaponSet::parse(&text.replace("Ring(count: 0)", "Single").replace("transparent.png", "transparnet.png")).is_err());
+    }
+
+    #[test]
+    fn test_switching_wraps_and_ignores_missing_weapons() {
+        assert_eq!(WeaponSwitch::Next.apply(2, 3), 0);
+        assert_eq!(WeaponSwitch::Previous.apply(0, 3), 2);
+        assert_eq!(WeaponSwitch::Select(1).apply(0, 3), 1);
+        assert_eq!(WeaponSwitch::Select(5).apply(2, 3), 2);
+    }
+}
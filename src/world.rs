@@ -12,21 +12,26 @@ use winit::keyboard::Key;
 
 use crate::bullets::BulletSystem;
 use crate::burn_marks::BurnMarks;
-use crate::enemy::{Enemy, EnemySystem};
+use crate::camera_rig::{CameraRig, CameraType};
+use crate::config::GameConfig;
+use crate::enemy::EnemySystem;
 use crate::floor::Floor;
-use crate::game_loop::CameraType;
+use crate::game_loop::VIEW_PORT_HEIGHT;
+use crate::game_phase::GamePhase;
+use crate::game_state::GameState;
 // use crate::params::floor_lighting::FloorLightingHandler;
 use crate::muzzle_flash::MuzzleFlash;
 use crate::params::shader_params::ShaderParametersHandler;
 use crate::player::Player;
+use crate::render::bullet_compute::BulletCompute;
 use crate::render::main_render::WorldRender;
+use crate::replay::ReplayMode;
+use crate::score::HighScores;
+use crate::timestep::FixedTimestep;
+use crate::weapons::WeaponSwitch;
 
-pub const FIRE_INTERVAL: f32 = 0.1;
-// seconds
-pub const SPREAD_AMOUNT: i32 = 20;
-pub const MAX_BULLET_GROUPS: i32 = 10;
-
-pub const PLAYER_COLLISION_RADIUS: f32 = 0.35;
+// groups alive at once with the default weapon, sizes the initial bullet storage
+pub const BULLET_GROUPS_IN_FLIGHT: i32 = 10;
 
 // Models
 pub const PLAYER_MODEL_SCALE: f32 = 0.0044;
@@ -37,22 +42,25 @@ pub const PLAYER_MODEL_GUN_MUZZLE_OFFSET: f32 = 100.0;
 // un-scaled
 pub const MONSTER_Y: f32 = PLAYER_MODEL_SCALE * PLAYER_MODEL_GUN_HEIGHT;
 
-// Lighting
-pub const LIGHT_FACTOR: f32 = 0.8;
-pub const NON_BLUE: f32 = 0.9;
+// Game camera clip planes, shadow cascades are fitted between them
+pub const GAME_CAMERA_NEAR: f32 = 0.1;
+pub const GAME_CAMERA_FAR: f32 = 100.0;
 
-pub const BLUR_SCALE: i32 = 2;
+// Half height of the top down and side views in world units, the width follows the window's aspect ratio
+pub const ORTHO_HALF_HEIGHT: f32 = VIEW_PORT_HEIGHT as f32 / 130.0;
 
-pub const FLOOR_LIGHT_FACTOR: f32 = 0.35;
-pub const FLOOR_NON_BLUE: f32 = 0.7;
+// Forward pass MSAA samples, lowered to what the adapter supports
+pub const MSAA_SAMPLE_COUNT: u32 = 4;
 
-// Enemies
-pub const MONSTER_SPEED: f32 = 0.6;
+// Emission is blurred at 1 / BLUR_SCALE of the output size
+pub const BLUR_SCALE: u32 = 2;
 
 pub struct World {
     pub camera_controller: FlyCameraController,
     pub camera_handler: CameraHandler,
-    pub camera_follow_vec: Vec3,
+    pub camera_rig: CameraRig,
+    // the loaded config file, the simulation and camera rig keep copies of their parts
+    pub config: GameConfig,
     pub run: bool,
     pub window_scale: (f32, f32),
     pub key_presses: HashSet<Key>,
@@ -66,10 +74,13 @@ pub struct World {
     pub start_instant: Instant,
     pub delta_time: f32,
     pub frame_time: f32,
+    pub timestep: FixedTimestep,
     pub first_mouse: bool,
     pub mouse_x: f32,
     pub mouse_y: f32,
     pub input: Input,
+    // number key or mouse wheel weapon change, applied on the next simulation tick
+    pub weapon_switch: Option<WeaponSwitch>,
     pub player: RefCell<Player>,
     // pub scene_render: RefCell<WorldRender>,
     pub shader_params: ShaderParametersHandler,
@@ -77,8 +88,14 @@ pub struct World {
     pub enemy_system: Rc<RefCell<EnemySystem>>,
     pub muzzle_flash: Rc<RefCell<MuzzleFlash>>,
     pub bullet_system: Rc<RefCell<BulletSystem>>,
-    pub enemies: Vec<Enemy>,
+    // None when the adapter can't run compute shaders
+    pub bullet_compute: Option<BulletCompute>,
+    pub use_gpu_bullets: bool,
     pub burn_marks: BurnMarks,
+    pub phase: GamePhase,
+    pub state: GameState,
+    pub high_scores: HighScores,
+    pub replay: ReplayMode,
     // pub sound_system: SoundSystem,
     pub light_direction: Vec3,
 }
@@ -94,6 +111,15 @@ impl World {
         self.frame_time = current_time;
     }
 
+    /// Rebuilds the camera projections for the window's aspect ratio.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.game_projection = Mat4::perspective_rh(self.game_camera.zoom.to_radians(), aspect_ratio, GAME_CAMERA_NEAR, GAME_CAMERA_FAR);
+        self.floating_projection = Mat4::perspective_rh(self.floating_camera.zoom.to_radians(), aspect_ratio, 0.1, 100.0);
+
+        let ortho_width = ORTHO_HALF_HEIGHT * aspect_ratio;
+        self.orthographic_projection = Mat4::orthographic_rh(-ortho_width, ortho_width, -ORTHO_HALF_HEIGHT, ORTHO_HALF_HEIGHT, 0.1, 100.0);
+    }
+
     pub fn handle_input(&mut self) {
         if let Some(mouse_position) = self.input.mouse_position {
             self.mouse_x = mouse_position.x;